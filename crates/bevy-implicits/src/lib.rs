@@ -14,12 +14,14 @@ use bevy::{
 };
 use planiscope::mesher::MesherInputs;
 
+pub use self::utils::{mass_properties_bundle, MATERIAL_PALETTE};
 use self::{inputs::*, loader::*, reader::*};
 
 pub mod prelude {
   pub use planiscope::{
+    mass::MassProperties,
     mesher::{MesherDetail, MesherInputs, MesherRegion},
-    shape::{builder, Shape},
+    shape::{builder, Bounds, Shape},
   };
 
   pub use crate::{
@@ -33,8 +35,10 @@ fn path_from_inputs(inputs: MesherInputs) -> Result<PathBuf> {
 }
 
 /// Generates a `bevy::asset::AssetPath` for a mesh generated from the given
-/// `MesherInputs`.
+/// `MesherInputs`. Fails if the inputs don't pass `MesherInputs::validate`, so
+/// that invalid shapes are never queued for loading.
 pub fn asset_path(inputs: MesherInputs) -> Result<AssetPath<'static>> {
+  inputs.validate()?;
   let path = path_from_inputs(inputs)?;
   Ok(AssetPath::from(path).with_source("implicit"))
 }
@@ -72,6 +76,8 @@ impl Plugin for ImplicitsPlugin {
       .init_asset::<ImplicitMesh>()
       .init_asset::<ColliderAsset>()
       .register_type::<ImplicitInputs>()
+      .register_type::<planiscope::shape::Shape>()
+      .register_type::<planiscope::shape::compound::Compound>()
       .register_asset_loader(ImplicitMeshAssetLoader)
       .add_systems(Update, sync_implicits)
       .add_systems(Update, sync_implicits_once);
@@ -92,7 +98,14 @@ fn sync_implicits(
   colliders: Res<Assets<ColliderAsset>>,
 ) {
   for (entity, inputs) in query.iter() {
-    let asset_path = asset_path(inputs.0.clone()).unwrap();
+    let asset_path = match asset_path(inputs.0.clone()) {
+      Ok(asset_path) => asset_path,
+      Err(e) => {
+        error!("not syncing implicit mesh for {entity:?}: {e}");
+        commands.entity(entity).remove::<SyncImplicits>();
+        continue;
+      }
+    };
     let handle: Handle<ImplicitMesh> = asset_server.load(asset_path);
 
     commands.entity(entity).insert(handle.clone());
@@ -128,7 +141,14 @@ fn sync_implicits_once(
   colliders: Res<Assets<ColliderAsset>>,
 ) {
   for (entity, inputs) in query.iter() {
-    let asset_path = asset_path(inputs.0.clone()).unwrap();
+    let asset_path = match asset_path(inputs.0.clone()) {
+      Ok(asset_path) => asset_path,
+      Err(e) => {
+        error!("not syncing implicit mesh for {entity:?}: {e}");
+        commands.entity(entity).remove::<SyncImplicitsOnce>();
+        continue;
+      }
+    };
     let handle: Handle<ImplicitMesh> = asset_server.load(asset_path);
 
     commands.entity(entity).insert(handle.clone());
@@ -167,6 +187,7 @@ mod tests {
         simplify: true,
       },
       gen_collider: true,
+      bindings:     Default::default(),
     });
     let path: PathBuf = inputs.clone().try_into().unwrap();
     let inputs2: ImplicitInputs = path.try_into().unwrap();
@@ -8,6 +8,7 @@ use bevy_xpbd_3d::components::Collider;
 use planiscope::{
   cache::{CacheProvider, DiskCacheProvider},
   mesher::FastSurfaceNetsMesher,
+  shape::ValidationReport,
 };
 use thiserror::Error;
 
@@ -23,6 +24,8 @@ pub(crate) struct ImplicitMeshAssetLoader;
 pub(crate) enum ImplicitMeshError {
   #[error("Failed to generate mesh: {0}")]
   MeshError(fidget::Error),
+  #[error("Invalid shape: {0}")]
+  InvalidShape(ValidationReport),
 }
 
 impl AssetLoader for ImplicitMeshAssetLoader {
@@ -40,6 +43,10 @@ impl AssetLoader for ImplicitMeshAssetLoader {
       let mut bytes = Vec::new();
       reader.read_to_end(&mut bytes).await.unwrap();
       let inputs: ImplicitInputs = bincode::deserialize(&bytes).unwrap();
+      inputs
+        .0
+        .validate()
+        .map_err(ImplicitMeshError::InvalidShape)?;
 
       let (mesh, collider) =
         DiskCacheProvider::<FastSurfaceNetsMesher>::default()
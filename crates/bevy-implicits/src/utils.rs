@@ -1,7 +1,58 @@
 use bevy::prelude::*;
-use planiscope::mesher::BufMesh;
+use bevy_xpbd_3d::components::{
+  CenterOfMass, Inertia, InverseInertia, InverseMass, Mass,
+  MassPropertiesBundle,
+};
+use planiscope::{mass::MassProperties, mesher::BufMesh};
 
-/// Converts a `planiscope::mesher::FullMesh` to a `bevy::render::mesh::Mesh`.
+/// The vertex colors, in linear RGBA, given to each material ID by
+/// `bevy_mesh_from_pls_mesh`. IDs past the end wrap around. Material 0 is
+/// white, so untagged surfaces keep their material's base color.
+pub const MATERIAL_PALETTE: [[f32; 4]; 8] = [
+  // default
+  [1.0, 1.0, 1.0, 1.0],
+  // grass
+  [0.12, 0.35, 0.05, 1.0],
+  // rock
+  [0.25, 0.24, 0.22, 1.0],
+  // brick
+  [0.45, 0.09, 0.05, 1.0],
+  // sand
+  [0.75, 0.62, 0.38, 1.0],
+  // dirt
+  [0.2, 0.1, 0.04, 1.0],
+  // snow
+  [0.9, 0.92, 0.95, 1.0],
+  // water
+  [0.02, 0.12, 0.35, 1.0],
+];
+
+/// Builds the `bevy_xpbd_3d` mass properties of a body made of a shape with
+/// the given `planiscope::mass::MassProperties`, at `density`. Empty shapes
+/// get zero inverse mass and inertia rather than infinite ones.
+pub fn mass_properties_bundle(
+  props: &MassProperties,
+  density: f32,
+) -> MassPropertiesBundle {
+  let mass = props.mass(density);
+  let inertia = props.inertia(density);
+  let (inverse_mass, inverse_inertia) = if mass > 0.0 {
+    (mass.recip(), inertia.inverse())
+  } else {
+    (0.0, Mat3::ZERO)
+  };
+  MassPropertiesBundle {
+    mass:            Mass(mass),
+    inverse_mass:    InverseMass(inverse_mass),
+    inertia:         Inertia(inertia),
+    inverse_inertia: InverseInertia(inverse_inertia),
+    center_of_mass:  CenterOfMass(props.centroid.into()),
+  }
+}
+
+/// Converts a `planiscope::mesher::BufMesh` to a `bevy::render::mesh::Mesh`.
+/// If the mesh has materials, they're written to `Mesh::ATTRIBUTE_COLOR`
+/// using `MATERIAL_PALETTE`.
 pub fn bevy_mesh_from_pls_mesh(mesh: BufMesh) -> Mesh {
   let mut bevy_mesh =
     Mesh::new(bevy::render::render_resource::PrimitiveTopology::TriangleList);
@@ -22,6 +73,16 @@ pub fn bevy_mesh_from_pls_mesh(mesh: BufMesh) -> Mesh {
       .map(|v| v.to_array())
       .collect::<Vec<_>>(),
   );
+  if !mesh.materials.is_empty() {
+    bevy_mesh.insert_attribute(
+      Mesh::ATTRIBUTE_COLOR,
+      mesh
+        .materials
+        .into_iter()
+        .map(|id| MATERIAL_PALETTE[id as usize % MATERIAL_PALETTE.len()])
+        .collect::<Vec<_>>(),
+    );
+  }
 
   bevy_mesh.set_indices(Some(bevy::render::mesh::Indices::U32(
     mesh
@@ -49,6 +49,8 @@ impl Primitive for Brick {
   }
   // source: https://www.engineeringtoolbox.com/bricks-density-d_1777.html
   fn density(&self) -> ColliderDensity { ColliderDensity(1765.0) }
+  // bricks are laid loose, so walls can be knocked down
+  fn rigid_body(&self) -> RigidBody { RigidBody::Dynamic }
   fn material(&self) -> ToonMaterial {
     ToonMaterial {
       base:      StandardMaterial {
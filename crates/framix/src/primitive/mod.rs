@@ -4,11 +4,18 @@ pub mod brick;
 pub mod concrete;
 pub mod plank;
 
+use std::{
+  collections::hash_map::DefaultHasher,
+  hash::{Hash, Hasher},
+};
+
 use bevy::{
   prelude::{Transform, *},
   render::primitives::Aabb,
+  utils::HashMap,
 };
 use bevy_implicits::{
+  mass_properties_bundle,
   prelude::{builder as sb, *},
   SyncImplicitsOnce,
 };
@@ -20,6 +27,16 @@ use common::materials::{ToonExtension, ToonMaterial};
 pub use self::{brick::Brick, concrete::ConcreteBlock, plank::Plank};
 use crate::{find_or_add::FindOrAdd, spawnable::Spawnable};
 
+/// The most that the volume used for a primitive's mass can be off by, as a
+/// fraction of the volume of its [`Aabb`].
+const MASS_VOLUME_ERROR: f32 = 0.01;
+
+/// Mass properties already estimated for primitives, by a hash of their
+/// shape and [`Aabb`]. Every brick in a wall has the same shape, so only the
+/// first one spawned pays for the estimate.
+#[derive(Resource, Default)]
+struct PrimitiveMasses(HashMap<u64, Option<MassProperties>>);
+
 /// A trait for physical definitions of a physical building primitive.
 pub trait Primitive: Spawnable<SpawnContext = (Entity, Transform)> {
   /// The [`Aabb`] of the primitive.
@@ -41,6 +58,33 @@ pub trait Primitive: Spawnable<SpawnContext = (Entity, Transform)> {
   fn material(&self) -> ToonMaterial;
   /// The density properties of the primitive.
   fn density(&self) -> ColliderDensity;
+  /// The [`RigidBody`] of the primitive. Primitives are static unless they
+  /// say otherwise. Mass properties are only estimated for dynamic bodies,
+  /// since static and kinematic ones ignore mass.
+  fn rigid_body(&self) -> RigidBody { RigidBody::Static }
+  /// The mass properties of the primitive at a density of one, estimated from
+  /// its `shape` within its `aabb`. Returns `None` if the shape can't be
+  /// evaluated, in which case the mass comes from the collider instead.
+  ///
+  /// This evaluates the shape many times over, so spawning caches the result
+  /// for each shape.
+  fn mass_properties(&self) -> Option<MassProperties> {
+    let aabb = self.aabb();
+    let region = Bounds {
+      min: aabb.min(),
+      max: aabb.max(),
+    };
+    let max_error =
+      (aabb.half_extents * 2.0).to_array().iter().product::<f32>()
+        * MASS_VOLUME_ERROR;
+    match self.shape().mass_properties(region, max_error) {
+      Ok(props) => Some(props),
+      Err(e) => {
+        warn!("failed to estimate primitive mass from its shape: {e}");
+        None
+      }
+    }
+  }
   /// The friction properties of the primitive.
   fn friction(&self) -> Friction;
   /// The restitution properties of the primitive.
@@ -61,6 +105,27 @@ impl<T: Primitive> Spawnable for T {
 
     let collider_attempt = self.collider();
     let aabb = self.aabb();
+    let rigid_body = self.rigid_body();
+    let mass_properties = match rigid_body {
+      RigidBody::Dynamic => {
+        let mut hasher = DefaultHasher::new();
+        self.shape().hash(&mut hasher);
+        for v in aabb
+          .center
+          .to_array()
+          .into_iter()
+          .chain(aabb.half_extents.to_array())
+        {
+          v.to_bits().hash(&mut hasher);
+        }
+        *world
+          .get_resource_or_insert_with(PrimitiveMasses::default)
+          .0
+          .entry(hasher.finish())
+          .or_insert_with(|| self.mass_properties())
+      }
+      RigidBody::Static | RigidBody::Kinematic => None,
+    };
 
     world.entity_mut(parent).with_children(|p| {
       let mut entity = p.spawn((
@@ -76,10 +141,10 @@ impl<T: Primitive> Spawnable for T {
             simplify: false,
           },
           gen_collider: collider_attempt.is_none(),
+          bindings:     Default::default(),
         }),
         SyncImplicitsOnce,
-        RigidBody::Static,
-        self.density(),
+        rigid_body,
         self.friction(),
         self.restitution(),
         Name::new("building_primitive"),
@@ -87,6 +152,48 @@ impl<T: Primitive> Spawnable for T {
       if let Some(collider) = collider_attempt {
         entity.insert(collider);
       }
+      match mass_properties {
+        // the shape's mass replaces the collider's, which ignores any holes
+        Some(props) => {
+          let bundle = mass_properties_bundle(&props, self.density().0);
+          entity.insert((bundle, ColliderDensity(0.0)));
+        }
+        None => {
+          entity.insert(self.density());
+        }
+      }
     });
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use bevy_xpbd_3d::components::Mass;
+
+  use super::*;
+
+  /// Spawns `primitive` into a fresh world, returning the world and the
+  /// primitive's entity.
+  fn spawn(primitive: impl Primitive) -> (World, Entity) {
+    let mut world = World::new();
+    world.init_resource::<Assets<ToonMaterial>>();
+    let parent = world.spawn_empty().id();
+    primitive.spawn(&mut world, (parent, Transform::default()));
+    let child = world.get::<Children>(parent).unwrap()[0];
+    (world, child)
+  }
+
+  #[test]
+  fn dynamic_primitives_get_their_shapes_mass() {
+    let (world, brick) = spawn(Brick::default());
+    let solid = 0.2 * 0.05 * 0.1 * 1765.0;
+    let mass = world.get::<Mass>(brick).expect("bricks are dynamic").0;
+    // the holes take about a fifth of the solid's mass away
+    assert!(0.0 < mass && mass < solid * 0.9, "{mass} of {solid}");
+    assert_eq!(world.get::<ColliderDensity>(brick).unwrap().0, 0.0);
+
+    let (world, block) = spawn(ConcreteBlock::default());
+    assert!(world.get::<Mass>(block).is_none());
+    assert_eq!(world.get::<ColliderDensity>(block).unwrap().0, 2400.0);
+  }
+}
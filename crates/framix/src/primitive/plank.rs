@@ -24,6 +24,7 @@ impl Primitive for Plank {
   }
   // https://www.engineeringtoolbox.com/wood-density-d_40.html
   fn density(&self) -> ColliderDensity { ColliderDensity(790.0) }
+  fn rigid_body(&self) -> RigidBody { RigidBody::Dynamic }
   fn material(&self) -> ToonMaterial {
     ToonMaterial {
       base:      StandardMaterial {
@@ -15,14 +15,17 @@ pub struct TerrainDetailTarget;
 
 #[derive(Resource, Reflect)]
 #[reflect(Resource)]
-pub struct TerrainCurrentShape(#[reflect(ignore)] pub Shape);
+pub struct TerrainCurrentShape(pub Shape);
 
 impl Default for TerrainCurrentShape {
   fn default() -> Self {
-    TerrainCurrentShape(Shape::new_expr(
-      "(sqrt(square(x) + square(y + 5000) + square(z)) - 5000) + ((sin(x / \
-       20.0) + sin(y / 20.0) + sin(z / 20.0)) * 4.0)",
-    ))
+    TerrainCurrentShape(
+      Shape::parse(
+        "(sqrt(square(x) + square(y + 5000) + square(z)) - 5000) + fbm(0, 4, \
+         0.05, 2.0, 0.5) * 8.0",
+      )
+      .expect("default terrain expression is valid"),
+    )
   }
 }
 
@@ -176,9 +179,16 @@ fn create_generation(
       shape: shape.0.clone(),
       region,
       gen_collider: true,
+      bindings: Default::default(),
+    };
+    // the shape can be edited live in the inspector, so it may not be valid
+    let path = match bevy_implicits::asset_path(inputs) {
+      Ok(path) => path,
+      Err(e) => {
+        error!("not generating terrain for the current shape: {e}");
+        return;
+      }
     };
-    let path =
-      bevy_implicits::asset_path(inputs).expect("failed to get mesh path");
 
     let handle: Handle<ImplicitMesh> = asset_server.load(path);
     commands.spawn((
@@ -11,9 +11,29 @@ pub struct BufMesh {
   pub positions: Vec<glam::Vec3A>,
   /// The triangle indices of the mesh.
   pub triangles: Vec<glam::UVec3>,
+  /// The material ID of each vertex. Empty if the mesh has no materials.
+  #[serde(default)]
+  pub materials: Vec<u32>,
 }
 
 impl BufMesh {
+  /// Pairs up the per-vertex buffers into `FullVertex`es. Vertices get
+  /// material 0 if the mesh has no materials.
+  pub fn full_vertices(&self) -> Vec<FullVertex> {
+    let materials = self.materials.iter().copied().chain(std::iter::repeat(0));
+    self
+      .positions
+      .iter()
+      .zip(self.normals.iter())
+      .zip(materials)
+      .map(|((p, n), m)| FullVertex {
+        position: *p,
+        normal:   *n,
+        material: m,
+      })
+      .collect()
+  }
+
   /// Transforms the mesh to the desired translation and scale.
   ///
   /// `mesh_new()` produces a mesh only between -1 and 1 on all axes.
@@ -56,8 +76,14 @@ pub struct FullVertex {
   pub position: glam::Vec3A,
   /// The normal of the vertex.
   pub normal:   glam::Vec3A,
+  /// The material ID of the vertex.
+  pub material: u32,
 }
 
 impl VertexData for FullVertex {
   fn pos(&self) -> glam::Vec3A { self.position }
+
+  fn can_merge_with(&self, other: &Self) -> bool {
+    self.material == other.material
+  }
 }
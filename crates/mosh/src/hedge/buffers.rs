@@ -152,20 +152,12 @@ impl<D: VertexData> HedgeMesh<D> {
 impl HedgeMesh<FullVertex> {
   /// Creates a `HedgeMesh<FullVertex>` from a `BufMesh`.
   pub fn from_bufmesh(mesh: BufMesh) -> Self {
+    let vertices = mesh.full_vertices();
     let triangles = mesh
       .triangles
       .into_iter()
       .map(|t| (t.x as usize, t.y as usize, t.z as usize))
       .collect::<Vec<_>>();
-    let vertices = mesh
-      .positions
-      .into_iter()
-      .zip(mesh.normals)
-      .map(|(p, n)| FullVertex {
-        position: p,
-        normal:   n,
-      })
-      .collect::<Vec<_>>();
 
     Self::from_buffers(&triangles, vertices.as_slice())
   }
@@ -176,6 +168,7 @@ impl HedgeMesh<FullVertex> {
 
     let positions = vertices.iter().map(|v| v.position).collect::<Vec<_>>();
     let normals = vertices.iter().map(|v| v.normal).collect::<Vec<_>>();
+    let materials = vertices.iter().map(|v| v.material).collect::<Vec<_>>();
 
     BufMesh {
       positions,
@@ -184,6 +177,7 @@ impl HedgeMesh<FullVertex> {
         .iter()
         .map(|(a, b, c)| glam::UVec3::new(*a as u32, *b as u32, *c as u32))
         .collect::<Vec<_>>(),
+      materials,
     }
   }
 }
@@ -70,6 +70,20 @@ impl<D: VertexData> MizuMesh<D> {
     })
   }
 
+  /// Returns true if every vertex of the given faces can be merged with the
+  /// others (see [`VertexData::can_merge_with`]), so that replacing the faces
+  /// doesn't erase a boundary between them.
+  pub fn faces_can_merge(&self, faces: &[u32]) -> bool {
+    let mut vertices = faces
+      .iter()
+      .flat_map(|face| self.face(*face).vertices().to_array());
+    let Some(first) = vertices.next() else {
+      return true;
+    };
+    let first = self.vertex(first).data();
+    vertices.all(|vertex| first.can_merge_with(self.vertex(vertex).data()))
+  }
+
   /// Creates a new face using vertex info but does not add it to the mesh.
   pub fn create_face(
     &self,
@@ -108,6 +108,10 @@ impl<D: VertexData> MizuMesh<D> {
         {
           return None;
         }
+        // make sure merging doesn't erase a boundary, like between materials
+        if !self.faces_can_merge(&[tri_1, tri_2, tri_3, tri_4]) {
+          return None;
+        }
         // make sure the faces are only neighbors in the correct ways
         // i.e 1 and 2 are neighbors, 2 and 3 are neighbors, 3 and 4 are
         // neighbors
@@ -171,8 +175,11 @@ impl<D: VertexData> MizuMesh<D> {
         let tri_1 = index;
         let (tri_2, tri_2_start_pair) = neighbor.unwrap();
 
-        // make sure the faces are coplanar
-        if !self.face(tri_1).are_coplanar(self.face(tri_2)) {
+        // make sure the faces are coplanar, and that merging doesn't erase a
+        // boundary
+        if !self.face(tri_1).are_coplanar(self.face(tri_2))
+          || !self.faces_can_merge(&[tri_1, tri_2])
+        {
           return None;
         }
 
@@ -7,6 +7,12 @@
 pub trait VertexData: Clone + Sync {
   /// Returns the position of the vertex.
   fn pos(&self) -> glam::Vec3A;
+
+  /// Returns whether faces between this vertex and `other` can be merged
+  /// without losing anything, i.e. whether the vertices differ in a way that
+  /// has to stay visible, like a material boundary. This should be an
+  /// equivalence. Defaults to true.
+  fn can_merge_with(&self, _other: &Self) -> bool { true }
 }
 
 /// A vertex in a [`MizuMesh`](super::MizuMesh).
@@ -1,23 +1,13 @@
 use tracing::info_span;
 
-use crate::{
-  bufmesh::{BufMesh, FullVertex},
-  mizu::MizuMesh,
-};
+use crate::{bufmesh::BufMesh, mizu::MizuMesh};
 
 /// Simplifies a mesh by merging coplanar faces.
 pub fn simplify_mesh(mesh: BufMesh) -> BufMesh {
   let _span = info_span!("mosh::simplify_mesh::from_buffer").entered();
 
-  let vertices = mesh
-    .positions
-    .iter()
-    .zip(mesh.normals.iter())
-    .map(|(p, n)| FullVertex {
-      position: *p,
-      normal:   *n,
-    })
-    .collect::<Vec<_>>();
+  let has_materials = !mesh.materials.is_empty();
+  let vertices = mesh.full_vertices();
   let mut mizu = MizuMesh::from_buffers(&vertices, &mesh.triangles);
 
   drop(_span);
@@ -33,6 +23,11 @@ pub fn simplify_mesh(mesh: BufMesh) -> BufMesh {
     positions: vertices.iter().map(|v| v.position).collect::<Vec<_>>(),
     normals:   vertices.iter().map(|v| v.normal).collect::<Vec<_>>(),
     triangles: faces,
+    materials: if has_materials {
+      vertices.iter().map(|v| v.material).collect::<Vec<_>>()
+    } else {
+      Vec::new()
+    },
   };
   mesh
 }
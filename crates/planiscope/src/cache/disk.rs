@@ -1,7 +1,5 @@
 use std::{
-  collections::hash_map::DefaultHasher,
   fs::File,
-  hash::{Hash, Hasher},
   io::{BufReader, BufWriter},
   path::PathBuf,
 };
@@ -10,18 +8,12 @@ use parry3d::shape::SharedShape;
 use serde::{Deserialize, Serialize};
 use tracing::info_span;
 
-use super::{CacheProvider, DiskCacheProvider};
+use super::{key::cache_key, CacheProvider, DiskCacheProvider};
 use crate::{
   collider::{generate_collider, ColliderSettings},
   mesher::{Mesher, MesherInputs},
 };
 
-fn hash_single<H: Hash>(value: &H) -> u64 {
-  let mut hasher = DefaultHasher::new();
-  value.hash(&mut hasher);
-  hasher.finish()
-}
-
 fn serialize_to_file<V: Serialize>(path: &str, value: &V) -> Option<String> {
   let _span = info_span!("planiscope::serialize_to_file").entered();
 
@@ -49,9 +41,9 @@ impl<M: Mesher> CacheProvider for DiskCacheProvider<M> {
   ) -> Result<crate::mesher::BufMesh, fidget::Error> {
     let _span = info_span!("planiscope::get_mesh").entered();
 
-    // get the hash and resulting path
-    let inputs_hash = hash_single(inputs);
-    let path = format!("{}{}", self.mesh_path, inputs_hash);
+    // get the key and resulting path
+    let inputs_key = cache_key(inputs);
+    let path = format!("{}{:032x}", self.mesh_path, inputs_key);
 
     // try to open the file
     if let Some(file) = deserialize_from_file(&path) {
@@ -80,9 +72,9 @@ impl<M: Mesher> CacheProvider for DiskCacheProvider<M> {
       return None;
     }
 
-    // get the hash and resulting path
-    let inputs_hash = hash_single(inputs);
-    let path = format!("{}{}", self.mesh_path, inputs_hash);
+    // get the key and resulting path
+    let inputs_key = cache_key(inputs);
+    let path = format!("{}{:032x}", self.collider_path, inputs_key);
 
     // try to open the file
     if let Some(collider) = deserialize_from_file(&path) {
@@ -111,8 +103,8 @@ impl<M: Mesher> CacheProvider for DiskCacheProvider<M> {
 
     let mesh = self.get_mesh(inputs);
 
-    let inputs_hash = hash_single(inputs);
-    let path = format!("{}{}", self.collider_path, inputs_hash);
+    let inputs_key = cache_key(inputs);
+    let path = format!("{}{:032x}", self.collider_path, inputs_key);
 
     if !inputs.gen_collider {
       return (mesh, None);
@@ -0,0 +1,418 @@
+//! A canonical text encoding of serializable values, for cache keys.
+//!
+//! Every float is written as its exact bit pattern, so the encoding doesn't
+//! depend on how a toolchain chooses to format floats. Strings carry their
+//! length, so no escaping is needed, and everything else is written in a
+//! fixed, compact syntax:
+//!
+//! - floats: `f` or `d` and the hex bits of the `f32` or `f64`
+//! - strings: `s`, the byte length, `:` and the string
+//! - sequences and tuples: `[a,b]`; maps: `{k:v}`
+//! - enum variants and structs: `Name`, `Name(a,b)` or `Name{field:a}`
+
+use std::fmt::{self, Write};
+
+use serde::{ser, Serialize};
+
+/// Encodes `value`. Writing to a string can't fail and the encoding has no
+/// errors of its own, so this only fails if `value`'s `Serialize`
+/// implementation does.
+pub fn encode<T: Serialize + ?Sized>(value: &T) -> Result<String, EncodeError> {
+  let mut encoder = Encoder { out: String::new() };
+  value.serialize(&mut encoder)?;
+  Ok(encoder.out)
+}
+
+/// An error reported by a value's `Serialize` implementation.
+#[derive(Debug)]
+pub struct EncodeError(String);
+
+impl fmt::Display for EncodeError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "failed to encode value: {}", self.0)
+  }
+}
+
+impl std::error::Error for EncodeError {}
+
+impl ser::Error for EncodeError {
+  fn custom<T: fmt::Display>(msg: T) -> Self { EncodeError(msg.to_string()) }
+}
+
+struct Encoder {
+  out: String,
+}
+
+impl Encoder {
+  fn push(&mut self, s: &str) { self.out.push_str(s); }
+
+  fn open(&mut self, name: &str, open: char, close: char) -> Group<'_> {
+    self.push(name);
+    self.out.push(open);
+    Group {
+      encoder: self,
+      first: true,
+      close,
+    }
+  }
+}
+
+/// An open sequence, tuple, map or struct, which writes its separators and
+/// closes itself.
+struct Group<'a> {
+  encoder: &'a mut Encoder,
+  first:   bool,
+  close:   char,
+}
+
+impl Group<'_> {
+  fn separate(&mut self) {
+    if !self.first {
+      self.encoder.out.push(',');
+    }
+    self.first = false;
+  }
+
+  fn element<T: Serialize + ?Sized>(
+    &mut self,
+    value: &T,
+  ) -> Result<(), EncodeError> {
+    self.separate();
+    value.serialize(&mut *self.encoder)
+  }
+
+  fn field<T: Serialize + ?Sized>(
+    &mut self,
+    key: &str,
+    value: &T,
+  ) -> Result<(), EncodeError> {
+    self.separate();
+    self.encoder.push(key);
+    self.encoder.out.push(':');
+    value.serialize(&mut *self.encoder)
+  }
+
+  fn finish(self) -> Result<(), EncodeError> {
+    self.encoder.out.push(self.close);
+    Ok(())
+  }
+}
+
+macro_rules! encode_display {
+  ($($method:ident: $ty:ty),*) => {
+    $(fn $method(self, v: $ty) -> Result<(), EncodeError> {
+      // writing to a `String` can't fail
+      let _ = write!(self.out, "{v}");
+      Ok(())
+    })*
+  };
+}
+
+impl<'a> ser::Serializer for &'a mut Encoder {
+  type Ok = ();
+  type Error = EncodeError;
+  type SerializeSeq = Group<'a>;
+  type SerializeTuple = Group<'a>;
+  type SerializeTupleStruct = Group<'a>;
+  type SerializeTupleVariant = Group<'a>;
+  type SerializeMap = Group<'a>;
+  type SerializeStruct = Group<'a>;
+  type SerializeStructVariant = Group<'a>;
+
+  encode_display!(
+    serialize_bool: bool,
+    serialize_i8: i8,
+    serialize_i16: i16,
+    serialize_i32: i32,
+    serialize_i64: i64,
+    serialize_i128: i128,
+    serialize_u8: u8,
+    serialize_u16: u16,
+    serialize_u32: u32,
+    serialize_u64: u64,
+    serialize_u128: u128
+  );
+
+  fn serialize_f32(self, v: f32) -> Result<(), EncodeError> {
+    let _ = write!(self.out, "f{:08x}", v.to_bits());
+    Ok(())
+  }
+
+  fn serialize_f64(self, v: f64) -> Result<(), EncodeError> {
+    let _ = write!(self.out, "d{:016x}", v.to_bits());
+    Ok(())
+  }
+
+  fn serialize_char(self, v: char) -> Result<(), EncodeError> {
+    self.serialize_str(v.encode_utf8(&mut [0; 4]))
+  }
+
+  fn serialize_str(self, v: &str) -> Result<(), EncodeError> {
+    let _ = write!(self.out, "s{}:{v}", v.len());
+    Ok(())
+  }
+
+  fn serialize_bytes(self, v: &[u8]) -> Result<(), EncodeError> {
+    self.push("b");
+    for byte in v {
+      let _ = write!(self.out, "{byte:02x}");
+    }
+    self.push(";");
+    Ok(())
+  }
+
+  fn serialize_none(self) -> Result<(), EncodeError> {
+    self.push("None");
+    Ok(())
+  }
+
+  fn serialize_some<T: Serialize + ?Sized>(
+    self,
+    value: &T,
+  ) -> Result<(), EncodeError> {
+    let mut group = self.open("Some", '(', ')');
+    group.element(value)?;
+    group.finish()
+  }
+
+  fn serialize_unit(self) -> Result<(), EncodeError> {
+    self.push("()");
+    Ok(())
+  }
+
+  fn serialize_unit_struct(
+    self,
+    name: &'static str,
+  ) -> Result<(), EncodeError> {
+    self.push(name);
+    Ok(())
+  }
+
+  fn serialize_unit_variant(
+    self,
+    _name: &'static str,
+    _index: u32,
+    variant: &'static str,
+  ) -> Result<(), EncodeError> {
+    self.push(variant);
+    Ok(())
+  }
+
+  fn serialize_newtype_struct<T: Serialize + ?Sized>(
+    self,
+    name: &'static str,
+    value: &T,
+  ) -> Result<(), EncodeError> {
+    let mut group = self.open(name, '(', ')');
+    group.element(value)?;
+    group.finish()
+  }
+
+  fn serialize_newtype_variant<T: Serialize + ?Sized>(
+    self,
+    _name: &'static str,
+    _index: u32,
+    variant: &'static str,
+    value: &T,
+  ) -> Result<(), EncodeError> {
+    let mut group = self.open(variant, '(', ')');
+    group.element(value)?;
+    group.finish()
+  }
+
+  fn serialize_seq(
+    self,
+    _len: Option<usize>,
+  ) -> Result<Group<'a>, EncodeError> {
+    Ok(self.open("", '[', ']'))
+  }
+
+  fn serialize_tuple(self, _len: usize) -> Result<Group<'a>, EncodeError> {
+    Ok(self.open("", '[', ']'))
+  }
+
+  fn serialize_tuple_struct(
+    self,
+    name: &'static str,
+    _len: usize,
+  ) -> Result<Group<'a>, EncodeError> {
+    Ok(self.open(name, '(', ')'))
+  }
+
+  fn serialize_tuple_variant(
+    self,
+    _name: &'static str,
+    _index: u32,
+    variant: &'static str,
+    _len: usize,
+  ) -> Result<Group<'a>, EncodeError> {
+    Ok(self.open(variant, '(', ')'))
+  }
+
+  fn serialize_map(
+    self,
+    _len: Option<usize>,
+  ) -> Result<Group<'a>, EncodeError> {
+    Ok(self.open("", '{', '}'))
+  }
+
+  fn serialize_struct(
+    self,
+    name: &'static str,
+    _len: usize,
+  ) -> Result<Group<'a>, EncodeError> {
+    Ok(self.open(name, '{', '}'))
+  }
+
+  fn serialize_struct_variant(
+    self,
+    _name: &'static str,
+    _index: u32,
+    variant: &'static str,
+    _len: usize,
+  ) -> Result<Group<'a>, EncodeError> {
+    Ok(self.open(variant, '{', '}'))
+  }
+}
+
+impl ser::SerializeSeq for Group<'_> {
+  type Ok = ();
+  type Error = EncodeError;
+
+  fn serialize_element<T: Serialize + ?Sized>(
+    &mut self,
+    value: &T,
+  ) -> Result<(), EncodeError> {
+    self.element(value)
+  }
+
+  fn end(self) -> Result<(), EncodeError> { self.finish() }
+}
+
+impl ser::SerializeTuple for Group<'_> {
+  type Ok = ();
+  type Error = EncodeError;
+
+  fn serialize_element<T: Serialize + ?Sized>(
+    &mut self,
+    value: &T,
+  ) -> Result<(), EncodeError> {
+    self.element(value)
+  }
+
+  fn end(self) -> Result<(), EncodeError> { self.finish() }
+}
+
+impl ser::SerializeTupleStruct for Group<'_> {
+  type Ok = ();
+  type Error = EncodeError;
+
+  fn serialize_field<T: Serialize + ?Sized>(
+    &mut self,
+    value: &T,
+  ) -> Result<(), EncodeError> {
+    self.element(value)
+  }
+
+  fn end(self) -> Result<(), EncodeError> { self.finish() }
+}
+
+impl ser::SerializeTupleVariant for Group<'_> {
+  type Ok = ();
+  type Error = EncodeError;
+
+  fn serialize_field<T: Serialize + ?Sized>(
+    &mut self,
+    value: &T,
+  ) -> Result<(), EncodeError> {
+    self.element(value)
+  }
+
+  fn end(self) -> Result<(), EncodeError> { self.finish() }
+}
+
+impl ser::SerializeMap for Group<'_> {
+  type Ok = ();
+  type Error = EncodeError;
+
+  fn serialize_key<T: Serialize + ?Sized>(
+    &mut self,
+    key: &T,
+  ) -> Result<(), EncodeError> {
+    self.element(key)
+  }
+
+  fn serialize_value<T: Serialize + ?Sized>(
+    &mut self,
+    value: &T,
+  ) -> Result<(), EncodeError> {
+    self.encoder.out.push(':');
+    value.serialize(&mut *self.encoder)
+  }
+
+  fn end(self) -> Result<(), EncodeError> { self.finish() }
+}
+
+impl ser::SerializeStruct for Group<'_> {
+  type Ok = ();
+  type Error = EncodeError;
+
+  fn serialize_field<T: Serialize + ?Sized>(
+    &mut self,
+    key: &'static str,
+    value: &T,
+  ) -> Result<(), EncodeError> {
+    self.field(key, value)
+  }
+
+  fn end(self) -> Result<(), EncodeError> { self.finish() }
+}
+
+impl ser::SerializeStructVariant for Group<'_> {
+  type Ok = ();
+  type Error = EncodeError;
+
+  fn serialize_field<T: Serialize + ?Sized>(
+    &mut self,
+    key: &'static str,
+    value: &T,
+  ) -> Result<(), EncodeError> {
+    self.field(key, value)
+  }
+
+  fn end(self) -> Result<(), EncodeError> { self.finish() }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::shape::builder;
+
+  #[test]
+  fn floats_are_written_as_bits() {
+    let shape = builder::add(builder::x(), 0.1);
+    assert_eq!(
+      encode(&shape).unwrap(),
+      "Add(XNode,Constant(d3fb999999999999a))"
+    );
+    assert_eq!(encode(&[1.0_f32, -0.0]).unwrap(), "[f3f800000,f80000000]");
+  }
+
+  #[test]
+  fn distinct_values_encode_distinctly() {
+    let encodings = [
+      builder::sphere(builder::var("radius")),
+      builder::sphere(builder::x()),
+      builder::sphere(builder::expr("radius")),
+      builder::sphere(f64::INFINITY),
+      builder::sphere(f64::NEG_INFINITY),
+    ]
+    .map(|shape| encode(&shape).unwrap());
+    for (i, a) in encodings.iter().enumerate() {
+      for b in &encodings[i + 1..] {
+        assert_ne!(a, b);
+      }
+    }
+    assert_eq!(encode("a,b").unwrap(), "s3:a,b");
+  }
+}
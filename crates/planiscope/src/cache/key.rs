@@ -0,0 +1,158 @@
+//! Stable cache keys for [`MesherInputs`].
+//!
+//! Keys name files in shared, baked caches, so they must not change between
+//! toolchains or builds. Rather than relying on [`std::hash::Hash`] (whose
+//! output isn't stable across Rust releases), inputs are written out in a
+//! canonical text encoding and hashed with 128-bit FNV-1a, which has fixed,
+//! published parameters. Floats in the encoding, including those in the
+//! shape, are written as their exact bit patterns rather than formatted, since
+//! float formatting has changed between Rust releases.
+//!
+//! The encoding starts with [`CACHE_FORMAT_VERSION`]. Bump it whenever a
+//! change to the mesher or to the `Shape` types means existing cache entries
+//! should no longer be used.
+
+use std::fmt::Write;
+
+use super::encode::encode;
+use crate::mesher::{MesherDetail, MesherInputs};
+
+/// The version of the cache format, salted into every key.
+pub const CACHE_FORMAT_VERSION: u32 = 6;
+
+const FNV_OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+/// Hashes `bytes` with 128-bit FNV-1a.
+fn fnv1a_128(bytes: &[u8]) -> u128 {
+  bytes.iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+    (hash ^ *byte as u128).wrapping_mul(FNV_PRIME)
+  })
+}
+
+/// Writes out the canonical encoding of `inputs` that [`cache_key`] hashes.
+///
+/// The shape is simplified and then encoded field by field, so shapes that
+/// simplify to the same tree share a key. Every float, whether in the shape,
+/// the region or the variable bindings, is written as its exact bit pattern,
+/// and bindings are sorted by name.
+pub fn canonical_encoding(inputs: &MesherInputs) -> String {
+  let region = &inputs.region;
+  let bits = |v: glam::Vec3A| {
+    format!(
+      "{:08x} {:08x} {:08x}",
+      v.x.to_bits(),
+      v.y.to_bits(),
+      v.z.to_bits()
+    )
+  };
+  let detail = match region.detail {
+    MesherDetail::Subdivs(x) => format!("subdivs {x}"),
+    MesherDetail::Resolution(x) => format!("resolution {:08x}", x.to_bits()),
+    MesherDetail::Exact(x) => format!("exact {x}"),
+  };
+  let bindings = inputs
+    .bindings
+    .iter()
+    .map(|(name, value)| format!(" {name}={:08x}", value.to_bits()))
+    .collect::<String>();
+
+  // shapes don't fail to serialize, so neither does encoding them
+  let shape = encode(&inputs.shape.simplify()).expect("shapes always encode");
+
+  let mut encoding = String::new();
+  // writing to a `String` can't fail
+  let _ = write!(
+    encoding,
+    "planiscope cache v{CACHE_FORMAT_VERSION}\nshape: {}\nposition: \
+     {}\nscale: {}\ndetail: {detail}\nprune: {}\nsimplify: {}\ngen_collider: \
+     {}\nbindings:{bindings}\n",
+    shape,
+    bits(region.position),
+    bits(region.scale),
+    region.prune,
+    region.simplify,
+    inputs.gen_collider,
+  );
+  encoding
+}
+
+/// Returns the stable 128-bit cache key for `inputs`.
+pub fn cache_key(inputs: &MesherInputs) -> u128 {
+  fnv1a_128(canonical_encoding(inputs).as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{mesher::MesherRegion, shape::builder};
+
+  fn inputs() -> MesherInputs {
+    MesherInputs {
+      shape:        builder::translate(builder::sphere(1.0), 0.0, 2.0, 0.0),
+      region:       MesherRegion {
+        position: glam::Vec3A::new(0.0, 2.0, 0.0),
+        scale:    glam::Vec3A::splat(2.0),
+        detail:   MesherDetail::Subdivs(5),
+        prune:    false,
+        simplify: true,
+      },
+      gen_collider: true,
+      bindings:     Default::default(),
+    }
+  }
+
+  #[test]
+  fn fnv_matches_reference() {
+    assert_eq!(fnv1a_128(b""), FNV_OFFSET_BASIS);
+    assert_eq!(fnv1a_128(b"a"), 0xd228cb696f1a8caf78912b704e4a8964);
+  }
+
+  #[test]
+  fn encoding_is_canonical() {
+    assert_eq!(
+      canonical_encoding(&inputs()),
+      concat!(
+        "planiscope cache v6\n",
+        "shape: Remap{root:Extra(Sphere{radius:Constant(d3ff0000000000000)}),",
+        "new_x:XNode,new_y:Sub(YNode,Constant(d4000000000000000)),",
+        "new_z:ZNode}\n",
+        "position: 00000000 40000000 00000000\n",
+        "scale: 40000000 40000000 40000000\n",
+        "detail: subdivs 5\n",
+        "prune: false\n",
+        "simplify: true\n",
+        "gen_collider: true\n",
+        "bindings:\n",
+      )
+    );
+
+    let mut inputs = inputs();
+    inputs.bindings.insert("b".to_string(), 2.0);
+    inputs.bindings.insert("a".to_string(), 1.0);
+    assert!(canonical_encoding(&inputs)
+      .ends_with("\nbindings: a=3f800000 b=40000000\n"));
+  }
+
+  #[test]
+  fn keys_are_pinned() {
+    // if this fails, the cache format changed and `CACHE_FORMAT_VERSION`
+    // needs to be bumped along with the pinned value.
+    assert_eq!(cache_key(&inputs()), 0xaac1045f4743a56a45a600d5211575d0);
+  }
+
+  #[test]
+  fn equivalent_shapes_share_keys() {
+    let mut other = inputs();
+    other.shape = builder::translate(
+      builder::translate(builder::sphere(1.0), 0.0, 1.0, 0.0),
+      0.0,
+      1.0,
+      0.0,
+    );
+    assert_eq!(cache_key(&inputs()), cache_key(&other));
+
+    other.region.detail = MesherDetail::Subdivs(6);
+    assert_ne!(cache_key(&inputs()), cache_key(&other));
+  }
+}
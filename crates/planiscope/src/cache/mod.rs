@@ -1,4 +1,6 @@
 pub mod disk;
+mod encode;
+pub mod key;
 
 use mosh::BufMesh;
 use parry3d::shape::SharedShape;
@@ -0,0 +1,285 @@
+//! Human-readable, versioned JSON documents for shapes and mesher inputs.
+//!
+//! Documents record the schema version they were written with, alongside what
+//! kind of value they hold:
+//!
+//! ```json
+//! { "version": 1, "kind": "shape", "data": { "Extra": { "Sphere": ... } } }
+//! ```
+//!
+//! When a document is loaded, [`MIGRATIONS`] upgrade it one version at a time
+//! on the untyped JSON, before it's deserialized. Whenever a change to the
+//! `Shape` types would break existing documents, bump [`SCHEMA_VERSION`] and
+//! add a migration from the previous version.
+
+use std::fmt;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+  mesher::{MesherInputs, MesherRegion},
+  shape::Shape,
+};
+
+/// The version of the document schema written by [`to_json`].
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A type that can be stored in a document.
+pub trait DocumentKind: Serialize + DeserializeOwned {
+  /// The name stored in the document's `kind` field.
+  const KIND: &'static str;
+}
+
+impl DocumentKind for Shape {
+  const KIND: &'static str = "shape";
+}
+
+impl DocumentKind for MesherRegion {
+  const KIND: &'static str = "mesher_region";
+}
+
+impl DocumentKind for MesherInputs {
+  const KIND: &'static str = "mesher_inputs";
+}
+
+/// Upgrades the `data` of a document from version `from` to `from + 1`.
+#[derive(Clone, Copy)]
+pub struct Migration {
+  pub from:    u32,
+  /// Rewrites the data in place. Receives the document's kind, so that one
+  /// migration can handle shapes nested inside mesher inputs differently from
+  /// bare shapes.
+  pub migrate: fn(kind: &str, data: &mut Value) -> Result<(), String>,
+}
+
+/// The migrations applied by [`from_json`], in order.
+pub const MIGRATIONS: &[Migration] = &[];
+
+/// An error encountered while reading or writing a document.
+#[derive(Debug)]
+pub enum DocumentError {
+  Json(serde_json::Error),
+  /// The document holds a different kind of value than was asked for.
+  WrongKind {
+    expected: &'static str,
+    found:    String,
+  },
+  /// The document was written by a newer version of the schema.
+  TooNew {
+    version: u32,
+  },
+  /// No migration upgrades documents from this version.
+  MissingMigration {
+    from: u32,
+  },
+  /// A migration failed.
+  Migration {
+    from:    u32,
+    message: String,
+  },
+  /// The value contains a NaN or infinite number, which JSON can't represent.
+  NonFinite,
+}
+
+impl fmt::Display for DocumentError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Json(error) => write!(f, "invalid document: {error}"),
+      Self::WrongKind { expected, found } => {
+        write!(f, "expected a `{expected}` document, found `{found}`")
+      }
+      Self::TooNew { version } => write!(
+        f,
+        "document version {version} is newer than the supported version \
+         {SCHEMA_VERSION}"
+      ),
+      Self::MissingMigration { from } => {
+        write!(f, "no migration from document version {from}")
+      }
+      Self::Migration { from, message } => {
+        write!(
+          f,
+          "migration from document version {from} failed: {message}"
+        )
+      }
+      Self::NonFinite => {
+        write!(f, "non-finite numbers can't be written to a document")
+      }
+    }
+  }
+}
+
+impl std::error::Error for DocumentError {}
+
+impl From<serde_json::Error> for DocumentError {
+  fn from(error: serde_json::Error) -> Self { Self::Json(error) }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Document {
+  version: u32,
+  kind:    String,
+  data:    Value,
+}
+
+/// Writes `value` as a pretty-printed document at [`SCHEMA_VERSION`].
+pub fn to_json<T: DocumentKind>(value: &T) -> Result<String, DocumentError> {
+  let data = serde_json::to_value(value)?;
+  // serde_json writes non-finite floats as `null`, which wouldn't read back
+  if contains_null(&data) {
+    return Err(DocumentError::NonFinite);
+  }
+  let document = Document {
+    version: SCHEMA_VERSION,
+    kind: T::KIND.to_string(),
+    data,
+  };
+  Ok(serde_json::to_string_pretty(&document)?)
+}
+
+/// Reads a document, migrating it to [`SCHEMA_VERSION`] with [`MIGRATIONS`].
+pub fn from_json<T: DocumentKind>(text: &str) -> Result<T, DocumentError> {
+  from_json_with(text, MIGRATIONS)
+}
+
+/// Reads a document, migrating it to [`SCHEMA_VERSION`] with `migrations`.
+pub fn from_json_with<T: DocumentKind>(
+  text: &str,
+  migrations: &[Migration],
+) -> Result<T, DocumentError> {
+  let mut document: Document = serde_json::from_str(text)?;
+  if document.kind != T::KIND {
+    return Err(DocumentError::WrongKind {
+      expected: T::KIND,
+      found:    document.kind,
+    });
+  }
+  if document.version > SCHEMA_VERSION {
+    return Err(DocumentError::TooNew {
+      version: document.version,
+    });
+  }
+
+  while document.version < SCHEMA_VERSION {
+    let from = document.version;
+    let migration = migrations
+      .iter()
+      .find(|m| m.from == from)
+      .ok_or(DocumentError::MissingMigration { from })?;
+    (migration.migrate)(&document.kind, &mut document.data)
+      .map_err(|message| DocumentError::Migration { from, message })?;
+    document.version += 1;
+  }
+
+  Ok(serde_json::from_value(document.data)?)
+}
+
+fn contains_null(value: &Value) -> bool {
+  match value {
+    Value::Null => true,
+    Value::Array(values) => values.iter().any(contains_null),
+    Value::Object(map) => map.values().any(contains_null),
+    _ => false,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{mesher::MesherDetail, shape::builder};
+
+  fn inputs() -> MesherInputs {
+    MesherInputs {
+      shape:        builder::material(
+        builder::translate(builder::sphere(builder::var("r")), 0.0, 1.0, 0.0),
+        2,
+      ),
+      region:       MesherRegion {
+        position: glam::Vec3A::new(0.0, 1.0, 0.0),
+        scale:    glam::Vec3A::splat(2.0),
+        detail:   MesherDetail::Resolution(8.0),
+        prune:    true,
+        simplify: false,
+      },
+      gen_collider: true,
+      bindings:     [("r".to_string(), 1.5)].into(),
+    }
+  }
+
+  #[test]
+  fn inputs_round_trip() {
+    let text = to_json(&inputs()).unwrap();
+    let read: MesherInputs = from_json(&text).unwrap();
+    assert_eq!(format!("{:?}", read), format!("{:?}", inputs()));
+  }
+
+  #[test]
+  fn handwritten_documents_load() {
+    let text = r#"{
+      "version": 1,
+      "kind": "shape",
+      "data": { "Extra": { "Sphere": { "radius": { "Constant": 2.0 } } } }
+    }"#;
+    let shape: Shape = from_json(text).unwrap();
+    assert_eq!(
+      format!("{:?}", shape),
+      format!("{:?}", builder::sphere(2.0))
+    );
+  }
+
+  #[test]
+  fn kinds_and_versions_are_checked() {
+    let text = to_json(&builder::sphere(1.0)).unwrap();
+    assert!(matches!(
+      from_json::<MesherRegion>(&text),
+      Err(DocumentError::WrongKind { .. })
+    ));
+
+    let text = r#"{ "version": 99, "kind": "shape", "data": "XNode" }"#;
+    assert!(matches!(
+      from_json::<Shape>(text),
+      Err(DocumentError::TooNew { version: 99 })
+    ));
+
+    assert!(matches!(
+      to_json(&builder::constant(f64::INFINITY)),
+      Err(DocumentError::NonFinite)
+    ));
+  }
+
+  #[test]
+  fn migrations_upgrade_old_documents() {
+    // pretend that version 0 called constants `Const`
+    fn rename_const(_kind: &str, data: &mut Value) -> Result<(), String> {
+      match data {
+        Value::Object(map) => {
+          if let Some(value) = map.remove("Const") {
+            map.insert("Constant".to_string(), value);
+          }
+          map.values_mut().try_for_each(|v| rename_const("", v))
+        }
+        Value::Array(values) => {
+          values.iter_mut().try_for_each(|v| rename_const("", v))
+        }
+        _ => Ok(()),
+      }
+    }
+    let migrations = [Migration {
+      from:    0,
+      migrate: rename_const,
+    }];
+
+    let text = r#"{
+      "version": 0,
+      "kind": "shape",
+      "data": { "Add": ["XNode", { "Const": 1.0 }] }
+    }"#;
+    let shape: Shape = from_json_with(text, &migrations).unwrap();
+    assert_eq!(shape.to_string(), "x + 1.0");
+    assert!(matches!(
+      from_json::<Shape>(text),
+      Err(DocumentError::MissingMigration { from: 0 })
+    ));
+  }
+}
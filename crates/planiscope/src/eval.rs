@@ -0,0 +1,206 @@
+//! Point evaluation of [`Shape`]s outside of the mesher.
+
+use fidget::eval::{Family, Tape};
+use tracing::info_span;
+
+use crate::{
+  mesher::{bind_vars, Bindings, DefaultEvalFamily},
+  shape::{CachedIntoNode, Shape},
+};
+
+/// A [`Shape`] compiled once for repeated point queries.
+///
+/// The evaluator only holds the compiled tape and the values of the shape's
+/// [`Shape::Var`]s, so it's cheap to clone and can be shared between threads;
+/// each query builds its own scratch evaluator. Variables start out unbound
+/// (evaluating to NaN) and are set with [`ShapeEvaluator::bind`], which
+/// doesn't recompile the shape.
+pub struct ShapeEvaluator<F: Family = DefaultEvalFamily> {
+  pub(crate) tape: Tape<F>,
+  pub(crate) vars: Vec<f32>,
+}
+
+// manual impl so that `F` itself doesn't need to be `Clone`
+impl<F: Family> Clone for ShapeEvaluator<F> {
+  fn clone(&self) -> Self {
+    Self {
+      tape: self.tape.clone(),
+      vars: self.vars.clone(),
+    }
+  }
+}
+
+impl<F: Family> ShapeEvaluator<F> {
+  /// Compiles `shape` into an evaluator.
+  pub fn new(shape: &Shape) -> Result<Self, fidget::Error> {
+    let _span = info_span!("planiscope::ShapeEvaluator::new").entered();
+
+    let mut ctx = fidget::Context::new();
+    let node = shape.simplify().eval_root_cached(&mut ctx)?;
+    let tape = ctx.get_tape::<F>(node)?;
+    let vars = bind_vars(&tape, &Bindings::new());
+    Ok(Self { tape, vars })
+  }
+
+  /// Sets the values of the shape's variables. Variables missing from
+  /// `bindings` become unbound again.
+  pub fn bind(&mut self, bindings: &Bindings) {
+    self.vars = bind_vars(&self.tape, bindings);
+  }
+
+  /// Evaluates the distance field at `point`.
+  pub fn eval(&self, point: glam::Vec3A) -> Result<f32, fidget::Error> {
+    Ok(self.eval_many(&[point])?[0])
+  }
+
+  /// Evaluates the distance field at each of `points`.
+  pub fn eval_many(
+    &self,
+    points: &[glam::Vec3A],
+  ) -> Result<Vec<f32>, fidget::Error> {
+    let _span = info_span!(
+      "planiscope::ShapeEvaluator::eval_many",
+      points = points.len()
+    )
+    .entered();
+
+    let evaluator = fidget::eval::FloatSliceEval::new(&self.tape);
+    let values = evaluator.eval(
+      &points.iter().map(|v| v.x).collect::<Vec<_>>(),
+      &points.iter().map(|v| v.y).collect::<Vec<_>>(),
+      &points.iter().map(|v| v.z).collect::<Vec<_>>(),
+      &self.vars,
+    )?;
+    Ok(values.to_vec())
+  }
+
+  /// Evaluates the gradient of the distance field at `point`.
+  pub fn gradient(
+    &self,
+    point: glam::Vec3A,
+  ) -> Result<glam::Vec3A, fidget::Error> {
+    Ok(self.gradient_many(&[point])?[0])
+  }
+
+  /// Evaluates the gradient of the distance field at each of `points`.
+  pub fn gradient_many(
+    &self,
+    points: &[glam::Vec3A],
+  ) -> Result<Vec<glam::Vec3A>, fidget::Error> {
+    let _span = info_span!(
+      "planiscope::ShapeEvaluator::gradient_many",
+      points = points.len()
+    )
+    .entered();
+
+    crate::mesher::fidget_normals(points, &self.tape, &self.vars)
+  }
+
+  /// Returns whether `point` is inside the shape, i.e. whether the field is
+  /// negative there.
+  pub fn inside(&self, point: glam::Vec3A) -> Result<bool, fidget::Error> {
+    Ok(self.eval(point)? < 0.0)
+  }
+
+  /// Returns whether each of `points` is inside the shape.
+  pub fn inside_many(
+    &self,
+    points: &[glam::Vec3A],
+  ) -> Result<Vec<bool>, fidget::Error> {
+    Ok(
+      self
+        .eval_many(points)?
+        .into_iter()
+        .map(|v| v < 0.0)
+        .collect(),
+    )
+  }
+}
+
+impl Shape {
+  /// Compiles the shape into a [`ShapeEvaluator`]. Prefer this over
+  /// [`Shape::eval`] and [`Shape::gradient`] when making more than one query.
+  pub fn evaluator(&self) -> Result<ShapeEvaluator, fidget::Error> {
+    ShapeEvaluator::new(self)
+  }
+
+  /// Evaluates the distance field at a single point.
+  pub fn eval(&self, point: glam::Vec3A) -> Result<f32, fidget::Error> {
+    self.evaluator()?.eval(point)
+  }
+
+  /// Evaluates the gradient of the distance field at a single point.
+  pub fn gradient(
+    &self,
+    point: glam::Vec3A,
+  ) -> Result<glam::Vec3A, fidget::Error> {
+    self.evaluator()?.gradient(point)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use float_cmp::approx_eq;
+  use glam::Vec3A;
+
+  use super::*;
+  use crate::shape::builder;
+
+  #[test]
+  fn evaluates_points() {
+    let evaluator = builder::sphere(2.0).evaluator().unwrap();
+    let values = evaluator
+      .eval_many(&[Vec3A::ZERO, Vec3A::new(3.0, 0.0, 0.0)])
+      .unwrap();
+    assert!(approx_eq!(f32, values[0], -2.0, epsilon = 1e-5));
+    assert!(approx_eq!(f32, values[1], 1.0, epsilon = 1e-5));
+    assert_eq!(
+      evaluator
+        .inside_many(&[Vec3A::ZERO, Vec3A::new(0.0, 3.0, 0.0)])
+        .unwrap(),
+      vec![true, false]
+    );
+  }
+
+  #[test]
+  fn evaluates_gradients() {
+    let shape = builder::translate(builder::sphere(1.0), 1.0, 0.0, 0.0);
+    let gradient = shape.gradient(Vec3A::new(1.0, 2.0, 0.0)).unwrap();
+    assert!(approx_eq!(f32, gradient.x, 0.0, epsilon = 1e-5));
+    assert!(approx_eq!(f32, gradient.y, 1.0, epsilon = 1e-5));
+    assert!(approx_eq!(f32, gradient.z, 0.0, epsilon = 1e-5));
+  }
+
+  #[test]
+  fn rebinds_vars_without_recompiling() {
+    let shape = builder::sphere(builder::var("radius"));
+    let mut evaluator = shape.evaluator().unwrap();
+    assert!(evaluator.eval(Vec3A::ZERO).unwrap().is_nan());
+
+    for radius in [1.0, 2.5] {
+      evaluator.bind(&Bindings::from([("radius".to_string(), radius)]));
+      let value = evaluator.eval(Vec3A::new(3.0, 0.0, 0.0)).unwrap();
+      assert!(approx_eq!(f32, value, 3.0 - radius, epsilon = 1e-5));
+    }
+  }
+
+  #[test]
+  fn evaluates_from_other_threads() {
+    let evaluator = builder::cuboid(1.0, 1.0, 1.0).evaluator().unwrap();
+    let handles = (0..4)
+      .map(|i| {
+        let evaluator = evaluator.clone();
+        std::thread::spawn(move || {
+          evaluator
+            .inside(Vec3A::new(i as f32 * 0.6, 0.0, 0.0))
+            .unwrap()
+        })
+      })
+      .collect::<Vec<_>>();
+    let results = handles
+      .into_iter()
+      .map(|h| h.join().unwrap())
+      .collect::<Vec<_>>();
+    assert_eq!(results, vec![true, true, false, false]);
+  }
+}
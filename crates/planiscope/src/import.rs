@@ -0,0 +1,502 @@
+//! Importing closed triangle meshes as [`Shape`]s.
+//!
+//! A mesh is sampled into a [`distance_grid`](builder::distance_grid) around
+//! its bounding box. Each sample's distance is the distance to the nearest
+//! triangle, and its sign comes from the mesh's generalized winding number
+//! at that point: the sum of the solid angles of every triangle, over `4π`.
+//! It's close to one inside a closed mesh and zero outside, and it degrades
+//! gracefully for meshes with small holes or flipped triangles, which ray
+//! parity doesn't.
+//!
+//! Every sample visits every triangle, so imports cost `samples * triangles`
+//! and are best done once, ahead of time. The grid is also part of the shape's
+//! tape, so it's held to [`MAX_GRID_SAMPLES`](crate::shape::MAX_GRID_SAMPLES)
+//! like any other.
+
+use std::{fmt, path::Path};
+
+use glam::{UVec3, Vec3A};
+use mosh::BufMesh;
+use tracing::info_span;
+
+use crate::shape::{builder, grid::check_size, GridError, Shape};
+
+/// Empty cells left around the mesh on every side of the grid, so that the
+/// surface doesn't touch the grid's boundary.
+const PADDING_CELLS: f32 = 2.0;
+
+/// An error encountered while importing a mesh.
+#[derive(Debug)]
+pub enum ImportError {
+  Io(std::io::Error),
+  /// A mesh file couldn't be parsed. `line` is zero for binary files.
+  Parse {
+    line:    usize,
+    message: String,
+  },
+  /// The file's extension isn't `obj` or `stl`.
+  UnknownFormat,
+  /// The mesh has no triangles.
+  Empty,
+  Grid(GridError),
+}
+
+impl fmt::Display for ImportError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Io(error) => write!(f, "couldn't read mesh: {error}"),
+      Self::Parse { line: 0, message } => write!(f, "invalid mesh: {message}"),
+      Self::Parse { line, message } => {
+        write!(f, "invalid mesh on line {line}: {message}")
+      }
+      Self::UnknownFormat => write!(f, "expected an `obj` or `stl` file"),
+      Self::Empty => write!(f, "mesh has no triangles"),
+      Self::Grid(error) => write!(f, "{error}"),
+    }
+  }
+}
+
+impl std::error::Error for ImportError {}
+
+impl From<std::io::Error> for ImportError {
+  fn from(error: std::io::Error) -> Self { Self::Io(error) }
+}
+
+impl From<GridError> for ImportError {
+  fn from(error: GridError) -> Self { Self::Grid(error) }
+}
+
+fn parse_error<T>(
+  line: usize,
+  message: impl Into<String>,
+) -> Result<T, ImportError> {
+  Err(ImportError::Parse {
+    line,
+    message: message.into(),
+  })
+}
+
+/// Samples a closed mesh into a signed distance grid, with `resolution`
+/// samples along the longest side of its bounding box, including the padding
+/// around it. The grid is placed around the mesh, so the shape lines up with
+/// it.
+///
+/// Each sample adds a couple of operations to the shape's tape, and a point
+/// evaluated without pruning pays for all of them, so the grid may have at
+/// most [`MAX_GRID_SAMPLES`](crate::shape::MAX_GRID_SAMPLES) samples. That
+/// allows a `resolution` of about 40 for a mesh as deep as it is wide, and more
+/// for flatter ones; larger grids are rejected with [`GridError::TooLarge`]
+/// before any sampling is done.
+pub fn mesh_to_shape(
+  mesh: &BufMesh,
+  resolution: u32,
+) -> Result<Shape, ImportError> {
+  let _span = info_span!("planiscope::import::mesh_to_shape").entered();
+
+  let triangles = mesh
+    .triangles
+    .iter()
+    .map(|t| {
+      let vertex = |i: u32| {
+        mesh.positions.get(i as usize).copied().ok_or_else(|| {
+          ImportError::Parse {
+            line:    0,
+            message: format!("triangle uses missing vertex {i}"),
+          }
+        })
+      };
+      Ok([vertex(t.x)?, vertex(t.y)?, vertex(t.z)?])
+    })
+    .collect::<Result<Vec<_>, ImportError>>()?;
+  if triangles.is_empty() {
+    return Err(ImportError::Empty);
+  }
+
+  let (min, max) = triangles.iter().flatten().fold(
+    (Vec3A::splat(f32::INFINITY), Vec3A::splat(f32::NEG_INFINITY)),
+    |(min, max), p| (min.min(*p), max.max(*p)),
+  );
+  let center = (min + max) / 2.0;
+  // pad the longest side by whole cells, then fit the others to that spacing
+  let longest = (max - min).max_element();
+  let cell = longest / (resolution as f32 - 1.0 - 2.0 * PADDING_CELLS).max(1.0);
+  let cell = if cell > 0.0 { cell } else { 1.0 };
+  let size = ((max - min) / cell + 2.0 * PADDING_CELLS)
+    .ceil()
+    .to_array()
+    .map(|cells| (cells as u32).saturating_add(1));
+  let extent = size.map(|n| (n - 1) as f32 * cell / 2.0);
+
+  let mut values = Vec::with_capacity(check_size(&size)?);
+  for k in 0..size[2] {
+    for j in 0..size[1] {
+      for i in 0..size[0] {
+        let offset = Vec3A::new(i as f32, j as f32, k as f32) * cell;
+        let point = center - Vec3A::from_array(extent) + offset;
+        values.push(signed_distance(&triangles, point));
+      }
+    }
+  }
+
+  let grid = builder::distance_grid(size, extent, values)?;
+  Ok(builder::translate(
+    grid,
+    center.x as f64,
+    center.y as f64,
+    center.z as f64,
+  ))
+}
+
+/// Reads an OBJ or STL file, by its extension, and samples it with
+/// [`mesh_to_shape`].
+pub fn mesh_file_to_shape(
+  path: impl AsRef<Path>,
+  resolution: u32,
+) -> Result<Shape, ImportError> {
+  let path = path.as_ref();
+  let extension = path
+    .extension()
+    .and_then(|e| e.to_str())
+    .map(|e| e.to_ascii_lowercase());
+  let mesh = match extension.as_deref() {
+    Some("obj") => parse_obj(&std::fs::read_to_string(path)?)?,
+    Some("stl") => parse_stl(&std::fs::read(path)?)?,
+    _ => return Err(ImportError::UnknownFormat),
+  };
+  mesh_to_shape(&mesh, resolution)
+}
+
+/// Parses the vertices and faces of an OBJ file. Polygons are split into
+/// fans of triangles; everything besides `v` and `f` lines is ignored.
+pub fn parse_obj(text: &str) -> Result<BufMesh, ImportError> {
+  let mut positions = Vec::new();
+  let mut triangles = Vec::new();
+
+  for (index, line) in text.lines().enumerate() {
+    let line_number = index + 1;
+    let mut words = line.split_whitespace();
+    match words.next() {
+      Some("v") => {
+        let mut coords = [0.0; 3];
+        for coord in coords.iter_mut() {
+          *coord = match words.next().map(str::parse) {
+            Some(Ok(value)) => value,
+            _ => return parse_error(line_number, "expected 3 coordinates"),
+          };
+        }
+        positions.push(Vec3A::from_array(coords));
+      }
+      Some("f") => {
+        // indices are one-based, or negative to count back from the end
+        let face = words
+          .map(|word| {
+            let index = word.split('/').next().unwrap_or_default();
+            match index.parse::<i64>() {
+              Ok(i) if i > 0 && i as usize <= positions.len() => {
+                Ok(i as u32 - 1)
+              }
+              Ok(i)
+                if i < 0 && i.unsigned_abs() as usize <= positions.len() =>
+              {
+                Ok((positions.len() as i64 + i) as u32)
+              }
+              _ => parse_error(line_number, format!("invalid vertex `{word}`")),
+            }
+          })
+          .collect::<Result<Vec<_>, _>>()?;
+        if face.len() < 3 {
+          return parse_error(line_number, "faces need at least 3 vertices");
+        }
+        for i in 1..face.len() - 1 {
+          triangles.push(UVec3::new(face[0], face[i], face[i + 1]));
+        }
+      }
+      _ => {}
+    }
+  }
+
+  Ok(with_normals(positions, triangles))
+}
+
+/// Parses a binary or ASCII STL file.
+pub fn parse_stl(bytes: &[u8]) -> Result<BufMesh, ImportError> {
+  // binary files can also start with "solid", so check their size first
+  if bytes.len() >= 84 {
+    let count =
+      u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]);
+    if bytes.len() == 84 + 50 * count as usize {
+      return Ok(parse_binary_stl(&bytes[84..]));
+    }
+  }
+  match std::str::from_utf8(bytes) {
+    Ok(text) if text.trim_start().starts_with("solid") => parse_ascii_stl(text),
+    _ => parse_error(0, "not a valid STL file"),
+  }
+}
+
+fn parse_binary_stl(records: &[u8]) -> BufMesh {
+  let float = |b: &[u8]| f32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+  // each record is a normal, three vertices and two bytes of attributes
+  let positions = records
+    .chunks_exact(50)
+    .flat_map(|record| {
+      (0..3).map(move |v| {
+        let start = 12 + v * 12;
+        Vec3A::new(
+          float(&record[start..]),
+          float(&record[start + 4..]),
+          float(&record[start + 8..]),
+        )
+      })
+    })
+    .collect::<Vec<_>>();
+  let triangles = (0..positions.len() as u32 / 3)
+    .map(|t| UVec3::new(t * 3, t * 3 + 1, t * 3 + 2))
+    .collect();
+  with_normals(positions, triangles)
+}
+
+fn parse_ascii_stl(text: &str) -> Result<BufMesh, ImportError> {
+  let mut positions = Vec::new();
+  for (index, line) in text.lines().enumerate() {
+    let mut words = line.split_whitespace();
+    if words.next() != Some("vertex") {
+      continue;
+    }
+    let coords = words.map(str::parse).collect::<Result<Vec<f32>, _>>();
+    match coords.as_deref() {
+      Ok(&[x, y, z]) => positions.push(Vec3A::new(x, y, z)),
+      _ => return parse_error(index + 1, "expected 3 coordinates"),
+    }
+  }
+  if positions.len() % 3 != 0 {
+    return parse_error(0, "facets need exactly 3 vertices");
+  }
+  let triangles = (0..positions.len() as u32 / 3)
+    .map(|t| UVec3::new(t * 3, t * 3 + 1, t * 3 + 2))
+    .collect();
+  Ok(with_normals(positions, triangles))
+}
+
+/// Builds a mesh with area-weighted vertex normals.
+fn with_normals(positions: Vec<Vec3A>, triangles: Vec<UVec3>) -> BufMesh {
+  let mut normals = vec![Vec3A::ZERO; positions.len()];
+  for t in &triangles {
+    let [a, b, c] = t.to_array().map(|i| positions[i as usize]);
+    let normal = (b - a).cross(c - a);
+    for i in t.to_array() {
+      normals[i as usize] += normal;
+    }
+  }
+  BufMesh {
+    normals: normals.into_iter().map(Vec3A::normalize_or_zero).collect(),
+    positions,
+    triangles,
+    materials: Vec::new(),
+  }
+}
+
+/// The distance from `point` to the nearest triangle, negative where the
+/// winding number is over one half.
+fn signed_distance(triangles: &[[Vec3A; 3]], point: Vec3A) -> f32 {
+  let mut distance_squared = f32::INFINITY;
+  let mut solid_angle = 0.0;
+  for triangle in triangles {
+    let closest = closest_point_on_triangle(triangle, point);
+    distance_squared = distance_squared.min(closest.distance_squared(point));
+    solid_angle += solid_angle_of_triangle(triangle, point);
+  }
+
+  let winding = solid_angle / (4.0 * std::f32::consts::PI);
+  // a consistently inside-out mesh winds the other way
+  let sign = if winding.abs() > 0.5 { -1.0 } else { 1.0 };
+  sign * distance_squared.sqrt()
+}
+
+/// The signed solid angle that a triangle covers, seen from `point`, after
+/// Van Oosterom and Strackee.
+fn solid_angle_of_triangle(triangle: &[Vec3A; 3], point: Vec3A) -> f32 {
+  let [a, b, c] = triangle.map(|v| v - point);
+  let (la, lb, lc) = (a.length(), b.length(), c.length());
+  let numerator = a.dot(b.cross(c));
+  let denominator =
+    la * lb * lc + a.dot(b) * lc + b.dot(c) * la + c.dot(a) * lb;
+  2.0 * numerator.atan2(denominator)
+}
+
+/// The closest point on a triangle to `p`, from Ericson's "Real-Time
+/// Collision Detection".
+fn closest_point_on_triangle(triangle: &[Vec3A; 3], p: Vec3A) -> Vec3A {
+  let [a, b, c] = *triangle;
+  let ab = b - a;
+  let ac = c - a;
+  let ap = p - a;
+  let d1 = ab.dot(ap);
+  let d2 = ac.dot(ap);
+  if d1 <= 0.0 && d2 <= 0.0 {
+    return a;
+  }
+
+  let bp = p - b;
+  let d3 = ab.dot(bp);
+  let d4 = ac.dot(bp);
+  if d3 >= 0.0 && d4 <= d3 {
+    return b;
+  }
+
+  let vc = d1 * d4 - d3 * d2;
+  if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+    return a + ab * (d1 / (d1 - d3));
+  }
+
+  let cp = p - c;
+  let d5 = ab.dot(cp);
+  let d6 = ac.dot(cp);
+  if d6 >= 0.0 && d5 <= d6 {
+    return c;
+  }
+
+  let vb = d5 * d2 - d1 * d6;
+  if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+    return a + ac * (d2 / (d2 - d6));
+  }
+
+  let va = d3 * d6 - d5 * d4;
+  if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+    return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+  }
+
+  // inside the face
+  let denominator = 1.0 / (va + vb + vc);
+  a + ab * (vb * denominator) + ac * (vc * denominator)
+}
+
+#[cfg(test)]
+mod tests {
+  use float_cmp::approx_eq;
+
+  use super::*;
+
+  /// A cube with half-extent 1 in OBJ form, with quad faces wound outwards.
+  const CUBE_OBJ: &str = "# a cube
+v -1 -1 -1
+v 1 -1 -1
+v 1 1 -1
+v -1 1 -1
+v -1 -1 1
+v 1 -1 1
+v 1 1 1
+v -1 1 1
+f 1 4 3 2
+f 5 6 7 8
+f 1 2 6 5
+f 4 8 7 3
+f 1 5 8 4
+f 2 3 7 6
+";
+
+  fn eval(shape: &Shape, point: [f32; 3]) -> f32 {
+    shape.eval(Vec3A::from_array(point)).unwrap()
+  }
+
+  #[test]
+  fn cubes_become_boxes() {
+    let mesh = parse_obj(CUBE_OBJ).unwrap();
+    assert_eq!(mesh.triangles.len(), 12);
+    let shape = mesh_to_shape(&mesh, 16).unwrap();
+
+    // the samples are exact, so the field is within half a cell of exact
+    for (point, expected) in [
+      ([0.0, 0.0, 0.0], -1.0),
+      ([0.5, 0.0, 0.0], -0.5),
+      ([1.0, 0.3, -0.2], 0.0),
+      ([1.25, 0.0, 0.0], 0.25),
+      ([3.0, 0.0, 0.0], 2.0),
+    ] {
+      let actual = eval(&shape, point);
+      assert!(
+        approx_eq!(f32, actual, expected, epsilon = 0.1),
+        "expected {expected} at {point:?}, got {actual}"
+      );
+    }
+  }
+
+  #[test]
+  fn inside_out_meshes_keep_their_sign() {
+    let mut mesh = parse_obj(CUBE_OBJ).unwrap();
+    for triangle in &mut mesh.triangles {
+      *triangle = UVec3::new(triangle.x, triangle.z, triangle.y);
+    }
+    let shape = mesh_to_shape(&mesh, 8).unwrap();
+    assert!(eval(&shape, [0.0, 0.0, 0.0]) < 0.0);
+    assert!(eval(&shape, [1.5, 0.0, 0.0]) > 0.0);
+  }
+
+  #[test]
+  fn stl_formats_agree() {
+    let mesh = parse_obj(CUBE_OBJ).unwrap();
+    let triangles = mesh
+      .triangles
+      .iter()
+      .map(|t| t.to_array().map(|i| mesh.positions[i as usize]))
+      .collect::<Vec<_>>();
+
+    let mut ascii = String::from("solid cube\n");
+    let mut binary = vec![0; 80];
+    binary.extend((triangles.len() as u32).to_le_bytes());
+    for triangle in &triangles {
+      ascii.push_str("facet normal 0 0 0\nouter loop\n");
+      binary.extend([0; 12]);
+      for v in triangle {
+        ascii.push_str(&format!("vertex {} {} {}\n", v.x, v.y, v.z));
+        for coord in v.to_array() {
+          binary.extend(coord.to_le_bytes());
+        }
+      }
+      ascii.push_str("endloop\nendfacet\n");
+      binary.extend([0; 2]);
+    }
+    ascii.push_str("endsolid cube\n");
+
+    for mesh in [
+      parse_stl(ascii.as_bytes()).unwrap(),
+      parse_stl(&binary).unwrap(),
+    ] {
+      assert_eq!(mesh.triangles.len(), 12);
+      let shape = mesh_to_shape(&mesh, 16).unwrap();
+      assert!(approx_eq!(
+        f32,
+        eval(&shape, [0.0, 0.0, 0.0]),
+        -1.0,
+        epsilon = 0.1
+      ));
+    }
+  }
+
+  #[test]
+  fn bad_files_are_rejected() {
+    assert!(matches!(
+      parse_obj("v 0 0 0\nf 1 2 3\n"),
+      Err(ImportError::Parse { line: 2, .. })
+    ));
+    assert!(matches!(
+      mesh_to_shape(&parse_obj("v 0 0 0\n").unwrap(), 8),
+      Err(ImportError::Empty)
+    ));
+    assert!(matches!(
+      mesh_file_to_shape("statue.fbx", 8),
+      Err(ImportError::UnknownFormat)
+    ));
+  }
+
+  #[test]
+  fn oversized_grids_are_rejected() {
+    let mesh = parse_obj(CUBE_OBJ).unwrap();
+    for resolution in [64, u32::MAX] {
+      assert!(matches!(
+        mesh_to_shape(&mesh, resolution),
+        Err(ImportError::Grid(GridError::TooLarge { .. }))
+      ));
+    }
+  }
+}
@@ -2,6 +2,11 @@
 
 pub mod cache;
 pub mod collider;
+pub mod document;
+pub mod eval;
+pub mod import;
+pub mod mass;
 pub mod mesher;
 pub mod nso;
+pub mod raycast;
 pub mod shape;
@@ -0,0 +1,286 @@
+//! Volume, center of mass and inertia of [`Shape`]s.
+//!
+//! The region is split into an octree with interval evaluation: cells that
+//! are entirely outside the shape are dropped, cells that are entirely inside
+//! are integrated exactly, and cells that straddle the surface are split
+//! again. Splitting goes breadth first, so the largest unresolved cells are
+//! always split next, until the straddling cells add up to less than the
+//! requested error. Each of those is then counted as inside or outside from
+//! the field at its center. The true volume can only differ from the estimate
+//! by the volume of those cells, which is reported as
+//! [`MassProperties::volume_error`].
+
+use fidget::eval::Family;
+use glam::{Mat3, Vec3A};
+use tracing::info_span;
+
+use crate::{
+  eval::ShapeEvaluator,
+  shape::{Bounds, Shape},
+};
+
+/// The deepest the octree is split, relative to the region.
+const MAX_DEPTH: u32 = 10;
+/// Splitting stops early rather than evaluate more cells than this in one
+/// level of the octree.
+const MAX_CELLS: usize = 1 << 20;
+
+/// The mass properties of a shape with a density of one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MassProperties {
+  pub volume:       f32,
+  /// The most that `volume` can differ from the true volume.
+  pub volume_error: f32,
+  /// The center of mass. It's the center of the region if the shape is empty.
+  pub centroid:     Vec3A,
+  /// The inertia tensor about `centroid`.
+  pub inertia:      Mat3,
+}
+
+impl MassProperties {
+  /// The mass of the shape at `density`.
+  pub fn mass(&self, density: f32) -> f32 { self.volume * density }
+
+  /// The inertia tensor of the shape at `density`.
+  pub fn inertia(&self, density: f32) -> Mat3 { self.inertia * density }
+}
+
+/// The zeroth, first and second moments of a volume.
+#[derive(Default)]
+struct Moments {
+  volume: f64,
+  first:  [f64; 3],
+  /// The integrals of `x_i * x_j` over the volume.
+  second: [[f64; 3]; 3],
+}
+
+impl Moments {
+  /// Adds the moments of a solid cell.
+  fn add_cell(&mut self, cell: Bounds) {
+    let min = cell.min.to_array().map(f64::from);
+    let max = cell.max.to_array().map(f64::from);
+    let center: [f64; 3] = std::array::from_fn(|i| (min[i] + max[i]) / 2.0);
+    let width: [f64; 3] = std::array::from_fn(|i| max[i] - min[i]);
+    let volume = width[0] * width[1] * width[2];
+
+    self.volume += volume;
+    for i in 0..3 {
+      self.first[i] += volume * center[i];
+      for j in 0..3 {
+        self.second[i][j] += volume * center[i] * center[j];
+      }
+      // a box's spread about its center along its own axis
+      self.second[i][i] += volume * width[i] * width[i] / 12.0;
+    }
+  }
+
+  fn into_properties(
+    self,
+    region: Bounds,
+    volume_error: f64,
+  ) -> MassProperties {
+    if self.volume <= 0.0 {
+      return MassProperties {
+        volume:       0.0,
+        volume_error: volume_error as f32,
+        centroid:     region.center(),
+        inertia:      Mat3::ZERO,
+      };
+    }
+
+    let c = self.first.map(|m| m / self.volume);
+    // second moments about the centroid
+    let s: [[f64; 3]; 3] = std::array::from_fn(|i| {
+      std::array::from_fn(|j| self.second[i][j] - self.volume * c[i] * c[j])
+    });
+    let trace = s[0][0] + s[1][1] + s[2][2];
+    let inertia: [[f64; 3]; 3] = std::array::from_fn(|i| {
+      std::array::from_fn(|j| if i == j { trace - s[i][i] } else { -s[i][j] })
+    });
+
+    MassProperties {
+      volume:       self.volume as f32,
+      volume_error: volume_error as f32,
+      centroid:     Vec3A::new(c[0] as f32, c[1] as f32, c[2] as f32),
+      inertia:      Mat3::from_cols_array_2d(
+        &inertia.map(|col| col.map(|v| v as f32)),
+      ),
+    }
+  }
+}
+
+impl Shape {
+  /// Estimates the mass properties of the part of the shape inside `region`,
+  /// to within `max_error` of the true volume where possible. See
+  /// [`ShapeEvaluator::mass_properties`].
+  pub fn mass_properties(
+    &self,
+    region: Bounds,
+    max_error: f32,
+  ) -> Result<MassProperties, fidget::Error> {
+    self.evaluator()?.mass_properties(region, max_error)
+  }
+}
+
+impl<F: Family> ShapeEvaluator<F> {
+  /// Estimates the mass properties of the part of the shape inside `region`.
+  ///
+  /// Cells along the surface are split until they add up to less than
+  /// `max_error`, but splitting stops at a fixed depth, so very small errors
+  /// may not be reached; check [`MassProperties::volume_error`] for the bound
+  /// that was.
+  pub fn mass_properties(
+    &self,
+    region: Bounds,
+    max_error: f32,
+  ) -> Result<MassProperties, fidget::Error> {
+    let _span =
+      info_span!("planiscope::ShapeEvaluator::mass_properties").entered();
+
+    let interval_eval = self.tape.new_interval_evaluator();
+    let mut moments = Moments::default();
+    let mut straddling = vec![region];
+    let cell_volume = |cell: &Bounds| {
+      let size = cell.max - cell.min;
+      size.x as f64 * size.y as f64 * size.z as f64
+    };
+
+    for depth in 0..=MAX_DEPTH {
+      let mut next = Vec::new();
+      for cell in straddling {
+        let (interval, _) = interval_eval.eval(
+          [cell.min.x, cell.max.x],
+          [cell.min.y, cell.max.y],
+          [cell.min.z, cell.max.z],
+          &self.vars,
+        )?;
+        if interval.lower() > 0.0 {
+          continue;
+        }
+        if interval.upper() < 0.0 {
+          moments.add_cell(cell);
+          continue;
+        }
+        next.push(cell);
+      }
+      straddling = next;
+
+      let error: f64 = straddling.iter().map(cell_volume).sum();
+      if error <= max_error as f64
+        || depth == MAX_DEPTH
+        || straddling.len() * 8 > MAX_CELLS
+      {
+        break;
+      }
+      straddling = straddling.into_iter().flat_map(octants).collect();
+    }
+
+    // settle the cells left along the surface by their centers
+    let centers = straddling.iter().map(|c| c.center()).collect::<Vec<_>>();
+    let values = self.eval_many(&centers)?;
+    let mut volume_error = 0.0;
+    for (cell, value) in straddling.iter().zip(values) {
+      volume_error += cell_volume(cell);
+      if value < 0.0 {
+        moments.add_cell(*cell);
+      }
+    }
+
+    Ok(moments.into_properties(region, volume_error))
+  }
+}
+
+/// Splits a cell into its eight octants.
+fn octants(cell: Bounds) -> [Bounds; 8] {
+  let center = cell.center();
+  std::array::from_fn(|octant| {
+    let pick = |bit: usize, min: f32, mid: f32, max: f32| {
+      if octant & bit == 0 {
+        (min, mid)
+      } else {
+        (mid, max)
+      }
+    };
+    let (x0, x1) = pick(1, cell.min.x, center.x, cell.max.x);
+    let (y0, y1) = pick(2, cell.min.y, center.y, cell.max.y);
+    let (z0, z1) = pick(4, cell.min.z, center.z, cell.max.z);
+    Bounds {
+      min: Vec3A::new(x0, y0, z0),
+      max: Vec3A::new(x1, y1, z1),
+    }
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use float_cmp::approx_eq;
+
+  use super::*;
+  use crate::shape::builder;
+
+  fn region(half_extent: f32) -> Bounds {
+    Bounds {
+      min: Vec3A::splat(-half_extent),
+      max: Vec3A::splat(half_extent),
+    }
+  }
+
+  #[test]
+  fn boxes_are_exact() {
+    // the box's faces line up with octree cells, so the cells along them are
+    // settled correctly even though they count towards the error
+    let shape =
+      builder::translate(builder::cuboid(1.0, 0.5, 0.5), 1.0, 0.0, 0.0);
+    let props = shape.mass_properties(region(4.0), 0.5).unwrap();
+    assert!(approx_eq!(f32, props.volume, 2.0, epsilon = 1e-2));
+    assert!(approx_eq!(f32, props.centroid.x, 1.0, epsilon = 1e-2));
+    assert!(approx_eq!(f32, props.centroid.y, 0.0, epsilon = 1e-2));
+
+    // a solid box of mass m has I_xx = m (h^2 + d^2) / 12
+    let expected = [
+      2.0 * (1.0 + 1.0) / 12.0,
+      2.0 * (4.0 + 1.0) / 12.0,
+      2.0 * (4.0 + 1.0) / 12.0,
+    ];
+    for (axis, expected) in expected.into_iter().enumerate() {
+      let actual = props.inertia.col(axis)[axis];
+      assert!(approx_eq!(f32, actual, expected, epsilon = 1e-2));
+    }
+    assert!(props.inertia.col(0)[1].abs() < 1e-3);
+  }
+
+  #[test]
+  fn sphere_volume_is_within_the_error() {
+    let props = builder::sphere(1.0)
+      .mass_properties(region(2.0), 0.25)
+      .unwrap();
+    let true_volume = 4.0 / 3.0 * std::f32::consts::PI;
+    assert!(props.volume_error <= 0.25);
+    assert!((props.volume - true_volume).abs() <= props.volume_error);
+    assert!(props.centroid.length() < 1e-2);
+  }
+
+  #[test]
+  fn holes_are_subtracted() {
+    // the solid fills the region, so only the hole's surface is uncertain
+    let solid = builder::cuboid(2.0, 2.0, 2.0);
+    let holed = builder::max(solid.clone(), -builder::cylinder(0.5, 2.0));
+    let max_error = 0.25;
+    let solid = solid.mass_properties(region(1.0), max_error).unwrap();
+    let holed = holed.mass_properties(region(1.0), max_error).unwrap();
+    assert_eq!(solid.volume, 8.0);
+    assert!(holed.volume_error <= max_error);
+    // a cylinder of radius 0.5 and height 2, through the whole region
+    let hole = std::f32::consts::PI * 0.25 * 2.0;
+    let difference = solid.volume - holed.volume;
+    assert!((difference - hole).abs() <= holed.volume_error);
+  }
+
+  #[test]
+  fn empty_shapes_have_no_mass() {
+    let shape = builder::translate(builder::sphere(1.0), 10.0, 0.0, 0.0);
+    let props = shape.mass_properties(region(2.0), 0.01).unwrap();
+    assert_eq!(props.volume, 0.0);
+    assert_eq!(props.centroid, Vec3A::ZERO);
+  }
+}
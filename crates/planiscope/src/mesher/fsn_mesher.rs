@@ -7,129 +7,293 @@ use mosh::BufMesh;
 use tracing::info_span;
 
 use crate::{
-  mesher::{fidget_normals, FastSurfaceNetsMesher, Mesher, MesherInputs},
+  mesher::{
+    bind_vars, fidget_normals, DefaultEvalFamily, FastSurfaceNetsMesher,
+    Mesher, MesherInputs,
+  },
   nso,
-  shape::CachedIntoNode,
+  shape::{CachedIntoNode, DEFAULT_MATERIAL},
 };
 
 impl Mesher for FastSurfaceNetsMesher {
-  type EvalFamily = fidget::vm::Eval;
+  type EvalFamily = DefaultEvalFamily;
 
   fn build_mesh(
     &self,
     inputs: &MesherInputs,
   ) -> Result<BufMesh, fidget::Error> {
-    let _span =
-      info_span!("plansicope::FastSurfaceNetsMesher::build_mesh").entered();
+    build_mesh_with_family::<Self::EvalFamily>(inputs)
+  }
+}
 
-    // get a node for the composition
-    let mut ctx = Context::new();
-    let node = inputs.shape.eval_root_cached(&mut ctx)?;
+/// Builds a mesh with surface nets, evaluating the shape with the evaluator
+/// family `F`.
+fn build_mesh_with_family<F: fidget::eval::Family>(
+  inputs: &MesherInputs,
+) -> Result<BufMesh, fidget::Error> {
+  let _span =
+    info_span!("plansicope::FastSurfaceNetsMesher::build_mesh").entered();
+
+  // get a node for the composition
+  let mut ctx = Context::new();
+  let node = inputs.shape.simplify().eval_root_cached(&mut ctx)?;
+
+  // we need to normalize the target region into -1..1
+  let normalized_node = nso::regions::nso_normalize_region(
+    node,
+    inputs.region.position.to_array(),
+    inputs.region.scale.to_array(),
+    &mut ctx,
+  )?;
+
+  let tape = ctx.get_tape::<F>(normalized_node)?;
+  let vars = bind_vars(&tape, &inputs.bindings);
+  let tape = simplify_tape(tape, [[-1.0, 1.0]; 3], &vars)?;
+
+  // how many units the specified number of subdivisions will produce
+  let shape_length = inputs.region.voxel_side_length();
+
+  // a shape for the purpose of delinearizing in iteration
+  let ndshape_descriptor = RuntimeShape::<u32, 3>::new(shape_length);
+  // closure for getting from voxel units to -1..1 f32 units, i.e. node coords
+  // let voxel_to_node_coords =
+  // move |x| (x as f32) / (shape_length as f32 / 2.0) - 1.0;
+
+  // all of the delinearized points from the shape descriptor, in -1..1
+  let points = (0u32..ndshape_descriptor.size())
+    .map(|x| ndshape_descriptor.delinearize(x))
+    .map(|p| {
+      glam::UVec3::from_array(p).as_vec3a()
+        / (glam::UVec3::from_array(shape_length).as_vec3a() / 2.0)
+        - 1.0
+    })
+    .collect::<Vec<glam::Vec3A>>();
+
+  let eval_span =
+    info_span!("fidget_point_eval", points = points.len()).entered();
+  // evaluate the fidget tape on all of the points
+  let evaluator = fidget::eval::FloatSliceEval::new(&tape);
+  let values = evaluator.eval(
+    &points.iter().map(|v| v.x).collect::<Vec<_>>(),
+    &points.iter().map(|v| v.y).collect::<Vec<_>>(),
+    &points.iter().map(|v| v.z).collect::<Vec<_>>(),
+    &vars,
+  )?;
+  drop(eval_span);
+
+  let surface_nets_span = info_span!("surface_nets").entered();
+  // create a buffer for holding the surface_nets result
+  let mut buffer = SurfaceNetsBuffer::default();
+  surface_nets(
+    &values,
+    &ndshape_descriptor,
+    [0; 3],
+    (glam::UVec3::from_array(shape_length) - 1).to_array(),
+    &mut buffer,
+  );
+  drop(surface_nets_span);
+
+  // convert vertices and triangles into something we can use (what full_mesh
+  // is expecting), and scale them back up for the normal calc.
+  let positions = buffer
+    .positions
+    .iter()
+    // this is to convert from linearized integer coords back to -1..1
+    .map(|a| {
+      glam::Vec3A::from_array(*a)
+        / (glam::UVec3::from_array(shape_length).as_vec3a() / 2.0)
+        - 1.0
+    })
+    // this is to go from -1..1 to the normal scale
+    .collect::<Vec<glam::Vec3A>>();
+  // this uses a chunk operation on the slice because the indices aren't in
+  // triplets
+  let triangles = buffer
+    .indices
+    .chunks(3)
+    .map(|c| glam::UVec3::from_array([c[0], c[1], c[2]]))
+    .collect::<Vec<glam::UVec3>>();
+
+  // get the normals
+  let normals: Vec<glam::Vec3A> = fidget_normals(&positions, &tape, &vars)?;
+  let materials = vertex_materials::<F>(inputs, &positions)?;
+
+  let mut mesh = BufMesh {
+    positions,
+    triangles,
+    normals,
+    materials,
+  };
+
+  mesh.transform(glam::Vec3A::ZERO, inputs.region.scale);
+  let mesh = if inputs.region.simplify {
+    mosh::simplify_mesh(mesh)
+  } else {
+    mesh
+  };
+
+  Ok(mesh)
+}
+
+/// Picks the material of each vertex, given in -1..1 region space: the one
+/// whose field is nearest to zero there. Empty if the shape has no materials.
+fn vertex_materials<F: fidget::eval::Family>(
+  inputs: &MesherInputs,
+  positions: &[glam::Vec3A],
+) -> Result<Vec<u32>, fidget::Error> {
+  let fields = inputs.shape.material_fields();
+  if fields.is_empty() {
+    return Ok(Vec::new());
+  }
+  let _span = info_span!("vertex_materials", fields = fields.len()).entered();
 
-    // we need to normalize the target region into -1..1
-    let normalized_node = nso::regions::nso_normalize_region(
+  let xs = positions.iter().map(|v| v.x).collect::<Vec<_>>();
+  let ys = positions.iter().map(|v| v.y).collect::<Vec<_>>();
+  let zs = positions.iter().map(|v| v.z).collect::<Vec<_>>();
+
+  let mut nearest = vec![(f32::INFINITY, DEFAULT_MATERIAL); positions.len()];
+  for (id, field) in fields {
+    let mut ctx = Context::new();
+    let node = field.eval_root_cached(&mut ctx)?;
+    let node = nso::regions::nso_normalize_region(
       node,
       inputs.region.position.to_array(),
       inputs.region.scale.to_array(),
       &mut ctx,
     )?;
-
-    let tape = ctx.get_tape::<Self::EvalFamily>(normalized_node)?;
-    let tape = simplify_tape(tape, [[-1.0, 1.0]; 3])?;
-
-    // how many units the specified number of subdivisions will produce
-    let shape_length = inputs.region.voxel_side_length();
-
-    // a shape for the purpose of delinearizing in iteration
-    let ndshape_descriptor = RuntimeShape::<u32, 3>::new(shape_length);
-    // closure for getting from voxel units to -1..1 f32 units, i.e. node coords
-    // let voxel_to_node_coords =
-    // move |x| (x as f32) / (shape_length as f32 / 2.0) - 1.0;
-
-    // all of the delinearized points from the shape descriptor, in -1..1
-    let points = (0u32..ndshape_descriptor.size())
-      .map(|x| ndshape_descriptor.delinearize(x))
-      .map(|p| {
-        glam::UVec3::from_array(p).as_vec3a()
-          / (glam::UVec3::from_array(shape_length).as_vec3a() / 2.0)
-          - 1.0
-      })
-      .collect::<Vec<glam::Vec3A>>();
-
-    let eval_span =
-      info_span!("fidget_point_eval", points = points.len()).entered();
-    // evaluate the fidget tape on all of the points
-    let evaluator = fidget::eval::FloatSliceEval::new(&tape);
-    let values = evaluator.eval(
-      &points.iter().map(|v| v.x).collect::<Vec<_>>(),
-      &points.iter().map(|v| v.y).collect::<Vec<_>>(),
-      &points.iter().map(|v| v.z).collect::<Vec<_>>(),
-      &[],
-    )?;
-    drop(eval_span);
-
-    let surface_nets_span = info_span!("surface_nets").entered();
-    // create a buffer for holding the surface_nets result
-    let mut buffer = SurfaceNetsBuffer::default();
-    surface_nets(
-      &values,
-      &ndshape_descriptor,
-      [0; 3],
-      (glam::UVec3::from_array(shape_length) - 1).to_array(),
-      &mut buffer,
-    );
-    drop(surface_nets_span);
-
-    // convert vertices and triangles into something we can use (what full_mesh
-    // is expecting), and scale them back up for the normal calc.
-    let positions = buffer
-      .positions
-      .iter()
-      // this is to convert from linearized integer coords back to -1..1
-      .map(|a| {
-        glam::Vec3A::from_array(*a)
-          / (glam::UVec3::from_array(shape_length).as_vec3a() / 2.0)
-          - 1.0
-      })
-      // this is to go from -1..1 to the normal scale
-      .collect::<Vec<glam::Vec3A>>();
-    // this uses a chunk operation on the slice because the indices aren't in
-    // triplets
-    let triangles = buffer
-      .indices
-      .chunks(3)
-      .map(|c| glam::UVec3::from_array([c[0], c[1], c[2]]))
-      .collect::<Vec<glam::UVec3>>();
-
-    // get the normals
-    let normals: Vec<glam::Vec3A> = fidget_normals(&positions, &tape)?;
-
-    let mut mesh = BufMesh {
-      positions,
-      triangles,
-      normals,
-    };
-
-    mesh.transform(glam::Vec3A::ZERO, inputs.region.scale);
-    let mesh = if inputs.region.simplify {
-      mosh::simplify_mesh(mesh)
-    } else {
-      mesh
-    };
-
-    Ok(mesh)
+    let tape = ctx.get_tape::<F>(node)?;
+    let vars = bind_vars(&tape, &inputs.bindings);
+    let values =
+      fidget::eval::FloatSliceEval::new(&tape).eval(&xs, &ys, &zs, &vars)?;
+    for (nearest, value) in nearest.iter_mut().zip(values.iter()) {
+      // strictly nearer, so that ties go to the earlier, more deeply nested
+      // material. NaNs never win.
+      if value.abs() < nearest.0 {
+        *nearest = (value.abs(), id);
+      }
+    }
   }
+  Ok(nearest.into_iter().map(|(_, id)| id).collect())
 }
 
 fn simplify_tape<F: fidget::eval::Family>(
   tape: Tape<F>,
   region: [[f32; 2]; 3],
+  vars: &[f32],
 ) -> Result<Tape<F>, fidget::Error> {
   let interval_eval = tape.new_interval_evaluator();
   let (_, simplify) =
-    interval_eval.eval(region[0], region[1], region[2], &[])?;
+    interval_eval.eval(region[0], region[1], region[2], vars)?;
   match simplify {
     Some(simplify) => simplify.simplify(),
     None => Ok(tape),
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    mesher::{MesherDetail, MesherRegion},
+    shape::builder,
+  };
+
+  #[test]
+  fn vertices_get_the_material_they_lie_on() {
+    let inputs = MesherInputs {
+      shape:        builder::min(
+        builder::material(builder::cuboid(1.0, 0.5, 1.0), 2),
+        builder::translate(builder::sphere(0.5), 0.0, 0.75, 0.0),
+      ),
+      region:       MesherRegion {
+        position: glam::Vec3A::ZERO,
+        scale:    glam::Vec3A::splat(1.5),
+        detail:   MesherDetail::Subdivs(5),
+        prune:    false,
+        simplify: false,
+      },
+      gen_collider: false,
+      bindings:     Default::default(),
+    };
+    let mesh = build_mesh_with_family::<fidget::vm::Eval>(&inputs).unwrap();
+    assert_eq!(mesh.materials.len(), mesh.positions.len());
+    for (position, material) in mesh.positions.iter().zip(&mesh.materials) {
+      if position.y < 0.0 {
+        assert_eq!(*material, 2, "{position:?}");
+      } else if position.y > 1.0 {
+        assert_eq!(*material, DEFAULT_MATERIAL, "{position:?}");
+      }
+    }
+  }
+
+  #[test]
+  fn simplification_keeps_material_boundaries() {
+    // a flat slab, half of each material, so its top face is coplanar across
+    // the boundary at x = 0
+    let half = |x: f64, id: u32| {
+      builder::material(
+        builder::translate(builder::cuboid(0.5, 0.5, 1.0), x, 0.0, 0.0),
+        id,
+      )
+    };
+    let inputs = MesherInputs {
+      shape:        builder::min(half(-0.5, 1), half(0.5, 2)),
+      region:       MesherRegion {
+        position: glam::Vec3A::ZERO,
+        scale:    glam::Vec3A::splat(1.5),
+        detail:   MesherDetail::Subdivs(5),
+        prune:    false,
+        simplify: true,
+      },
+      gen_collider: false,
+      bindings:     Default::default(),
+    };
+    let mesh = build_mesh_with_family::<fidget::vm::Eval>(&inputs).unwrap();
+    for id in [1, 2] {
+      let near_boundary = mesh.positions.iter().zip(&mesh.materials).any(
+        |(position, material)| {
+          *material == id && position.y > 0.4 && position.x.abs() < 0.25
+        },
+      );
+      assert!(near_boundary, "material {id} was merged away from x = 0");
+    }
+  }
+
+  #[cfg(all(
+    feature = "jit",
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+  ))]
+  #[test]
+  fn jit_and_vm_meshes_match() {
+    let shapes = [
+      builder::sphere(1.0),
+      builder::cuboid(0.5, 1.0, 0.75),
+      builder::translate(builder::torus(1.0, 0.25), 0.25, 0.0, -0.25),
+      builder::smooth_min_cubic(
+        builder::sphere(0.75),
+        builder::translate(builder::cylinder(0.3, 2.0), 0.5, 0.0, 0.0),
+        0.25,
+      ),
+    ];
+    for shape in shapes {
+      let inputs = MesherInputs {
+        shape,
+        region: MesherRegion {
+          position: glam::Vec3A::ZERO,
+          scale:    glam::Vec3A::splat(1.5),
+          detail:   MesherDetail::Subdivs(5),
+          prune:    false,
+          simplify: false,
+        },
+        gen_collider: false,
+        bindings: Default::default(),
+      };
+      let vm = build_mesh_with_family::<fidget::vm::Eval>(&inputs).unwrap();
+      let jit = build_mesh_with_family::<fidget::jit::Eval>(&inputs).unwrap();
+      assert_eq!(vm.positions, jit.positions);
+      assert_eq!(vm.triangles, jit.triangles);
+      assert_eq!(vm.normals, jit.normals);
+    }
+  }
+}
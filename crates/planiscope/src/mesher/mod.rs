@@ -1,6 +1,9 @@
 pub mod fsn_mesher;
 
-use std::hash::{Hash, Hasher};
+use std::{
+  collections::BTreeMap,
+  hash::{Hash, Hasher},
+};
 
 use bevy_reflect::Reflect;
 use educe::Educe;
@@ -9,7 +12,9 @@ pub use mosh::{BufMesh, FullVertex};
 use serde::{Deserialize, Serialize};
 use tracing::info_span;
 
-use crate::shape::Shape;
+use crate::shape::{
+  Shape, ValidationError, ValidationErrorKind, ValidationReport,
+};
 
 /// The region over which a mesh is generated.
 #[derive(Clone, Debug, Reflect, Educe, Serialize, Deserialize)]
@@ -30,7 +35,35 @@ pub struct MesherRegion {
   pub simplify: bool,
 }
 
+/// How much [`MesherRegion::fit`] grows the bounds of a shape, relative to its
+/// size, so that the surface doesn't touch the edge of the region.
+const FIT_PADDING: f32 = 0.1;
+/// The minimum padding [`MesherRegion::fit`] adds on each side.
+const FIT_MIN_PADDING: f32 = 0.01;
+
 impl MesherRegion {
+  /// Builds a region that encloses the whole of `shape`, or `None` if the
+  /// shape is empty, with the shape's variables bound to `bindings`. See
+  /// [`Shape::bounds`] for the limits of the search.
+  pub fn fit(
+    shape: &Shape,
+    detail: MesherDetail,
+    bindings: &Bindings,
+  ) -> Result<Option<Self>, fidget::Error> {
+    Ok(shape.bounds(bindings)?.map(|bounds| {
+      let half_extents = bounds.half_extents();
+      let padding =
+        (half_extents * FIT_PADDING).max(glam::Vec3A::splat(FIT_MIN_PADDING));
+      Self {
+        position: bounds.center(),
+        scale: half_extents + padding,
+        detail,
+        prune: false,
+        simplify: false,
+      }
+    }))
+  }
+
   pub fn voxel_side_length(&self) -> [u32; 3] {
     match self.detail {
       MesherDetail::Subdivs(x) => [2_u32.pow(x as u32); 3],
@@ -60,14 +93,82 @@ pub enum MesherDetail {
   Exact(u32),
 }
 
+/// Values for the [`Shape::Var`]s in a shape, by name.
+pub type Bindings = BTreeMap<String, f32>;
+
 /// All of the inputs required to build a mesh.
-#[derive(Clone, Debug, Hash, Reflect, Serialize, Deserialize)]
+#[derive(Clone, Debug, Reflect, Educe, Serialize, Deserialize)]
+#[educe(Hash)]
 pub struct MesherInputs {
   pub shape:        Shape,
   pub region:       MesherRegion,
   pub gen_collider: bool,
+  /// The values of the shape's [`Shape::Var`]s. The shape is compiled the
+  /// same way whatever the values are, so one template can be meshed with
+  /// many different bindings.
+  #[serde(default)]
+  #[reflect(ignore)]
+  #[educe(Hash(method = "hash_bindings"))]
+  pub bindings:     Bindings,
 }
 
+impl MesherInputs {
+  /// Returns a copy of the inputs with the shape simplified. Equivalent shapes
+  /// that simplify to the same tree produce the same hash.
+  pub fn simplified(&self) -> Self {
+    Self {
+      shape:        self.shape.simplify(),
+      region:       self.region.clone(),
+      gen_collider: self.gen_collider,
+      bindings:     self.bindings.clone(),
+    }
+  }
+
+  /// Validates the shape (see [`Shape::validate`]) and checks that every
+  /// [`Shape::Var`] in it has a binding.
+  pub fn validate(&self) -> Result<(), ValidationReport> {
+    let mut errors = match self.shape.validate() {
+      Ok(()) => Vec::new(),
+      Err(report) => report.0,
+    };
+    errors.extend(
+      self
+        .shape
+        .vars()
+        .into_iter()
+        .filter(|name| !self.bindings.contains_key(name))
+        .map(|name| ValidationError {
+          path: Vec::new(),
+          kind: ValidationErrorKind::UnboundVariable(name),
+        }),
+    );
+    if errors.is_empty() {
+      Ok(())
+    } else {
+      Err(ValidationReport(errors))
+    }
+  }
+}
+
+/// The evaluator family used by default: fidget's JIT compiler when the `jit`
+/// feature is enabled on a platform it supports, and its interpreter
+/// otherwise.
+#[cfg(all(
+  feature = "jit",
+  target_os = "linux",
+  any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub type DefaultEvalFamily = fidget::jit::Eval;
+/// The evaluator family used by default: fidget's JIT compiler when the `jit`
+/// feature is enabled on a platform it supports, and its interpreter
+/// otherwise.
+#[cfg(not(all(
+  feature = "jit",
+  target_os = "linux",
+  any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+pub type DefaultEvalFamily = fidget::vm::Eval;
+
 #[derive(Clone, Debug, Default)]
 pub struct FastSurfaceNetsMesher;
 
@@ -78,9 +179,26 @@ pub trait Mesher {
     -> Result<BufMesh, fidget::Error>;
 }
 
+/// Lays out `bindings` in the order `tape` expects its variables in. Variables
+/// without a binding are NaN; [`MesherInputs::validate`] reports them.
+pub fn bind_vars<F: fidget::eval::Family>(
+  tape: &Tape<F>,
+  bindings: &Bindings,
+) -> Vec<f32> {
+  let vars = tape.vars();
+  let mut values = vec![f32::NAN; vars.len()];
+  for (name, index) in vars.iter() {
+    if let Some(value) = bindings.get(name) {
+      values[*index as usize] = *value;
+    }
+  }
+  values
+}
+
 pub fn fidget_normals<F: fidget::eval::Family>(
   vertices: &[glam::Vec3A],
   tape: &Tape<F>,
+  vars: &[f32],
 ) -> Result<Vec<glam::Vec3A>, fidget::Error> {
   let _span = info_span!("planiscope::fidget_normals").entered();
 
@@ -91,7 +209,7 @@ pub fn fidget_normals<F: fidget::eval::Family>(
         &vertices.iter().map(|v| v.x).collect::<Vec<_>>(),
         &vertices.iter().map(|v| v.y).collect::<Vec<_>>(),
         &vertices.iter().map(|v| v.z).collect::<Vec<_>>(),
-        &[],
+        vars,
       )?
       .into_iter()
       .map(|g| glam::Vec3A::new(g.dx, g.dy, g.dz))
@@ -105,6 +223,13 @@ pub fn hash_vec3a<H: Hasher>(s: &glam::Vec3A, state: &mut H) {
     .for_each(|v| decorum::hash::FloatHash::float_hash(v, state));
 }
 
+pub fn hash_bindings<H: Hasher>(s: &Bindings, state: &mut H) {
+  s.iter().for_each(|(name, value)| {
+    name.hash(state);
+    decorum::hash::FloatHash::float_hash(value, state);
+  });
+}
+
 pub fn hash_uvec3<H: Hasher>(s: &glam::UVec3, state: &mut H) {
   s.to_array().iter().for_each(|v| Hash::hash(v, state));
 }
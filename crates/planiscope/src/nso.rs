@@ -102,6 +102,211 @@ pub mod volumes {
 
     Ok(max_q_xyz)
   }
+
+  /// An exact box with half-extents `length`, `width` and `height`, whose
+  /// edges are rounded by `radius`. The rounding is taken from inside the
+  /// half-extents, so the AABB of the box doesn't change with `radius`.
+  pub fn nso_rounded_box(
+    length: Node,
+    width: Node,
+    height: Node,
+    radius: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let x = ctx.x();
+    let y = ctx.y();
+    let z = ctx.z();
+
+    let abs_x = ctx.abs(x)?;
+    let abs_y = ctx.abs(y)?;
+    let abs_z = ctx.abs(z)?;
+
+    let length = ctx.sub(length, radius)?;
+    let width = ctx.sub(width, radius)?;
+    let height = ctx.sub(height, radius)?;
+
+    let q_x = ctx.sub(abs_x, length)?;
+    let q_y = ctx.sub(abs_y, width)?;
+    let q_z = ctx.sub(abs_z, height)?;
+
+    let max_q_x_0 = ctx.max(q_x, 0.0)?;
+    let max_q_y_0 = ctx.max(q_y, 0.0)?;
+    let max_q_z_0 = ctx.max(q_z, 0.0)?;
+
+    let outside =
+      super::vectors::nso_magnitude_3d([max_q_x_0, max_q_y_0, max_q_z_0], ctx)?;
+
+    let a = ctx.max(q_y, q_z)?;
+    let b = ctx.max(q_x, a)?;
+    let inside = ctx.min(b, 0.0)?;
+
+    let d = ctx.add(outside, inside)?;
+    ctx.sub(d, radius)
+  }
+
+  /// A torus lying in the XZ plane, centered on the origin. `major` is the
+  /// distance from the origin to the center of the tube, and `minor` is the
+  /// radius of the tube.
+  pub fn nso_torus(
+    major: Node,
+    minor: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let x = ctx.x();
+    let y = ctx.y();
+    let z = ctx.z();
+
+    let dist_xz = super::vectors::nso_magnitude_2d([x, z], ctx)?;
+    let q_x = ctx.sub(dist_xz, major)?;
+    let dist = super::vectors::nso_magnitude_2d([q_x, y], ctx)?;
+    ctx.sub(dist, minor)
+  }
+
+  /// A capsule along the Y axis, centered on the origin. `height` is the
+  /// total length of the segment between the centers of the two end caps.
+  pub fn nso_capsule(
+    height: Node,
+    radius: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let x = ctx.x();
+    let y = ctx.y();
+    let z = ctx.z();
+
+    let half_height = ctx.div(height, 2.0)?;
+    let neg_half_height = ctx.neg(half_height)?;
+    let clamped_y =
+      super::other::nso_clamp(y, neg_half_height, half_height, ctx)?;
+    let new_y = ctx.sub(y, clamped_y)?;
+
+    let dist = super::vectors::nso_magnitude_3d([x, new_y, z], ctx)?;
+    ctx.sub(dist, radius)
+  }
+
+  /// A cone along the Y axis, with its base disk of `radius` at `-height / 2`
+  /// and its tip at `height / 2`.
+  pub fn nso_cone(
+    height: Node,
+    radius: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let x = ctx.x();
+    let y = ctx.y();
+    let z = ctx.z();
+
+    // work in the 2d half-plane of (distance from the axis, height), with the
+    // tip at the origin. `q` is the vector from the tip to the base rim.
+    let half_height = ctx.div(height, 2.0)?;
+    let w_x = super::vectors::nso_magnitude_2d([x, z], ctx)?;
+    let w_y = ctx.sub(y, half_height)?;
+    let q_x = radius;
+    let q_y = ctx.neg(height)?;
+
+    // distance to the slanted side
+    let w_dot_q =
+      super::vectors::nso_dot_product_2d([w_x, w_y], [q_x, q_y], ctx)?;
+    let q_dot_q =
+      super::vectors::nso_dot_product_2d([q_x, q_y], [q_x, q_y], ctx)?;
+    let t = ctx.div(w_dot_q, q_dot_q)?;
+    let t = ctx.max(t, 0.0)?;
+    let t = ctx.min(t, 1.0)?;
+    let a_x = ctx.mul(q_x, t)?;
+    let a_x = ctx.sub(w_x, a_x)?;
+    let a_y = ctx.mul(q_y, t)?;
+    let a_y = ctx.sub(w_y, a_y)?;
+
+    // distance to the base disk
+    let t = ctx.div(w_x, q_x)?;
+    let t = ctx.max(t, 0.0)?;
+    let t = ctx.min(t, 1.0)?;
+    let b_x = ctx.mul(q_x, t)?;
+    let b_x = ctx.sub(w_x, b_x)?;
+    let b_y = ctx.sub(w_y, q_y)?;
+
+    let a_dot_a =
+      super::vectors::nso_dot_product_2d([a_x, a_y], [a_x, a_y], ctx)?;
+    let b_dot_b =
+      super::vectors::nso_dot_product_2d([b_x, b_y], [b_x, b_y], ctx)?;
+    let d = ctx.min(a_dot_a, b_dot_b)?;
+    let d = ctx.sqrt(d)?;
+
+    // the side of the surface we're on; positive outside
+    let side = ctx.mul(w_x, height)?;
+    let rim = ctx.mul(w_y, radius)?;
+    let side = ctx.add(side, rim)?;
+    let base = ctx.add(w_y, height)?;
+    let base = ctx.neg(base)?;
+    let s = ctx.max(side, base)?;
+    let s = super::other::nso_steep_sign(s, ctx)?;
+
+    ctx.mul(d, s)
+  }
+
+  /// An infinite plane through the origin offset by `offset` along its normal
+  /// `(normal_x, normal_y, normal_z)`. The normal doesn't need to be
+  /// normalized. Everything on the side the normal points to is outside.
+  pub fn nso_plane(
+    normal_x: Node,
+    normal_y: Node,
+    normal_z: Node,
+    offset: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let x = ctx.x();
+    let y = ctx.y();
+    let z = ctx.z();
+
+    let magnitude =
+      super::vectors::nso_magnitude_3d([normal_x, normal_y, normal_z], ctx)?;
+    let d = super::vectors::nso_dot_product_3d(
+      [x, y, z],
+      [normal_x, normal_y, normal_z],
+      ctx,
+    )?;
+    let d = ctx.div(d, magnitude)?;
+    ctx.sub(d, offset)
+  }
+
+  /// An ellipsoid with radii `x_radius`, `y_radius` and `z_radius`.
+  ///
+  /// There is no closed form for the distance to an ellipsoid, so this is the
+  /// usual gradient-corrected approximation. It's exact on the axes and for
+  /// spheres, and a close bound elsewhere.
+  pub fn nso_ellipsoid(
+    x_radius: Node,
+    y_radius: Node,
+    z_radius: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let x = ctx.x();
+    let y = ctx.y();
+    let z = ctx.z();
+
+    let x_0 = ctx.div(x, x_radius)?;
+    let y_0 = ctx.div(y, y_radius)?;
+    let z_0 = ctx.div(z, z_radius)?;
+    let k_0 = super::vectors::nso_magnitude_3d([x_0, y_0, z_0], ctx)?;
+
+    let x_1 = ctx.div(x_0, x_radius)?;
+    let y_1 = ctx.div(y_0, y_radius)?;
+    let z_1 = ctx.div(z_0, z_radius)?;
+    let k_1 = super::vectors::nso_magnitude_3d([x_1, y_1, z_1], ctx)?;
+
+    // `k_0 / k_1` always lies between the smallest and largest radius, but
+    // is 0 / 0 at the center, so it's clamped back into that range. this
+    // also keeps intervals around the center finite.
+    let smallest = ctx.min(x_radius, y_radius)?;
+    let smallest = ctx.min(smallest, z_radius)?;
+    let largest = ctx.max(x_radius, y_radius)?;
+    let largest = ctx.max(largest, z_radius)?;
+    let k_1 = ctx.max(k_1, 1.0e-9)?;
+    let ratio = ctx.div(k_0, k_1)?;
+    let ratio = ctx.min(ratio, largest)?;
+    let ratio = ctx.max(ratio, smallest)?;
+
+    let a = ctx.sub(k_0, 1.0)?;
+    ctx.mul(a, ratio)
+  }
 }
 
 pub mod csg {
@@ -338,7 +543,7 @@ pub mod vectors {
     ctx.add(v0, v1)
   }
 
-  /// Returns the dot product of the given 2d vectors.
+  /// Returns the dot product of the given 3d vectors.
   pub fn nso_dot_product_3d(
     a: [Node; 3],
     b: [Node; 3],
@@ -346,7 +551,7 @@ pub mod vectors {
   ) -> Result<Node, fidget::Error> {
     let v0 = ctx.mul(a[0], b[0])?;
     let v1 = ctx.mul(a[1], b[1])?;
-    let v2 = ctx.mul(a[2], a[2])?;
+    let v2 = ctx.mul(a[2], b[2])?;
     let sum = ctx.add(v0, v1)?;
     ctx.add(sum, v2)
   }
@@ -422,6 +627,30 @@ pub mod other {
     ctx.min(sum_plus_one, 1.0)
   }
 
+  /// Approximates `sign(shape)` by clamping a steep slope through zero to
+  /// [-1, 1]. Unlike `nso_normalized_hardstep`, this is safe to use at exact
+  /// 0. Multiplying a distance by this only shrinks it within 1e-6 of zero, so
+  /// the result is still a valid bound.
+  pub fn nso_steep_sign(
+    shape: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let steep = ctx.mul(shape, 1.0e6)?;
+    let steep = ctx.min(steep, 1.0)?;
+    ctx.max(steep, -1.0)
+  }
+
+  /// Approximates a step function that is 0 for `shape <= 0` and 1 for
+  /// `shape > 1e-6`, with a steep ramp in between.
+  pub fn nso_steep_step(
+    shape: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let steep = ctx.mul(shape, 1.0e6)?;
+    let steep = ctx.min(steep, 1.0)?;
+    ctx.max(steep, 0.0)
+  }
+
   pub fn nso_clamp(
     shape: Node,
     min: Node,
@@ -535,6 +764,609 @@ pub mod other {
   }
 }
 
+pub mod repetition {
+  use fidget::{context::Node, Context};
+
+  use super::other::{nso_steep_sign, nso_steep_step};
+
+  /// Returns the offset of the nearest of `count` instances spaced `spacing`
+  /// apart and centered on the origin, along the axis `coord`.
+  ///
+  /// Fidget has no floor or modulo operation, so the instance index is built
+  /// from one step per cell boundary instead. Each extra instance only costs a
+  /// handful of nodes.
+  fn nso_staircase(
+    coord: Node,
+    spacing: f32,
+    count: u32,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let first = -(count as f32 - 1.0) / 2.0 * spacing;
+    let mut offset = ctx.constant(first.into());
+    for k in 1..count {
+      let boundary = (k as f32 - count as f32 / 2.0) * spacing;
+      let past = ctx.sub(coord, boundary)?;
+      let step = nso_steep_step(past, ctx)?;
+      let step = ctx.mul(step, spacing)?;
+      offset = ctx.add(offset, step)?;
+    }
+    Ok(offset)
+  }
+
+  /// Repeats `root` `count` times along each axis, `spacing` apart, centered on
+  /// the origin. A count of 0 or 1 leaves that axis alone.
+  ///
+  /// As with all domain repetition, each instance should fit within its cell
+  /// for the result to be a good distance estimate.
+  pub fn nso_repeat(
+    root: Node,
+    spacing: [f32; 3],
+    count: [u32; 3],
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let axes = [ctx.x(), ctx.y(), ctx.z()];
+    let mut new_axes = axes;
+    for i in 0..3 {
+      if count[i] > 1 {
+        let offset = nso_staircase(axes[i], spacing[i], count[i], ctx)?;
+        new_axes[i] = ctx.sub(axes[i], offset)?;
+      }
+    }
+    ctx.remap_xyz(root, new_axes)
+  }
+
+  /// How many doublings of the cell width [`nso_repeat_infinite`] folds back,
+  /// so it repeats out to `2^24` cells from the origin. That's as far as f32
+  /// coordinates can tell neighbouring cells apart anyway.
+  pub const INFINITE_REPEAT_DOUBLINGS: i32 = 24;
+
+  /// Folds `coord` into the cell of width `spacing` centered on the nearest
+  /// multiple of `spacing`.
+  ///
+  /// Without a modulo operation, the distance from the origin is reduced like
+  /// long division: every power-of-two multiple of the cell width that still
+  /// fits is subtracted, largest first. The values only ever shrink, so no
+  /// precision is lost to large offsets.
+  fn nso_fold(
+    coord: Node,
+    spacing: f32,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    // the fold is odd, so fold the distance from the origin and restore the
+    // sign afterwards
+    let sign = nso_steep_sign(coord, ctx)?;
+    let distance = ctx.abs(coord)?;
+    let mut shifted = ctx.add(distance, spacing / 2.0)?;
+    for k in (0..INFINITE_REPEAT_DOUBLINGS).rev() {
+      let width = spacing * 2.0_f32.powi(k);
+      let past = ctx.sub(shifted, width)?;
+      let step = nso_steep_step(past, ctx)?;
+      let step = ctx.mul(step, width)?;
+      shifted = ctx.sub(shifted, step)?;
+    }
+    let folded = ctx.sub(shifted, spacing / 2.0)?;
+    ctx.mul(folded, sign)
+  }
+
+  /// Repeats `root` endlessly along each axis, `spacing` apart, with an
+  /// instance centered on the origin. A spacing of 0 leaves that axis alone.
+  ///
+  /// Each axis costs about a hundred nodes, regardless of how many instances
+  /// a region covers.
+  pub fn nso_repeat_infinite(
+    root: Node,
+    spacing: [f32; 3],
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let axes = [ctx.x(), ctx.y(), ctx.z()];
+    let mut new_axes = axes;
+    for i in 0..3 {
+      if spacing[i] > 0.0 {
+        new_axes[i] = nso_fold(axes[i], spacing[i], ctx)?;
+      }
+    }
+    ctx.remap_xyz(root, new_axes)
+  }
+
+  /// Mirrors the half of `root` on the positive side of the plane
+  /// `dot(p, normal) = offset` onto the negative side, replacing whatever was
+  /// there.
+  pub fn nso_mirror(
+    root: Node,
+    normal: [f32; 3],
+    offset: f32,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let normal = glam::Vec3A::from_array(normal).normalize_or_zero();
+    let x = ctx.x();
+    let y = ctx.y();
+    let z = ctx.z();
+
+    let a = ctx.mul(x, normal.x)?;
+    let b = ctx.mul(y, normal.y)?;
+    let c = ctx.mul(z, normal.z)?;
+    let d = ctx.add(a, b)?;
+    let d = ctx.add(d, c)?;
+    let d = ctx.sub(d, offset)?;
+
+    // only points behind the plane get reflected
+    let behind = ctx.min(d, 0.0)?;
+    let behind = ctx.mul(behind, 2.0)?;
+
+    let new_x = ctx.mul(behind, normal.x)?;
+    let new_x = ctx.sub(x, new_x)?;
+    let new_y = ctx.mul(behind, normal.y)?;
+    let new_y = ctx.sub(y, new_y)?;
+    let new_z = ctx.mul(behind, normal.z)?;
+    let new_z = ctx.sub(z, new_z)?;
+    ctx.remap_xyz(root, [new_x, new_y, new_z])
+  }
+
+  /// Repeats `root` `count` times around the Y axis. The sector of `root`
+  /// centered on the +X axis, `2 * PI / count` radians wide, is the one that
+  /// gets repeated.
+  pub fn nso_polar_repeat(
+    root: Node,
+    count: u32,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    if count < 2 {
+      return Ok(root);
+    }
+    let x = ctx.x();
+    let y = ctx.y();
+    let z = ctx.z();
+
+    let sector = std::f32::consts::TAU / count as f32;
+    // which side of each sector boundary we're on; positive when
+    // counter-clockwise (from +X towards +Z) of it
+    let mut sides = Vec::with_capacity(count as usize);
+    for k in 1..=count {
+      let (sin, cos) = ((k as f32 - 0.5) * sector).sin_cos();
+      let a = ctx.mul(z, cos)?;
+      let b = ctx.mul(x, sin)?;
+      let side = ctx.sub(a, b)?;
+      sides.push(nso_steep_step(side, ctx)?);
+    }
+
+    // rotate each sector back onto the first. the first sector is the
+    // identity, so anywhere no sector claims is left alone.
+    let mut new_x = x;
+    let mut new_z = z;
+    for k in 1..count as usize {
+      let not_past_end = ctx.sub(1.0, sides[k])?;
+      let weight = ctx.mul(sides[k - 1], not_past_end)?;
+
+      let (sin, cos) = (k as f32 * sector).sin_cos();
+      let a = ctx.mul(x, cos)?;
+      let b = ctx.mul(z, sin)?;
+      let rotated_x = ctx.add(a, b)?;
+      let a = ctx.mul(z, cos)?;
+      let b = ctx.mul(x, sin)?;
+      let rotated_z = ctx.sub(a, b)?;
+
+      let delta_x = ctx.sub(rotated_x, x)?;
+      let delta_x = ctx.mul(delta_x, weight)?;
+      new_x = ctx.add(new_x, delta_x)?;
+      let delta_z = ctx.sub(rotated_z, z)?;
+      let delta_z = ctx.mul(delta_z, weight)?;
+      new_z = ctx.add(new_z, delta_z)?;
+    }
+
+    ctx.remap_xyz(root, [new_x, y, new_z])
+  }
+}
+
+pub mod deform {
+  use fidget::{context::Node, Context};
+
+  /// Twists `root` around the Y axis by `rate` radians per unit of Y, turning
+  /// from +X towards +Z as Y increases.
+  ///
+  /// Like all non-rigid deformations this doesn't preserve distances, so the
+  /// result is only a distance estimate.
+  pub fn nso_twist(
+    root: Node,
+    rate: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let x = ctx.x();
+    let y = ctx.y();
+    let z = ctx.z();
+
+    let angle = ctx.mul(y, rate)?;
+    let cos = ctx.cos(angle)?;
+    let sin = ctx.sin(angle)?;
+
+    // rotate the sample point backwards by the angle at its height
+    let a = ctx.mul(x, cos)?;
+    let b = ctx.mul(z, sin)?;
+    let new_x = ctx.add(a, b)?;
+    let a = ctx.mul(z, cos)?;
+    let b = ctx.mul(x, sin)?;
+    let new_z = ctx.sub(a, b)?;
+    ctx.remap_xyz(root, [new_x, y, new_z])
+  }
+
+  /// Bends the Y axis of `root` in the XY plane, curving towards +X for a
+  /// positive `rate`. The bend angle is `rate` radians per unit of Y.
+  pub fn nso_bend(
+    root: Node,
+    rate: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let x = ctx.x();
+    let y = ctx.y();
+    let z = ctx.z();
+
+    let angle = ctx.mul(y, rate)?;
+    let cos = ctx.cos(angle)?;
+    let sin = ctx.sin(angle)?;
+
+    let a = ctx.mul(x, cos)?;
+    let b = ctx.mul(y, sin)?;
+    let new_x = ctx.sub(a, b)?;
+    let a = ctx.mul(x, sin)?;
+    let b = ctx.mul(y, cos)?;
+    let new_y = ctx.add(a, b)?;
+    ctx.remap_xyz(root, [new_x, new_y, z])
+  }
+
+  /// Scales the XZ cross-section of `root` by `1 + rate * y`. Only meaningful
+  /// where that factor is positive.
+  pub fn nso_taper(
+    root: Node,
+    rate: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let x = ctx.x();
+    let y = ctx.y();
+    let z = ctx.z();
+
+    let factor = ctx.mul(y, rate)?;
+    let factor = ctx.add(factor, 1.0)?;
+    let new_x = ctx.div(x, factor)?;
+    let new_z = ctx.div(z, factor)?;
+    ctx.remap_xyz(root, [new_x, y, new_z])
+  }
+
+  /// Shears `root` so that it moves `x_rate` along X and `z_rate` along Z per
+  /// unit of Y.
+  pub fn nso_shear(
+    root: Node,
+    x_rate: Node,
+    z_rate: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let x = ctx.x();
+    let y = ctx.y();
+    let z = ctx.z();
+
+    let offset_x = ctx.mul(y, x_rate)?;
+    let new_x = ctx.sub(x, offset_x)?;
+    let offset_z = ctx.mul(y, z_rate)?;
+    let new_z = ctx.sub(z, offset_z)?;
+    ctx.remap_xyz(root, [new_x, y, new_z])
+  }
+}
+
+pub mod hollow {
+  use fidget::{context::Node, Context};
+
+  /// Moves the surface of `root` outwards by `distance`, or inwards for a
+  /// negative `distance`. Exact if `root` is.
+  ///
+  /// Growing a shape rounds its convex edges and corners with radius
+  /// `distance`.
+  pub fn nso_offset(
+    root: Node,
+    distance: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    ctx.sub(root, distance)
+  }
+
+  /// Hollows `root` into a shell `thickness` thick, centered on its surface.
+  /// Exact if `root` is.
+  pub fn nso_shell(
+    root: Node,
+    thickness: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let distance = ctx.abs(root)?;
+    let half = ctx.div(thickness, 2.0)?;
+    ctx.sub(distance, half)
+  }
+
+  /// Shells `root` `count` times over, each pass after the first splitting
+  /// every wall in two. The result is `2^(count - 1)` concentric walls around
+  /// the surface of `root`, each `thickness` thick and `thickness` apart: a
+  /// count of 1 is a plain [`nso_shell`], 2 gives two walls and 3 gives four.
+  /// A count of 0 leaves `root` alone.
+  pub fn nso_onion(
+    root: Node,
+    thickness: Node,
+    count: u32,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let mut shape = root;
+    for pass in 0..count {
+      // earlier passes place the later, thinner ones
+      let half = ctx.mul(thickness, 2.0_f32.powi((count - pass) as i32 - 2))?;
+      let distance = ctx.abs(shape)?;
+      shape = ctx.sub(distance, half)?;
+    }
+    Ok(shape)
+  }
+}
+
+pub mod noise {
+  use fidget::{context::Node, Context};
+
+  use super::{
+    other::{nso_steep_sign, nso_steep_step},
+    repetition::INFINITE_REPEAT_DOUBLINGS,
+  };
+
+  /// How far [`nso_hash`] stretches its first sine before wrapping it with a
+  /// second one. Anything much larger than a full turn scrambles neighbouring
+  /// lattice points; this is the usual constant from shader hashes.
+  const HASH_SCALE: f32 = 43758.547;
+
+  /// The largest value [`nso_gradient_noise`] can reach before it's scaled
+  /// into `-1..1`: half the diagonal of a cell times the longest gradient.
+  const NOISE_PEAK: f32 = 1.5;
+
+  /// A SplitMix64 step, used to derive the hashes of a noise octave from its
+  /// seed. Implemented here rather than pulled from a crate so that a seed
+  /// always produces the same noise.
+  fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+  }
+
+  /// Returns a uniformly distributed value in `0..1`.
+  fn unit(state: &mut u64) -> f32 {
+    (splitmix64(state) >> 40) as f32 / (1u64 << 24) as f32
+  }
+
+  /// Rounds `coord` down to a whole number, out to `2^24` from the origin.
+  ///
+  /// As in [`nso_repeat_infinite`](super::repetition::nso_repeat_infinite),
+  /// the distance from the origin is taken apart like long division, one
+  /// steep step per power of two. The whole part is summed from the steps
+  /// alone, so wherever a region stays within one cell, the steps and the
+  /// result are constant and interval evaluation prunes them away.
+  fn nso_floor(coord: Node, ctx: &mut Context) -> Result<Node, fidget::Error> {
+    let sign = nso_steep_sign(coord, ctx)?;
+    let mut remainder = ctx.abs(coord)?;
+    let mut whole = ctx.constant(0.0);
+    for k in (0..INFINITE_REPEAT_DOUBLINGS).rev() {
+      let width = 2.0_f32.powi(k);
+      let past = ctx.sub(remainder, width)?;
+      let step = nso_steep_step(past, ctx)?;
+      let step = ctx.mul(step, width)?;
+      remainder = ctx.sub(remainder, step)?;
+      whole = ctx.add(whole, step)?;
+    }
+    // below the origin, the whole part of the distance rounds up instead
+    let whole = ctx.mul(whole, sign)?;
+    let below = ctx.neg(coord)?;
+    let below = nso_steep_step(below, ctx)?;
+    ctx.sub(whole, below)
+  }
+
+  /// Hashes the lattice point `corner` to a value in `-1..1`, by wrapping a
+  /// greatly stretched sine of a seeded plane through it. `key` and `phase`
+  /// pick the plane.
+  fn nso_hash(
+    corner: [Node; 3],
+    key: [f32; 3],
+    phase: f32,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let mut along = ctx.constant(phase.into());
+    for (coord, key) in corner.into_iter().zip(key) {
+      let term = ctx.mul(coord, key)?;
+      along = ctx.add(along, term)?;
+    }
+    let inner = ctx.sin(along)?;
+    let inner = ctx.mul(inner, HASH_SCALE)?;
+    ctx.sin(inner)
+  }
+
+  /// The quintic fade `6t^5 - 15t^4 + 10t^3`, which eases the weights of a
+  /// cell's corners so the noise is smooth across cell boundaries.
+  fn nso_fade(t: Node, ctx: &mut Context) -> Result<Node, fidget::Error> {
+    let a = ctx.mul(t, 6.0)?;
+    let a = ctx.sub(a, 15.0)?;
+    let a = ctx.mul(a, t)?;
+    let a = ctx.add(a, 10.0)?;
+    let t_2 = ctx.mul(t, t)?;
+    let t_3 = ctx.mul(t_2, t)?;
+    ctx.mul(a, t_3)
+  }
+
+  /// Smooth, seeded 3D gradient noise in `-1..1`, on a lattice of cells
+  /// `1 / frequency` wide.
+  ///
+  /// Each lattice point gets a gradient hashed from its coordinates and the
+  /// seed. Within a cell, the noise is the sum of each corner's gradient
+  /// dotted with the offset from that corner, weighted by quintic fades of
+  /// the position in the cell, as in Perlin's improved noise. The lattice
+  /// cell is found with steep steps, which interval evaluation prunes away
+  /// wherever a region stays within one cell.
+  pub fn nso_gradient_noise(
+    seed: u32,
+    frequency: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let mut state = seed as u64;
+    let hashes = [(); 3].map(|_| {
+      let key = [(); 3].map(|_| {
+        // keys well away from 0, of either sign
+        let magnitude = 0.5 + unit(&mut state);
+        if unit(&mut state) < 0.5 {
+          -magnitude
+        } else {
+          magnitude
+        }
+      });
+      let phase = unit(&mut state) * std::f32::consts::TAU;
+      (key, phase)
+    });
+
+    // the near and far lattice coordinates of the cell, the offsets from
+    // them and their weights, along each axis
+    let axes = [ctx.x(), ctx.y(), ctx.z()];
+    let mut corners = [[axes[0]; 2]; 3];
+    let mut offsets = corners;
+    let mut weights = corners;
+    for (i, axis) in axes.into_iter().enumerate() {
+      let coord = ctx.mul(axis, frequency)?;
+      let near = nso_floor(coord, ctx)?;
+      let offset = ctx.sub(coord, near)?;
+      let fade = nso_fade(offset, ctx)?;
+      corners[i] = [near, ctx.add(near, 1.0)?];
+      offsets[i] = [offset, ctx.sub(offset, 1.0)?];
+      weights[i] = [ctx.sub(1.0, fade)?, fade];
+    }
+
+    let mut sum = ctx.constant(0.0);
+    for corner in 0..8 {
+      let side = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+      let point = [0, 1, 2].map(|i| corners[i][side[i]]);
+      let offset = [0, 1, 2].map(|i| offsets[i][side[i]]);
+
+      let mut dot = ctx.constant(0.0);
+      for (i, (key, phase)) in hashes.iter().enumerate() {
+        let gradient = nso_hash(point, *key, *phase, ctx)?;
+        let term = ctx.mul(gradient, offset[i])?;
+        dot = ctx.add(dot, term)?;
+      }
+      let weight = ctx.mul(weights[0][side[0]], weights[1][side[1]])?;
+      let weight = ctx.mul(weight, weights[2][side[2]])?;
+      let contribution = ctx.mul(dot, weight)?;
+      sum = ctx.add(sum, contribution)?;
+    }
+
+    // the clamp only changes intervals, which can overshoot the true range
+    let scaled = ctx.div(sum, NOISE_PEAK)?;
+    let scaled = ctx.min(scaled, 1.0)?;
+    ctx.max(scaled, -1.0)
+  }
+
+  /// Fractal Brownian motion: `octaves` layers of [`nso_gradient_noise`], each
+  /// `lacunarity` times the frequency and `gain` times the amplitude of the
+  /// last. The result is normalized back into `-1..1`.
+  pub fn nso_fbm(
+    seed: u32,
+    octaves: u32,
+    frequency: Node,
+    lacunarity: Node,
+    gain: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    if octaves == 0 {
+      return Ok(ctx.constant(0.0));
+    }
+    let mut frequency = frequency;
+    let mut amplitude = ctx.constant(1.0);
+    let mut total_amplitude = ctx.constant(0.0);
+    let mut sum = ctx.constant(0.0);
+    for octave in 0..octaves {
+      // each octave gets its own gradients, so they don't line up
+      let octave_seed = seed.wrapping_add(octave.wrapping_mul(0x9e3779b9));
+      let layer = nso_gradient_noise(octave_seed, frequency, ctx)?;
+      let layer = ctx.mul(layer, amplitude)?;
+      sum = ctx.add(sum, layer)?;
+      total_amplitude = ctx.add(total_amplitude, amplitude)?;
+
+      frequency = ctx.mul(frequency, lacunarity)?;
+      amplitude = ctx.mul(amplitude, gain)?;
+    }
+    ctx.div(sum, total_amplitude)
+  }
+
+  #[cfg(test)]
+  mod test {
+    use super::*;
+
+    fn samples() -> Vec<[f64; 3]> {
+      (0..64)
+        .map(|i| {
+          let i = i as f64;
+          [i * 0.37 - 10.0, (i * 1.3).sin() * 5.0, i * -0.21 + 3.0]
+        })
+        .collect()
+    }
+
+    fn eval_noise(seed: u32) -> Vec<f64> {
+      let mut ctx = Context::new();
+      let frequency = ctx.constant(0.5);
+      let node = nso_gradient_noise(seed, frequency, &mut ctx).unwrap();
+      samples()
+        .into_iter()
+        .map(|[x, y, z]| ctx.eval_xyz(node, x, y, z).unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn noise_is_seeded_and_bounded() {
+      let a = eval_noise(1);
+      assert_eq!(a, eval_noise(1));
+      assert_ne!(a, eval_noise(2));
+
+      assert!(a.iter().all(|v| (-1.0..=1.0).contains(v)));
+      let min = a.iter().copied().fold(f64::INFINITY, f64::min);
+      let max = a.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+      assert!(max - min > 0.25, "noise is too flat: {min}..{max}");
+    }
+
+    #[test]
+    fn noise_is_smooth_and_vanishes_on_the_lattice() {
+      let mut ctx = Context::new();
+      let frequency = ctx.constant(1.0);
+      let node = nso_gradient_noise(3, frequency, &mut ctx).unwrap();
+
+      for [x, y, z] in [[0.0, 0.0, 0.0], [-3.0, 2.0, 5.0], [7.0, -1.0, -4.0]] {
+        let v = ctx.eval_xyz(node, x, y, z).unwrap();
+        assert!(v.abs() < 1e-4, "{v} at {x}, {y}, {z}");
+      }
+      for x in [-2.0, 0.0, 1.0] {
+        let a = ctx.eval_xyz(node, x - 1e-3, 0.3, -0.6).unwrap();
+        let b = ctx.eval_xyz(node, x + 1e-3, 0.3, -0.6).unwrap();
+        assert!((a - b).abs() < 0.01, "{a} and {b} around {x}");
+      }
+    }
+
+    #[test]
+    fn fbm_intervals_contain_samples() {
+      let mut ctx = Context::new();
+      let frequency = ctx.constant(0.5);
+      let lacunarity = ctx.constant(2.0);
+      let gain = ctx.constant(0.5);
+      let node = nso_fbm(7, 4, frequency, lacunarity, gain, &mut ctx).unwrap();
+
+      let tape: fidget::eval::Tape<fidget::vm::Eval> =
+        ctx.get_tape(node).unwrap();
+      let (interval, _) = tape
+        .new_interval_evaluator()
+        .eval([-10.0, 14.0], [-5.0, 5.0], [-11.0, 3.0], &[])
+        .unwrap();
+      assert!(interval.lower() >= -1.0 - 1e-5);
+      assert!(interval.upper() <= 1.0 + 1e-5);
+      for [x, y, z] in samples() {
+        let v = ctx.eval_xyz(node, x, y, z).unwrap() as f32;
+        assert!(interval.lower() <= v && v <= interval.upper());
+      }
+    }
+  }
+}
+
 pub mod smooth {
   use fidget::{context::Node, Context};
 
@@ -561,179 +1393,1019 @@ pub mod smooth {
 
     ctx.sub(v2, v)
   }
+
+  /// The smooth counterpart of `max`, for blended intersections.
+  pub fn nso_smooth_max_cubic(
+    lhs: Node,
+    rhs: Node,
+    k: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let lhs = ctx.neg(lhs)?;
+    let rhs = ctx.neg(rhs)?;
+    let v = nso_smooth_min_cubic(lhs, rhs, k, ctx)?;
+    ctx.neg(v)
+  }
+
+  /// Smoothly subtracts `rhs` from `lhs`.
+  pub fn nso_smooth_difference_cubic(
+    lhs: Node,
+    rhs: Node,
+    k: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let rhs = ctx.neg(rhs)?;
+    nso_smooth_max_cubic(lhs, rhs, k, ctx)
+  }
+
+  /// Like `nso_smooth_min_cubic`, but with a quadratic falloff. The blend is
+  /// only C1 continuous, but it's a little cheaper and rounder.
+  pub fn nso_smooth_min_quadratic(
+    lhs: Node,
+    rhs: Node,
+    k: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let v = ctx.sub(lhs, rhs)?;
+    let v = ctx.abs(v)?;
+    let v = ctx.sub(k, v)?;
+    let v = ctx.max(v, 0.0)?;
+    let h = ctx.div(v, k)?;
+
+    let v = ctx.square(h)?;
+    let v = ctx.mul(v, k)?;
+    let v = ctx.mul(v, 0.25)?;
+    let v2 = ctx.min(lhs, rhs)?;
+
+    ctx.sub(v2, v)
+  }
+
+  /// The exponential smooth minimum, `-k * ln(exp(-lhs / k) + exp(-rhs /
+  /// k))`. Unlike the polynomial versions it blends everywhere, not just
+  /// within `k` of the seam.
+  pub fn nso_smooth_min_exponential(
+    lhs: Node,
+    rhs: Node,
+    k: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    // rewritten as `min - k * ln(1 + exp(-|lhs - rhs| / k))` so that the log
+    // only ever sees values in [1, 2], which keeps it accurate.
+    let v = ctx.sub(lhs, rhs)?;
+    let v = ctx.abs(v)?;
+    let v = ctx.div(v, k)?;
+    let v = ctx.neg(v)?;
+    let v = ctx.exp(v)?;
+    let v = nso_ln_1p_unit(v, ctx)?;
+    let v = ctx.mul(v, k)?;
+    let v2 = ctx.min(lhs, rhs)?;
+
+    ctx.sub(v2, v)
+  }
+
+  /// Computes `ln(1 + u)` for `u` in [0, 1]. Fidget has no logarithm, so this
+  /// uses the series `ln(1 + u) = 2 * atanh(u / (2 + u))`, which converges
+  /// quickly over that range; it's accurate to about 1e-7.
+  fn nso_ln_1p_unit(u: Node, ctx: &mut Context) -> Result<Node, fidget::Error> {
+    let denom = ctx.add(u, 2.0)?;
+    let s = ctx.div(u, denom)?;
+    let s2 = ctx.square(s)?;
+
+    // horner's method over the odd powers of s
+    let mut sum = ctx.constant(1.0 / 13.0);
+    for n in [11.0, 9.0, 7.0, 5.0, 3.0, 1.0] {
+      let v = ctx.mul(sum, s2)?;
+      sum = ctx.add(v, 1.0 / n)?;
+    }
+    let v = ctx.mul(sum, s)?;
+    ctx.mul(v, 2.0)
+  }
+
+  /// A union whose seam is cut by a 45 degree chamfer of size `r`.
+  pub fn nso_chamfer_min(
+    lhs: Node,
+    rhs: Node,
+    r: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let v = ctx.add(lhs, rhs)?;
+    let v = ctx.sub(v, r)?;
+    let v = ctx.mul(v, std::f64::consts::FRAC_1_SQRT_2)?;
+    let v2 = ctx.min(lhs, rhs)?;
+
+    ctx.min(v2, v)
+  }
+
+  /// A union whose seam is filled with a quarter circle of radius `r`. Unlike
+  /// the smooth minimums, this keeps a correct distance field outside.
+  pub fn nso_round_min(
+    lhs: Node,
+    rhs: Node,
+    r: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let u_x = ctx.sub(r, lhs)?;
+    let u_x = ctx.max(u_x, 0.0)?;
+    let u_y = ctx.sub(r, rhs)?;
+    let u_y = ctx.max(u_y, 0.0)?;
+    let length = super::vectors::nso_magnitude_2d([u_x, u_y], ctx)?;
+
+    let v = ctx.min(lhs, rhs)?;
+    let v = ctx.max(v, r)?;
+
+    ctx.sub(v, length)
+  }
+
+  #[cfg(test)]
+  mod test {
+    use float_cmp::approx_eq;
+
+    use super::*;
+
+    type SmoothOp =
+      fn(Node, Node, Node, &mut Context) -> Result<Node, fidget::Error>;
+
+    /// Evaluates `op` with `lhs` and `rhs` as the X and Y coordinates.
+    fn eval_op(op: SmoothOp, lhs: f64, rhs: f64, k: f64) -> f64 {
+      let mut ctx = Context::new();
+      let x = ctx.x();
+      let y = ctx.y();
+      let k = ctx.constant(k);
+      let node = op(x, y, k, &mut ctx).unwrap();
+      ctx.eval_xyz(node, lhs, rhs, 0.0).unwrap()
+    }
+
+    fn assert_op(op: SmoothOp, lhs: f64, rhs: f64, k: f64, expected: f64) {
+      let actual = eval_op(op, lhs, rhs, k);
+      assert!(
+        approx_eq!(f64, actual, expected, epsilon = 1e-5),
+        "expected {expected} for ({lhs}, {rhs}, {k}), got {actual}"
+      );
+    }
+
+    #[test]
+    fn smooth_ops_blend_only_near_the_seam() {
+      assert_op(nso_smooth_min_cubic, 0.0, 0.0, 1.0, -1.0 / 6.0);
+      assert_op(nso_smooth_min_cubic, 2.0, 0.0, 1.0, 0.0);
+      assert_op(nso_smooth_max_cubic, 0.0, 0.0, 1.0, 1.0 / 6.0);
+      assert_op(nso_smooth_max_cubic, 2.0, 0.0, 1.0, 2.0);
+      assert_op(nso_smooth_difference_cubic, 0.0, 0.0, 1.0, 1.0 / 6.0);
+      assert_op(nso_smooth_difference_cubic, 1.0, 3.0, 1.0, 1.0);
+      assert_op(nso_smooth_min_quadratic, 0.0, 0.0, 1.0, -0.25);
+      assert_op(nso_smooth_min_quadratic, 0.5, 0.0, 1.0, -0.0625);
+      assert_op(nso_smooth_min_quadratic, -2.0, 0.0, 1.0, -2.0);
+    }
+
+    #[test]
+    fn smooth_min_exponential_matches_closed_form() {
+      for (lhs, rhs, k) in [
+        (0.0, 0.0, 1.0),
+        (5.0, 0.0, 1.0),
+        (0.3, -0.2, 0.5),
+        (1.0, 2.0, 4.0),
+      ] {
+        let expected: f64 = -k * ((-lhs / k).exp() + (-rhs / k).exp()).ln();
+        assert_op(nso_smooth_min_exponential, lhs, rhs, k, expected);
+      }
+    }
+
+    #[test]
+    fn chamfer_and_round_cut_the_seam() {
+      assert_op(
+        nso_chamfer_min,
+        0.0,
+        0.0,
+        1.0,
+        -std::f64::consts::FRAC_1_SQRT_2,
+      );
+      assert_op(
+        nso_chamfer_min,
+        1.0,
+        1.0,
+        1.0,
+        std::f64::consts::FRAC_1_SQRT_2,
+      );
+      assert_op(nso_chamfer_min, 3.0, -1.0, 1.0, -1.0);
+      assert_op(nso_round_min, 0.0, 0.0, 1.0, 1.0 - 2.0_f64.sqrt());
+      assert_op(nso_round_min, 2.0, 2.0, 1.0, 2.0);
+      assert_op(nso_round_min, 0.5, 0.5, 1.0, 1.0 - 0.5_f64.sqrt());
+      assert_op(nso_round_min, 3.0, -1.0, 1.0, -1.0);
+    }
+  }
+}
+
+pub mod spline {
+  use fidget::{context::Node, Context};
+
+  /// The number of straight segments each span of a spline is divided into
+  /// before sweeping.
+  const SPAN_SUBDIVISIONS: usize = 8;
+
+  /// How far, as a fraction of its length, each segment reaches past the
+  /// miter planes at its ends so that neighbouring segments overlap.
+  const MITER_OVERLAP: f32 = 0.01;
+
+  /// The slope of the cut at each miter plane. It needs to be steep enough
+  /// that the cut stays outside of the profile until the overlap is used up.
+  const MITER_STEEPNESS: f32 = 1.0e6;
+
+  /// Samples a Catmull-Rom spline through `points`. A `tension` of 0.5 gives
+  /// the standard Catmull-Rom curve, and 0.0 gives straight lines between the
+  /// points. The first and last points are extended with ghost points so
+  /// that the curve passes through every point.
+  pub fn catmull_rom_samples(
+    points: &[[f32; 3]],
+    tension: f32,
+  ) -> Vec<glam::Vec3A> {
+    let points = points
+      .iter()
+      .map(|p| glam::Vec3A::from_array(*p))
+      .collect::<Vec<_>>();
+    if points.len() < 2 {
+      return points;
+    }
+
+    // add first and last ghost points
+    let first_point = points[0] * 2.0 - points[1];
+    let last_point = points[points.len() - 1] * 2.0 - points[points.len() - 2];
+    let padded = std::iter::once(first_point)
+      .chain(points.iter().copied())
+      .chain(std::iter::once(last_point))
+      .collect::<Vec<_>>();
+
+    let mut samples = vec![points[0]];
+    for window in padded.windows(4) {
+      let [p0, p1, p2, p3] = [window[0], window[1], window[2], window[3]];
+      let m1 = (p2 - p0) * tension;
+      let m2 = (p3 - p1) * tension;
+
+      for i in 1..=SPAN_SUBDIVISIONS {
+        let t = i as f32 / SPAN_SUBDIVISIONS as f32;
+        let t2 = t * t;
+        let t3 = t2 * t;
+        let h00 = 2.0 * t3 - 3.0 * t2 + 1.0;
+        let h10 = t3 - 2.0 * t2 + t;
+        let h01 = -2.0 * t3 + 3.0 * t2;
+        let h11 = t3 - t2;
+        samples.push(p1 * h00 + m1 * h10 + p2 * h01 + m2 * h11);
+      }
+    }
+    samples
+  }
+
+  /// A straight piece of a sampled spline, with a rotation-minimizing frame.
+  struct Segment {
+    start:       glam::Vec3A,
+    length:      f32,
+    arc_start:   f32,
+    tangent:     glam::Vec3A,
+    normal:      glam::Vec3A,
+    binormal:    glam::Vec3A,
+    /// The miter normal at the start, or `None` if this is the spline's start.
+    start_miter: Option<glam::Vec3A>,
+    /// The miter normal at the end, or `None` if this is the spline's end.
+    end_miter:   Option<glam::Vec3A>,
+  }
+
+  fn segments(samples: &[glam::Vec3A]) -> Vec<Segment> {
+    let pairs = samples
+      .windows(2)
+      .map(|w| (w[0], w[1] - w[0]))
+      .filter(|(_, d)| d.length() > f32::EPSILON)
+      .collect::<Vec<_>>();
+
+    let mut segments: Vec<Segment> = Vec::with_capacity(pairs.len());
+    let mut arc_start = 0.0;
+    for (start, delta) in pairs {
+      let length = delta.length();
+      let tangent = delta / length;
+
+      // parallel-transport the previous frame so the profile doesn't twist
+      let normal = match segments.last() {
+        Some(prev) => {
+          let rotation =
+            glam::Quat::from_rotation_arc(prev.tangent.into(), tangent.into());
+          rotation * prev.normal
+        }
+        None => tangent.any_orthonormal_pair().0,
+      };
+      let normal = (normal - tangent * normal.dot(tangent)).normalize();
+      let binormal = tangent.cross(normal);
+
+      segments.push(Segment {
+        start,
+        length,
+        arc_start,
+        tangent,
+        normal,
+        binormal,
+        start_miter: None,
+        end_miter: None,
+      });
+      arc_start += length;
+    }
+
+    for i in 1..segments.len() {
+      let miter = (segments[i - 1].tangent + segments[i].tangent)
+        .try_normalize()
+        .unwrap_or(segments[i].tangent);
+      segments[i - 1].end_miter = Some(miter);
+      segments[i].start_miter = Some(miter);
+    }
+
+    segments
+  }
+
+  fn nso_dot_const(
+    v: [Node; 3],
+    c: glam::Vec3A,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let a = ctx.mul(v[0], c.x)?;
+    let b = ctx.mul(v[1], c.y)?;
+    let c = ctx.mul(v[2], c.z)?;
+    let sum = ctx.add(a, b)?;
+    ctx.add(sum, c)
+  }
+
+  /// Sweeps the 2d profile `root` along a Catmull-Rom spline built from
+  /// `points`.
+  ///
+  /// The profile is evaluated with X and Y in the plane perpendicular to the
+  /// curve, and Z set to the arc length along the curve, so profiles can vary
+  /// along the sweep. The ends of the sweep are capped flat. Splines with
+  /// fewer than two distinct points are empty.
+  ///
+  /// The curve is swept as a chain of straight segments joined at miter
+  /// planes. Near the curve the result is as exact as the profile is, but
+  /// far away on the outside of tight bends (further than the radius of
+  /// curvature) the values overestimate the distance.
+  pub fn nso_catmull_rom_spline(
+    root: Node,
+    points: &[[f32; 3]],
+    tension: f32,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let samples = catmull_rom_samples(points, tension);
+    let segments = segments(&samples);
+
+    let x = ctx.x();
+    let y = ctx.y();
+    let z = ctx.z();
+
+    let mut result: Option<Node> = None;
+    for segment in segments {
+      let p_x = ctx.sub(x, segment.start.x)?;
+      let p_y = ctx.sub(y, segment.start.y)?;
+      let p_z = ctx.sub(z, segment.start.z)?;
+      let p = [p_x, p_y, p_z];
+
+      // local coordinates of the profile and along the curve
+      let u = nso_dot_const(p, segment.normal, ctx)?;
+      let v = nso_dot_const(p, segment.binormal, ctx)?;
+      let along = nso_dot_const(p, segment.tangent, ctx)?;
+      let t = ctx.max(along, 0.0)?;
+      let t = ctx.min(t, segment.length)?;
+      let s = ctx.add(t, segment.arc_start)?;
+      let profile = ctx.remap_xyz(root, [u, v, s])?;
+
+      // how far past each end we are; positive outside the segment
+      let end_offset = segment.tangent * segment.length;
+      let start_miter = segment.start_miter.unwrap_or(segment.tangent);
+      let end_miter = segment.end_miter.unwrap_or(segment.tangent);
+      let e_start = nso_dot_const(p, -start_miter, ctx)?;
+      let e_end = nso_dot_const(p, end_miter, ctx)?;
+      let e_end = ctx.sub(e_end, end_offset.dot(end_miter))?;
+
+      // the spline's own ends are capped exactly, like an extrusion
+      let cap = match (segment.start_miter, segment.end_miter) {
+        (None, None) => Some(ctx.max(e_start, e_end)?),
+        (None, Some(_)) => Some(e_start),
+        (Some(_), None) => Some(e_end),
+        (Some(_), Some(_)) => None,
+      };
+      let value = match cap {
+        Some(cap) => {
+          let a = ctx.max(profile, 0.0)?;
+          let b = ctx.max(cap, 0.0)?;
+          let outside = super::vectors::nso_magnitude_2d([a, b], ctx)?;
+          let inside = ctx.max(profile, cap)?;
+          let inside = ctx.min(inside, 0.0)?;
+          ctx.add(outside, inside)?
+        }
+        None => profile,
+      };
+
+      // interior ends are cut steeply just past the miter planes, so that
+      // neighbouring segments overlap slightly and no surface appears
+      // between them
+      let miter = match (segment.start_miter, segment.end_miter) {
+        (Some(_), Some(_)) => Some(ctx.max(e_start, e_end)?),
+        (Some(_), None) => Some(e_start),
+        (None, Some(_)) => Some(e_end),
+        (None, None) => None,
+      };
+      let value = match miter {
+        Some(miter) => {
+          let miter = ctx.sub(miter, segment.length * MITER_OVERLAP)?;
+          let miter = ctx.mul(miter, MITER_STEEPNESS)?;
+          ctx.max(value, miter)?
+        }
+        None => value,
+      };
+
+      result = Some(match result {
+        Some(result) => ctx.min(result, value)?,
+        None => value,
+      });
+    }
+
+    match result {
+      Some(result) => Ok(result),
+      None => Ok(ctx.constant(f64::INFINITY)),
+    }
+  }
+
+  #[cfg(test)]
+  mod test {
+    use float_cmp::approx_eq;
+
+    use super::*;
+
+    fn tube(
+      points: &[[f32; 3]],
+      tension: f32,
+      radius: f64,
+      ctx: &mut Context,
+    ) -> Node {
+      let x = ctx.x();
+      let y = ctx.y();
+      let dist = crate::nso::vectors::nso_magnitude_2d([x, y], ctx).unwrap();
+      let profile = ctx.sub(dist, radius).unwrap();
+      nso_catmull_rom_spline(profile, points, tension, ctx).unwrap()
+    }
+
+    #[test]
+    fn samples_pass_through_points() {
+      let points = [[0.0, 0.0, 0.0], [1.0, 2.0, 0.0], [3.0, 1.0, -1.0]];
+      let samples = catmull_rom_samples(&points, 0.5);
+      assert_eq!(samples.len(), 2 * SPAN_SUBDIVISIONS + 1);
+      for (i, point) in points.iter().enumerate() {
+        let sample = samples[i * SPAN_SUBDIVISIONS];
+        assert!(sample.distance(glam::Vec3A::from_array(*point)) < 1e-5);
+      }
+    }
+
+    #[test]
+    fn straight_tube_has_exact_distance() {
+      let mut ctx = Context::new();
+      let points = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [2.0, 0.0, 0.0]];
+      let node = tube(&points, 0.5, 0.25, &mut ctx);
+
+      for (point, expected) in [
+        ([0.5, 0.0, 0.0], -0.25),
+        ([1.0, 0.0, 0.0], -0.25),
+        ([1.5, 0.0, 0.25], 0.0),
+        ([1.0, 1.0, 0.0], 0.75),
+        ([3.0, 0.0, 0.0], 1.0),
+        ([-1.0, 0.0, 0.0], 1.0),
+        ([2.5, 0.0, 0.5], 0.3125_f64.sqrt()),
+      ] {
+        let value = ctx.eval_xyz(node, point[0], point[1], point[2]).unwrap();
+        assert!(
+          approx_eq!(f64, value, expected, epsilon = 1e-5),
+          "expected {expected} at {point:?}, got {value}"
+        );
+      }
+    }
+
+    #[test]
+    fn curved_tube_contains_its_samples() {
+      let mut ctx = Context::new();
+      let points = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [-1.0, 0.0, 0.0], [
+        0.0, -1.0, 0.5,
+      ]];
+      let node = tube(&points, 0.5, 0.1, &mut ctx);
+
+      // the end points sit on the caps, the rest are on the center line
+      let samples = catmull_rom_samples(&points, 0.5);
+      for sample in &samples[1..samples.len() - 1] {
+        let value = ctx
+          .eval_xyz(node, sample.x.into(), sample.y.into(), sample.z.into())
+          .unwrap();
+        assert!(
+          approx_eq!(f64, value, -0.1, epsilon = 1e-4),
+          "expected -0.1 at {sample:?}, got {value}"
+        );
+      }
+
+      // the center of the loop is well outside the tube
+      assert!(ctx.eval_xyz(node, 0.0, 0.0, 0.0).unwrap() > 0.5);
+    }
+
+    #[test]
+    fn degenerate_spline_is_empty() {
+      let mut ctx = Context::new();
+      let node = tube(&[[1.0, 1.0, 1.0]], 0.5, 1.0, &mut ctx);
+      assert!(ctx.eval_xyz(node, 1.0, 1.0, 1.0).unwrap() > 0.0);
+    }
+  }
+}
+
+pub mod profile {
+  //! 2d profiles, and the operators that lift them into 3d.
+  //!
+  //! Profiles lie in the XY plane and ignore Z, so on their own they're
+  //! infinite prisms along Z. Their values are exact 2d distances.
+
+  use fidget::{context::Node, Context};
+
+  /// A circle of `radius`, centered on the origin.
+  pub fn nso_circle(
+    radius: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let x = ctx.x();
+    let y = ctx.y();
+    let dist = super::vectors::nso_magnitude_2d([x, y], ctx)?;
+    ctx.sub(dist, radius)
+  }
+
+  /// A rectangle with half-extents `width` and `height`, centered on the
+  /// origin.
+  pub fn nso_rectangle(
+    width: Node,
+    height: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let x = ctx.x();
+    let y = ctx.y();
+
+    let abs_x = ctx.abs(x)?;
+    let abs_y = ctx.abs(y)?;
+    let q_x = ctx.sub(abs_x, width)?;
+    let q_y = ctx.sub(abs_y, height)?;
+
+    let max_q_x_0 = ctx.max(q_x, 0.0)?;
+    let max_q_y_0 = ctx.max(q_y, 0.0)?;
+    let outside =
+      super::vectors::nso_magnitude_2d([max_q_x_0, max_q_y_0], ctx)?;
+
+    let a = ctx.max(q_x, q_y)?;
+    let inside = ctx.min(a, 0.0)?;
+    ctx.add(outside, inside)
+  }
+
+  /// A rectangle with half-extents `width` and `height` whose corners are
+  /// rounded by `radius`. Like `nso_rounded_box`, the rounding is taken from
+  /// inside the half-extents.
+  pub fn nso_rounded_rectangle(
+    width: Node,
+    height: Node,
+    radius: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let width = ctx.sub(width, radius)?;
+    let height = ctx.sub(height, radius)?;
+    let rectangle = nso_rectangle(width, height, ctx)?;
+    ctx.sub(rectangle, radius)
+  }
+
+  /// A closed polygon through `points`, in either winding order. Polygons
+  /// with fewer than three points are empty.
+  ///
+  /// The distance is the distance to the nearest edge. Its sign comes from
+  /// counting the edges crossed by a ray along +X, where each crossing is
+  /// found with steep steps instead of comparisons, so that the whole thing
+  /// stays a product of nodes.
+  pub fn nso_polygon(
+    points: &[[f32; 2]],
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    use super::other::nso_steep_step;
+
+    if points.len() < 3 {
+      return Ok(ctx.constant(f64::INFINITY));
+    }
+    let points = points
+      .iter()
+      .map(|p| glam::Vec2::from_array(*p))
+      .collect::<Vec<_>>();
+
+    let x = ctx.x();
+    let y = ctx.y();
+
+    let mut distance: Option<Node> = None;
+    let mut sign = ctx.constant(1.0);
+    for (i, &a) in points.iter().enumerate() {
+      let b = points[(i + 1) % points.len()];
+      let e = b - a;
+      let w_x = ctx.sub(x, a.x)?;
+      let w_y = ctx.sub(y, a.y)?;
+
+      // the squared distance to the closest point on the edge
+      let along = if e.length_squared() > 0.0 {
+        let e_scaled = e / e.length_squared();
+        let u = ctx.mul(w_x, e_scaled.x)?;
+        let v = ctx.mul(w_y, e_scaled.y)?;
+        let t = ctx.add(u, v)?;
+        let t = ctx.max(t, 0.0)?;
+        Some(ctx.min(t, 1.0)?)
+      } else {
+        None
+      };
+      let (d_x, d_y) = match along {
+        Some(t) => {
+          let t_x = ctx.mul(t, e.x)?;
+          let t_y = ctx.mul(t, e.y)?;
+          (ctx.sub(w_x, t_x)?, ctx.sub(w_y, t_y)?)
+        }
+        None => (w_x, w_y),
+      };
+      let d_x = ctx.square(d_x)?;
+      let d_y = ctx.square(d_y)?;
+      let edge_distance = ctx.add(d_x, d_y)?;
+      distance = Some(match distance {
+        Some(distance) => ctx.min(distance, edge_distance)?,
+        None => edge_distance,
+      });
+
+      // the ray crosses the edge if y is within the edge's span (including
+      // its start, excluding its end) and the point is on the edge's left,
+      // or if neither holds for an edge running the other way
+      let below_a = ctx.sub(a.y, y)?;
+      let below_a = nso_steep_step(below_a, ctx)?;
+      let above_a = ctx.sub(1.0, below_a)?;
+      let below_b = ctx.sub(b.y, y)?;
+      let below_b = nso_steep_step(below_b, ctx)?;
+      let u = ctx.mul(w_y, e.x)?;
+      let v = ctx.mul(w_x, e.y)?;
+      let cross = ctx.sub(u, v)?;
+      let left = nso_steep_step(cross, ctx)?;
+
+      let all = ctx.mul(above_a, below_b)?;
+      let all = ctx.mul(all, left)?;
+      let above_b = ctx.sub(1.0, below_b)?;
+      let right = ctx.sub(1.0, left)?;
+      let none = ctx.mul(below_a, above_b)?;
+      let none = ctx.mul(none, right)?;
+      let crossed = ctx.add(all, none)?;
+      let crossed = ctx.mul(crossed, 2.0)?;
+      let flip = ctx.sub(1.0, crossed)?;
+      sign = ctx.mul(sign, flip)?;
+    }
+
+    let distance = ctx.sqrt(distance.unwrap())?;
+    ctx.mul(distance, sign)
+  }
+
+  /// Extrudes the 2d `profile` along Z, `depth` deep in total and centered
+  /// on the XY plane, with flat caps. Exact if `profile` is.
+  pub fn nso_extrude(
+    profile: Node,
+    depth: Node,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let z = ctx.z();
+    let half = ctx.div(depth, 2.0)?;
+    let abs_z = ctx.abs(z)?;
+    let cap = ctx.sub(abs_z, half)?;
+
+    let a = ctx.max(profile, 0.0)?;
+    let b = ctx.max(cap, 0.0)?;
+    let outside = super::vectors::nso_magnitude_2d([a, b], ctx)?;
+    let inside = ctx.max(profile, cap)?;
+    let inside = ctx.min(inside, 0.0)?;
+    ctx.add(outside, inside)
+  }
+
+  /// Revolves the 2d `profile` around `axis`, which passes through the
+  /// origin. The profile's X is the distance from the axis and its Y is the
+  /// distance along it, so only the half of the profile with positive X is
+  /// swept. Exact if `profile` is.
+  pub fn nso_revolve(
+    profile: Node,
+    axis: [f32; 3],
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let axis = glam::Vec3A::from_array(axis)
+      .try_normalize()
+      .unwrap_or(glam::Vec3A::Y);
+    let x = ctx.x();
+    let y = ctx.y();
+    let z = ctx.z();
+
+    let a = ctx.mul(x, axis.x)?;
+    let b = ctx.mul(y, axis.y)?;
+    let c = ctx.mul(z, axis.z)?;
+    let along = ctx.add(a, b)?;
+    let along = ctx.add(along, c)?;
+
+    let a = ctx.mul(along, axis.x)?;
+    let a = ctx.sub(x, a)?;
+    let b = ctx.mul(along, axis.y)?;
+    let b = ctx.sub(y, b)?;
+    let c = ctx.mul(along, axis.z)?;
+    let c = ctx.sub(z, c)?;
+    let radial = super::vectors::nso_magnitude_3d([a, b, c], ctx)?;
+
+    let zero = ctx.constant(0.0);
+    ctx.remap_xyz(profile, [radial, along, zero])
+  }
 }
 
-// pub mod spline {
-//   use fidget::{context::Node, Context};
-
-//   use crate::nso::other::nso_hardstep_region;
-
-//   fn to_t(
-//     root: Node,
-//     n_segments: usize,
-//     ctx: &mut Context,
-//   ) -> Result<Node, fidget::Error> { let zero = ctx.constant(0.0); let one =
-//     ctx.constant(1.0); let n_segments_node = ctx.constant(n_segments as f64);
-//     let root = super::other::nso_clamp(root, zero, one, ctx)?; ctx.mul(root,
-//     n_segments_node)
-//   }
-
-//   /// Remaps the path between [0.0, 0.0, 0.0] and [0.0, 0.0, 1.0] to the path
-// of   /// a Catmull-Rom spline built from the points specified.
-//   pub fn nso_catmull_rom_spline(
-//     root: Node,
-//     points: &Vec<[f32; 3]>,
-//     tension: f32,
-//     ctx: &mut Context,
-//   ) -> Result<Node, fidget::Error> { // set out some constants let n_segments
-//     = points.len() - 1;
-
-//     let zero = ctx.constant(0.0);
-//     let one = ctx.constant(1.0);
-//     let x = ctx.x();
-//     let y = ctx.y();
-//     let z = ctx.z();
-//     let t_x = to_t(x, n_segments, ctx)?;
-//     let t_y = to_t(y, n_segments, ctx)?;
-//     let t_z = to_t(z, n_segments, ctx)?;
-
-//     // get original point set as vectors
-//     let mut points = points
-//       .iter()
-//       .map(|p| glam::Vec3A::from_array(*p))
-//       .collect::<Vec<_>>();
-
-//     // add first and last ghost points
-//     let first_point = points[0] - (points[1] - points[0]);
-//     let last_point = points[points.len() - 1]
-//       + (points[points.len() - 1] - points[points.len() - 2]);
-//     points.insert(0, first_point);
-//     points.push(last_point);
-//     // reassign to remove mutability
-//     let points = points;
-
-//     let mut running_x_axis = ctx.constant(0.0);
-//     let mut running_y_axis = ctx.constant(0.0);
-//     let mut running_z_axis = ctx.constant(0.0);
-
-//     for i in 0..n_segments {
-//       let p0 = points[i];
-//       let p1 = points[i + 1];
-//       let p2 = points[i + 2];
-//       let p3 = points[i + 3];
-//       // println!(
-//       //   "for round {i}:\n\tgot p0: {p0:?}\n\tgot p1: {p1:?}\n\tgot p2: \
-//       //    {p2:?}\n\tgot p3: {p3:?}"
-//       // );
-
-//       let matrix = glam::Mat4::from_cols(
-//         glam::Vec4::new(0.0, -1.0, 2.0, -1.0),
-//         glam::Vec4::new(2.0, 0.0, -5.0, 3.0),
-//         glam::Vec4::new(0.0, 1.0, 4.0, -3.0),
-//         glam::Vec4::new(0.0, 0.0, -1.0, 1.0),
-//       )
-//       // .inverse()
-//         * 0.5;
-
-//       let l0 = p0 * matrix.x_axis.x
-//         + p1 * matrix.y_axis.x
-//         + p2 * matrix.z_axis.x
-//         + p3 * matrix.w_axis.x;
-//       let l1 = p0 * matrix.x_axis.y
-//         + p1 * matrix.y_axis.y
-//         + p2 * matrix.z_axis.y
-//         + p3 * matrix.w_axis.y;
-//       let l2 = p0 * matrix.x_axis.z
-//         + p1 * matrix.y_axis.z
-//         + p2 * matrix.z_axis.z
-//         + p3 * matrix.w_axis.z;
-//       let l3 = p0 * matrix.x_axis.w
-//         + p1 * matrix.y_axis.w
-//         + p2 * matrix.z_axis.w
-//         + p3 * matrix.w_axis.w;
-
-//       // move t back to [0, 1]
-//       let i_node = ctx.constant(i as f64);
-//       let t_x = ctx.sub(t_x, i_node)?;
-//       let t_y = ctx.sub(t_y, i_node)?;
-//       let t_z = ctx.sub(t_z, i_node)?;
-
-//       // let t0 = ctx.constant(1.0);
-//       let t1_x = t_x;
-//       let t2_x = ctx.mul(t1_x, t_x)?;
-//       let t3_x = ctx.mul(t2_x, t_x)?;
-//       let t1_y = t_y;
-//       let t2_y = ctx.mul(t1_y, t_y)?;
-//       let t3_y = ctx.mul(t2_y, t_y)?;
-//       let t1_z = t_z;
-//       let t2_z = ctx.mul(t1_z, t_z)?;
-//       let t3_z = ctx.mul(t2_z, t_z)?;
-
-//       let x_axis_l0 = ctx.constant(l0.x.into());
-//       let x_axis_l1 = ctx.constant(l1.x.into());
-//       let x_axis_l2 = ctx.constant(l2.x.into());
-//       let x_axis_l3 = ctx.constant(l3.x.into());
-
-//       let x_axis_a = ctx.mul(x_axis_l1, t1_x)?;
-//       let x_axis_b = ctx.mul(x_axis_l2, t2_x)?;
-//       let x_axis_c = ctx.mul(x_axis_l3, t3_x)?;
-//       let x_axis_sum = ctx.add(x_axis_l0, x_axis_a)?;
-//       let x_axis_sum = ctx.add(x_axis_sum, x_axis_b)?;
-//       let x_axis_sum = ctx.add(x_axis_sum, x_axis_c)?;
-
-//       let y_axis_l0 = ctx.constant(l0.y.into());
-//       let y_axis_l1 = ctx.constant(l1.y.into());
-//       let y_axis_l2 = ctx.constant(l2.y.into());
-//       let y_axis_l3 = ctx.constant(l3.y.into());
-
-//       let y_axis_a = ctx.mul(y_axis_l1, t1_y)?;
-//       let y_axis_b = ctx.mul(y_axis_l2, t2_y)?;
-//       let y_axis_c = ctx.mul(y_axis_l3, t3_y)?;
-//       let y_axis_sum = ctx.add(y_axis_l0, y_axis_a)?;
-//       let y_axis_sum = ctx.add(y_axis_sum, y_axis_b)?;
-//       let y_axis_sum = ctx.add(y_axis_sum, y_axis_c)?;
-
-//       let z_axis_l0 = ctx.constant(l0.z.into());
-//       let z_axis_l1 = ctx.constant(l1.z.into());
-//       let z_axis_l2 = ctx.constant(l2.z.into());
-//       let z_axis_l3 = ctx.constant(l3.z.into());
-
-//       let z_axis_a = ctx.mul(z_axis_l1, t1_z)?;
-//       let z_axis_b = ctx.mul(z_axis_l2, t2_z)?;
-//       let z_axis_c = ctx.mul(z_axis_l3, t3_z)?;
-//       let z_axis_sum = ctx.add(z_axis_l0, z_axis_a)?;
-//       let z_axis_sum = ctx.add(z_axis_sum, z_axis_b)?;
-//       let z_axis_sum = ctx.add(z_axis_sum, z_axis_c)?;
-
-//       let x_axis = nso_hardstep_region(t_x, zero, one, x_axis_sum, ctx)?;
-//       let y_axis = nso_hardstep_region(t_y, zero, one, y_axis_sum, ctx)?;
-//       let z_axis = nso_hardstep_region(t_z, zero, one, z_axis_sum, ctx)?;
-
-//       *(&mut running_x_axis) = ctx.add(running_x_axis, x_axis)?;
-//       *(&mut running_y_axis) = ctx.add(running_y_axis, y_axis)?;
-//       *(&mut running_z_axis) = ctx.add(running_z_axis, z_axis)?;
-//     }
-
-//     let x = ctx.x();
-//     let y = ctx.y();
-//     let z = ctx.z();
-//     let new_x = ctx.sub(x, running_x_axis)?;
-//     // let y = ctx.div(y, 2.0)?;
-//     let new_y = ctx.sub(y, running_y_axis)?;
-//     // let new_y = running_y_axis;
-//     let new_z = ctx.sub(z, running_z_axis)?;
-
-//     // for t in 0..21 {
-//     //   let t = t as f64 / 20.0;
-//     //   println!(
-//     //     "x: {:?}, y: {:?}, z: {:?}",
-//     //     ctx.eval_xyz(new_x, 0.0, t, 0.0)?,
-//     //     ctx.eval_xyz(new_y, 0.0, t, 0.0)?,
-//     //     ctx.eval_xyz(new_z, 0.0, t, 0.0)?
-//     //   );
-//     // }
-
-//     // ctx.remap_xyz(root, [new_x, new_y, new_z])
-//     ctx.remap_xyz(root, [running_x_axis, running_y_axis, running_z_axis])
-//   }
-// }
+pub mod grid {
+  //! Shapes sampled on regular grids, interpolated between the samples.
+  //!
+  //! There's no way to index into a table from a node, so the interpolation
+  //! is written out in full: each sample is weighted by a "hat" along each
+  //! axis, `max(0, 1 - |u - i|)`, which is one at the sample and falls to zero
+  //! at its neighbours. Summing the weighted samples gives exactly linear
+  //! interpolation.
+  //!
+  //! To keep that from costing every sample at every point, the grid is split
+  //! into bricks of [`BRICK_CELLS`] cells along each axis. Each brick
+  //! interpolates only its own samples, at the point clamped into the brick,
+  //! minus a penalty that grows with how far the point was moved. The penalty
+  //! is steeper than the samples can change, so the largest brick value is
+  //! always the one from the brick holding the point, and taking the `max`
+  //! over bricks gives exactly the interpolated value. Interval evaluation
+  //! over a small region then finds the far bricks always lose, so
+  //! simplifying the tape there drops them. An unsimplified tape still visits
+  //! every sample, which is why grids are capped at [`MAX_GRID_SAMPLES`].
+
+  use fidget::{context::Node, Context};
+
+  /// The most samples a grid may have. Each sample adds a couple of
+  /// operations to the tape, so this bounds evaluation of a grid over a
+  /// region too large for its bricks to be pruned to a few hundred thousand
+  /// operations per point.
+  pub const MAX_GRID_SAMPLES: usize = 1 << 16;
+
+  /// The number of cells along each axis of a brick.
+  const BRICK_CELLS: usize = 8;
+
+  /// The number of samples in a grid of `size`, or `None` if that doesn't fit
+  /// in a `usize`.
+  pub fn sample_count(size: &[u32]) -> Option<usize> {
+    size
+      .iter()
+      .try_fold(1_usize, |count, n| count.checked_mul(*n as usize))
+  }
+
+  /// Whether a grid of `size` can be built from `count` samples: it has
+  /// samples along every axis, no more than [`MAX_GRID_SAMPLES`] of them, and
+  /// `count` is how many.
+  fn is_buildable(size: &[u32], count: usize) -> bool {
+    matches!(
+      sample_count(size),
+      Some(n) if n != 0 && n <= MAX_GRID_SAMPLES && n == count
+    )
+  }
+
+  /// The position of `coord` in units of samples, for `count` samples spread
+  /// evenly over `[-extent, extent]`. It's clamped to that range first.
+  fn sample_coord(
+    coord: Node,
+    count: u32,
+    extent: f32,
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let coord = ctx.max(coord, -extent)?;
+    let coord = ctx.min(coord, extent)?;
+    let scale = if count > 1 && extent > 0.0 {
+      (count - 1) as f32 / (2.0 * extent)
+    } else {
+      0.0
+    };
+    let u = ctx.add(coord, extent)?;
+    ctx.mul(u, scale)
+  }
+
+  /// The samples of a brick along one axis.
+  struct Span {
+    /// The index of the first sample.
+    first:    usize,
+    /// The hat weights of the samples at the sample coordinate clamped into
+    /// the span.
+    hats:     Vec<Node>,
+    /// How far the sample coordinate was moved by the clamping.
+    distance: Node,
+  }
+
+  /// Splits `count` samples along the sample coordinate `u` into spans of
+  /// [`BRICK_CELLS`] cells, neighbouring spans sharing their end samples.
+  fn spans(
+    u: Node,
+    count: usize,
+    ctx: &mut Context,
+  ) -> Result<Vec<Span>, fidget::Error> {
+    (0..count.max(2) - 1)
+      .step_by(BRICK_CELLS)
+      .map(|first| {
+        let last = (first + BRICK_CELLS).min(count - 1);
+        let clamped = ctx.max(u, first as f32)?;
+        let clamped = ctx.min(clamped, last as f32)?;
+        let moved = ctx.sub(u, clamped)?;
+        let distance = ctx.abs(moved)?;
+        let hats = (first..=last)
+          .map(|i| {
+            let offset = ctx.sub(clamped, i as f32)?;
+            let offset = ctx.abs(offset)?;
+            let hat = ctx.sub(1.0, offset)?;
+            ctx.max(hat, 0.0)
+          })
+          .collect::<Result<Vec<_>, _>>()?;
+        Ok(Span {
+          first,
+          hats,
+          distance,
+        })
+      })
+      .collect()
+  }
+
+  /// Returns `sum(weights[i] * samples[i])`, skipping zero samples.
+  fn weighted_samples(
+    weights: &[Node],
+    samples: &[f32],
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let mut sum = ctx.constant(0.0);
+    for (weight, sample) in weights.iter().zip(samples) {
+      if *sample == 0.0 {
+        continue;
+      }
+      let term = ctx.mul(*weight, *sample)?;
+      sum = ctx.add(sum, term)?;
+    }
+    Ok(sum)
+  }
+
+  /// Returns `sum(weights[i] * values[i])`.
+  fn weighted_sum(
+    weights: &[Node],
+    values: &[Node],
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let mut sum = ctx.constant(0.0);
+    for (weight, value) in weights.iter().zip(values) {
+      let term = ctx.mul(*weight, *value)?;
+      sum = ctx.add(sum, term)?;
+    }
+    Ok(sum)
+  }
+
+  /// Interpolates the samples of one brick, given its span along each axis.
+  /// `strides` are the distances in `samples` between neighbours along each
+  /// axis, and `offset` is where the brick's first sample along the axes not
+  /// in `spans` is.
+  fn interpolate(
+    spans: &[&Span],
+    strides: &[usize],
+    offset: usize,
+    samples: &[f32],
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    let ((span, spans), (stride, strides)) =
+      match (spans.split_last(), strides.split_last()) {
+        (Some(span), Some(stride)) => (span, stride),
+        _ => unreachable!("grids have at least one axis"),
+      };
+    if spans.is_empty() {
+      let row = &samples[offset + span.first * stride..][..span.hats.len()];
+      return weighted_samples(&span.hats, row, ctx);
+    }
+    let values = (span.first..span.first + span.hats.len())
+      .map(|i| interpolate(spans, strides, offset + i * stride, samples, ctx))
+      .collect::<Result<Vec<_>, _>>()?;
+    weighted_sum(&span.hats, &values, ctx)
+  }
+
+  /// Interpolates a grid of `samples` of `size`, with X varying fastest, at
+  /// the sample coordinates `coords`, brick by brick.
+  fn nso_bricks(
+    coords: &[Node],
+    size: &[u32],
+    samples: &[f32],
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    // interpolation changes by at most the range of the samples per unit of
+    // sample coordinate, so a steeper penalty always loses to the right brick
+    let lowest = samples.iter().copied().fold(f32::INFINITY, f32::min);
+    let highest = samples.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let slope = highest - lowest + 1.0;
+
+    let spans = coords
+      .iter()
+      .zip(size)
+      .map(|(u, n)| spans(*u, *n as usize, ctx))
+      .collect::<Result<Vec<_>, _>>()?;
+    let strides = size
+      .iter()
+      .scan(1, |stride, n| {
+        let this = *stride;
+        *stride *= *n as usize;
+        Some(this)
+      })
+      .collect::<Vec<_>>();
+    let bricks = spans.iter().fold(vec![Vec::new()], |bricks, axis| {
+      bricks
+        .into_iter()
+        .flat_map(|brick| {
+          axis.iter().map(move |span| {
+            let mut brick = brick.clone();
+            brick.push(span);
+            brick
+          })
+        })
+        .collect()
+    });
+
+    let mut value = None;
+    for brick in bricks {
+      let inside = interpolate(&brick, &strides, 0, samples, ctx)?;
+      let mut distance = ctx.constant(0.0);
+      for span in &brick {
+        distance = ctx.add(distance, span.distance)?;
+      }
+      let penalty = ctx.mul(distance, slope)?;
+      let candidate = ctx.sub(inside, penalty)?;
+      value = Some(match value {
+        Some(value) => ctx.max(value, candidate)?,
+        None => candidate,
+      });
+    }
+    Ok(value.expect("grids have at least one brick"))
+  }
+
+  /// Trilinearly interpolates a grid of distances, centered on the origin
+  /// with half-extents `extent`. `values` holds `size[0] * size[1] *
+  /// size[2]` samples with X varying fastest, then Y, then Z; grids with the
+  /// wrong number of samples, or more than [`MAX_GRID_SAMPLES`], are empty.
+  ///
+  /// Outside the grid, the value at the nearest point on its boundary is
+  /// extended by the distance to that point, which keeps the field a
+  /// distance bound if the samples are.
+  pub fn nso_distance_grid(
+    size: [u32; 3],
+    extent: [f32; 3],
+    values: &[f32],
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    if !is_buildable(&size, values.len()) {
+      return Ok(ctx.constant(f64::INFINITY));
+    }
+
+    let x = ctx.x();
+    let y = ctx.y();
+    let z = ctx.z();
+    let ux = sample_coord(x, size[0], extent[0], ctx)?;
+    let uy = sample_coord(y, size[1], extent[1], ctx)?;
+    let uz = sample_coord(z, size[2], extent[2], ctx)?;
+    let inside = nso_bricks(&[ux, uy, uz], &size, values, ctx)?;
+
+    // the distance from the grid's box, zero within it
+    let outside = [(x, extent[0]), (y, extent[1]), (z, extent[2])]
+      .into_iter()
+      .map(|(coord, extent)| {
+        let abs = ctx.abs(coord)?;
+        let beyond = ctx.sub(abs, extent)?;
+        ctx.max(beyond, 0.0)
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+    let outside = super::vectors::nso_magnitude_3d(
+      [outside[0], outside[1], outside[2]],
+      ctx,
+    )?;
+    ctx.add(inside, outside)
+  }
+
+  /// A terrain surface at the bilinearly interpolated height of a grid of
+  /// `heights` over the XZ plane, centered on the origin with half-extents
+  /// `extent`. `heights` holds `size[0] * size[1]` samples with X varying
+  /// fastest; grids with the wrong number of samples, or more than
+  /// [`MAX_GRID_SAMPLES`], are empty. Beyond the grid the edge heights carry
+  /// on outwards.
+  ///
+  /// The value is the height above the surface, so like any heightfield it
+  /// overestimates the distance on slopes.
+  pub fn nso_heightmap(
+    size: [u32; 2],
+    extent: [f32; 2],
+    heights: &[f32],
+    ctx: &mut Context,
+  ) -> Result<Node, fidget::Error> {
+    if !is_buildable(&size, heights.len()) {
+      return Ok(ctx.constant(f64::INFINITY));
+    }
+
+    let x = ctx.x();
+    let y = ctx.y();
+    let z = ctx.z();
+    let ux = sample_coord(x, size[0], extent[0], ctx)?;
+    let uz = sample_coord(z, size[1], extent[1], ctx)?;
+    let height = nso_bricks(&[ux, uz], &size, heights, ctx)?;
+    ctx.sub(y, height)
+  }
+}
@@ -0,0 +1,192 @@
+//! Ray queries against [`Shape`]s, without meshing them.
+//!
+//! Rays are sphere traced: each step advances by the field's value, which is a
+//! safe distance to the surface when the field is an exact distance field.
+//! Many shapes aren't (smooth unions, deformations, noise and scaled fields can
+//! all overestimate), so every step is also checked with interval arithmetic
+//! over the segment it covers. If the segment can't be shown to be empty, the
+//! step is halved until it can, down to [`HIT_TOLERANCE`], and the surface is
+//! found by bisection when a short step crosses it.
+
+use fidget::eval::Family;
+use glam::Vec3A;
+use tracing::info_span;
+
+use crate::{eval::ShapeEvaluator, shape::Shape};
+
+/// Points where the field is below this are treated as being on the surface.
+/// It's also the shortest step a ray takes.
+pub const HIT_TOLERANCE: f32 = 1e-4;
+/// The most steps a ray takes before giving up, in case the field
+/// underestimates the distance badly enough that the ray barely advances.
+const MAX_STEPS: u32 = 1024;
+/// How many times the crossing is bisected once it's been bracketed.
+const BISECTION_STEPS: u32 = 24;
+
+/// Where a ray hit a shape.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+  pub point:    Vec3A,
+  /// The unit normal of the surface at `point`, from the field's gradient.
+  pub normal:   Vec3A,
+  /// The distance from the ray's origin to `point`.
+  pub distance: f32,
+}
+
+/// Casts a ray into `shape`. See [`ShapeEvaluator::raycast`].
+pub fn raycast(
+  shape: &Shape,
+  origin: Vec3A,
+  dir: Vec3A,
+  max_dist: f32,
+) -> Result<Option<RayHit>, fidget::Error> {
+  shape.evaluator()?.raycast(origin, dir, max_dist)
+}
+
+impl<F: Family> ShapeEvaluator<F> {
+  /// Casts a ray from `origin` along `dir`, returning the first point within
+  /// `max_dist` where it enters the shape.
+  ///
+  /// `dir` doesn't need to be normalized; a zero direction never hits. Rays
+  /// that start inside the shape hit at distance zero, and rays through
+  /// fields that evaluate to NaN (like ones with unbound variables) miss.
+  pub fn raycast(
+    &self,
+    origin: Vec3A,
+    dir: Vec3A,
+    max_dist: f32,
+  ) -> Result<Option<RayHit>, fidget::Error> {
+    let _span = info_span!("planiscope::ShapeEvaluator::raycast").entered();
+
+    let dir = dir.normalize_or_zero();
+    if dir == Vec3A::ZERO {
+      return Ok(None);
+    }
+    let at = |t: f32| origin + dir * t;
+
+    let float_eval = fidget::eval::FloatSliceEval::new(&self.tape);
+    let eval = |t: f32| -> Result<f32, fidget::Error> {
+      let p = at(t);
+      Ok(float_eval.eval(&[p.x], &[p.y], &[p.z], &self.vars)?[0])
+    };
+    let interval_eval = self.tape.new_interval_evaluator();
+    let segment_is_empty = |t0: f32, t1: f32| -> Result<bool, fidget::Error> {
+      let (a, b) = (at(t0), at(t1));
+      let (min, max) = (a.min(b), a.max(b));
+      let (interval, _) = interval_eval.eval(
+        [min.x, max.x],
+        [min.y, max.y],
+        [min.z, max.z],
+        &self.vars,
+      )?;
+      Ok(interval.lower() > 0.0)
+    };
+
+    let mut t = 0.0;
+    for _ in 0..MAX_STEPS {
+      let distance = eval(t)?;
+      if distance.is_nan() {
+        return Ok(None);
+      }
+      if distance < HIT_TOLERANCE {
+        return self.hit(at(t), t).map(Some);
+      }
+      if t >= max_dist {
+        return Ok(None);
+      }
+
+      let mut step = distance.max(HIT_TOLERANCE);
+      loop {
+        let end = (t + step).min(max_dist);
+        if segment_is_empty(t, end)? {
+          t = end;
+          break;
+        }
+        if step > HIT_TOLERANCE {
+          step = (step / 2.0).max(HIT_TOLERANCE);
+          continue;
+        }
+
+        // the segment is too short to split further, so see if it crosses
+        if eval(end)? < HIT_TOLERANCE {
+          let (mut outside, mut inside) = (t, end);
+          for _ in 0..BISECTION_STEPS {
+            let mid = (outside + inside) / 2.0;
+            if eval(mid)? < HIT_TOLERANCE {
+              inside = mid;
+            } else {
+              outside = mid;
+            }
+          }
+          return self.hit(at(inside), inside).map(Some);
+        }
+        t = end;
+        break;
+      }
+    }
+    Ok(None)
+  }
+
+  fn hit(&self, point: Vec3A, distance: f32) -> Result<RayHit, fidget::Error> {
+    Ok(RayHit {
+      point,
+      normal: self.gradient(point)?.normalize_or_zero(),
+      distance,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use float_cmp::approx_eq;
+
+  use super::*;
+  use crate::shape::builder;
+
+  #[test]
+  fn hits_spheres() {
+    let shape = builder::translate(builder::sphere(1.0), 0.0, 0.0, 5.0);
+    let hit = raycast(&shape, Vec3A::ZERO, Vec3A::new(0.0, 0.0, 2.0), 10.0)
+      .unwrap()
+      .unwrap();
+    assert!(approx_eq!(f32, hit.distance, 4.0, epsilon = 1e-3));
+    assert!(approx_eq!(f32, hit.point.z, 4.0, epsilon = 1e-3));
+    assert!(approx_eq!(f32, hit.normal.z, -1.0, epsilon = 1e-3));
+  }
+
+  #[test]
+  fn misses_past_max_dist_and_beside_the_shape() {
+    let shape = builder::translate(builder::sphere(1.0), 0.0, 0.0, 5.0);
+    let evaluator = shape.evaluator().unwrap();
+    assert_eq!(evaluator.raycast(Vec3A::ZERO, Vec3A::Z, 3.0).unwrap(), None);
+    assert_eq!(
+      evaluator.raycast(Vec3A::ZERO, Vec3A::X, 10.0).unwrap(),
+      None
+    );
+    assert_eq!(
+      evaluator.raycast(Vec3A::ZERO, Vec3A::ZERO, 10.0).unwrap(),
+      None
+    );
+  }
+
+  #[test]
+  fn rays_starting_inside_hit_immediately() {
+    let hit = raycast(&builder::sphere(1.0), Vec3A::ZERO, Vec3A::X, 10.0)
+      .unwrap()
+      .unwrap();
+    assert_eq!(hit.distance, 0.0);
+  }
+
+  #[test]
+  fn overestimating_fields_do_not_tunnel() {
+    // scaling the field up makes sphere tracing overshoot by 10x, which
+    // would step straight through the thin slab
+    let slab = builder::cuboid(10.0, 10.0, 0.05);
+    let shape = builder::mul(builder::translate(slab, 0.0, 0.0, 5.0), 10.0);
+    let hit = raycast(&shape, Vec3A::ZERO, Vec3A::Z, 10.0)
+      .unwrap()
+      .unwrap();
+    assert!(approx_eq!(f32, hit.distance, 4.95, epsilon = 1e-3));
+    assert!(approx_eq!(f32, hit.normal.z, -1.0, epsilon = 1e-3));
+  }
+}
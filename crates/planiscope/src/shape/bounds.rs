@@ -0,0 +1,211 @@
+//! Bounding-box inference for [`Shape`]s.
+
+use fidget::eval::Tape;
+use glam::Vec3A;
+use tracing::info_span;
+
+use super::{CachedIntoNode, Shape};
+use crate::mesher::{bind_vars, Bindings};
+
+/// The half-extent of the volume, centered on the origin, that
+/// [`Shape::bounds`] searches for the surface in.
+pub const SEARCH_HALF_EXTENT: f32 = 1024.0;
+/// How many times each refinement pass subdivides its search volume.
+const PASS_DEPTH: u32 = 6;
+/// The maximum number of refinement passes.
+const MAX_PASSES: u32 = 8;
+/// Refinement stops once a pass shrinks the bounds by less than this fraction.
+const CONVERGENCE: f32 = 0.01;
+
+/// An axis-aligned bounding box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+  pub min: Vec3A,
+  pub max: Vec3A,
+}
+
+impl Bounds {
+  pub fn center(&self) -> Vec3A { (self.min + self.max) / 2.0 }
+  pub fn half_extents(&self) -> Vec3A { (self.max - self.min) / 2.0 }
+
+  fn union(self, other: Self) -> Self {
+    Self {
+      min: self.min.min(other.min),
+      max: self.max.max(other.max),
+    }
+  }
+}
+
+impl Shape {
+  /// Finds an axis-aligned box enclosing the solid part of the shape, using
+  /// interval evaluation. Returns `None` if the shape is empty.
+  ///
+  /// The search is limited to [`SEARCH_HALF_EXTENT`] around the origin, so
+  /// unbounded shapes (like planes) produce bounds clipped to that volume. The
+  /// result is conservative: interval arithmetic can overestimate, so the box
+  /// may be somewhat larger than the true extent, but never smaller.
+  ///
+  /// The shape's [`Shape::Var`]s take their values from `bindings`.
+  pub fn bounds(
+    &self,
+    bindings: &Bindings,
+  ) -> Result<Option<Bounds>, fidget::Error> {
+    self.bounds_within(
+      Bounds {
+        min: Vec3A::splat(-SEARCH_HALF_EXTENT),
+        max: Vec3A::splat(SEARCH_HALF_EXTENT),
+      },
+      bindings,
+    )
+  }
+
+  /// Like [`Shape::bounds`], but only searches within `search`.
+  pub fn bounds_within(
+    &self,
+    search: Bounds,
+    bindings: &Bindings,
+  ) -> Result<Option<Bounds>, fidget::Error> {
+    let _span = info_span!("planiscope::Shape::bounds").entered();
+
+    let mut ctx = fidget::Context::new();
+    let node = self.simplify().eval_root_cached(&mut ctx)?;
+    let tape: Tape<fidget::vm::Eval> = ctx.get_tape(node)?;
+    let vars = bind_vars(&tape, bindings);
+    let evaluator = tape.new_interval_evaluator();
+    let interval = |cell: Bounds| {
+      let (interval, _) = evaluator.eval(
+        [cell.min.x, cell.max.x],
+        [cell.min.y, cell.max.y],
+        [cell.min.z, cell.max.z],
+        &vars,
+      )?;
+      Ok((interval.lower(), interval.upper()))
+    };
+
+    let mut bounds = search;
+    for _ in 0..MAX_PASSES {
+      let Some(refined) = refine(&interval, bounds, PASS_DEPTH)? else {
+        return Ok(None);
+      };
+      let old_size = (bounds.max - bounds.min).max_element();
+      let new_size = (refined.max - refined.min).max_element();
+      bounds = refined;
+      if new_size > old_size * (1.0 - CONVERGENCE) {
+        break;
+      }
+    }
+    Ok(Some(bounds))
+  }
+}
+
+/// Returns the union of all the cells within `cell` that might be inside the
+/// shape, subdividing `depth` times.
+fn refine(
+  interval: &impl Fn(Bounds) -> Result<(f32, f32), fidget::Error>,
+  cell: Bounds,
+  depth: u32,
+) -> Result<Option<Bounds>, fidget::Error> {
+  let (lower, upper) = interval(cell)?;
+  // the whole cell is outside the shape
+  if lower > 0.0 {
+    return Ok(None);
+  }
+  // the cell is either entirely inside the shape or we can't subdivide further
+  if upper < 0.0 || depth == 0 {
+    return Ok(Some(cell));
+  }
+
+  let center = cell.center();
+  let mut result: Option<Bounds> = None;
+  for octant in 0..8 {
+    let pick = |bit: u32, min: f32, mid: f32, max: f32| {
+      if octant & bit == 0 {
+        (min, mid)
+      } else {
+        (mid, max)
+      }
+    };
+    let (x0, x1) = pick(1, cell.min.x, center.x, cell.max.x);
+    let (y0, y1) = pick(2, cell.min.y, center.y, cell.max.y);
+    let (z0, z1) = pick(4, cell.min.z, center.z, cell.max.z);
+    let child = Bounds {
+      min: Vec3A::new(x0, y0, z0),
+      max: Vec3A::new(x1, y1, z1),
+    };
+    if let Some(found) = refine(interval, child, depth - 1)? {
+      result = Some(match result {
+        Some(result) => result.union(found),
+        None => found,
+      });
+    }
+  }
+  Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    mesher::{MesherDetail, MesherRegion},
+    shape::builder,
+  };
+
+  fn assert_bounds_near(bounds: Bounds, min: [f32; 3], max: [f32; 3]) {
+    // the result may only ever be larger than the true bounds
+    let tolerance = 0.05;
+    for i in 0..3 {
+      assert!(bounds.min[i] <= min[i] && bounds.min[i] > min[i] - tolerance);
+      assert!(bounds.max[i] >= max[i] && bounds.max[i] < max[i] + tolerance);
+    }
+  }
+
+  #[test]
+  fn sphere_bounds() {
+    let bounds = builder::sphere(1.0)
+      .bounds(&Bindings::new())
+      .unwrap()
+      .unwrap();
+    assert_bounds_near(bounds, [-1.0; 3], [1.0; 3]);
+  }
+
+  #[test]
+  fn translated_cuboid_bounds() {
+    let shape =
+      builder::translate(builder::cuboid(1.0, 0.5, 2.0), 3.0, -2.0, 10.0);
+    let bounds = shape.bounds(&Bindings::new()).unwrap().unwrap();
+    assert_bounds_near(bounds, [2.0, -2.5, 8.0], [4.0, -1.5, 12.0]);
+  }
+
+  #[test]
+  fn region_fits_shape() {
+    let shape = builder::translate(builder::sphere(2.0), 0.0, 5.0, 0.0);
+    let region =
+      MesherRegion::fit(&shape, MesherDetail::Subdivs(5), &Bindings::new())
+        .unwrap()
+        .unwrap();
+    assert!(region.position.distance(Vec3A::new(0.0, 5.0, 0.0)) < 0.05);
+    assert!(region.scale.cmpgt(Vec3A::splat(2.0)).all());
+    assert!(region.scale.cmplt(Vec3A::splat(2.5)).all());
+  }
+
+  #[test]
+  fn empty_shape_has_no_bounds() {
+    assert_eq!(
+      builder::constant(1.0).bounds(&Bindings::new()).unwrap(),
+      None
+    );
+  }
+
+  #[test]
+  fn bounds_use_bindings() {
+    let shape = builder::sphere(builder::var("radius"));
+    let bindings = Bindings::from([("radius".to_string(), 2.0)]);
+    let bounds = shape.bounds(&bindings).unwrap().unwrap();
+    assert_bounds_near(bounds, [-2.0; 3], [2.0; 3]);
+
+    let region = MesherRegion::fit(&shape, MesherDetail::Subdivs(5), &bindings)
+      .unwrap()
+      .unwrap();
+    assert!(region.scale.cmpgt(Vec3A::splat(2.0)).all());
+  }
+}
@@ -9,35 +9,60 @@ pub fn y() -> Shape { Shape::YNode }
 pub fn z() -> Shape { Shape::ZNode }
 
 pub fn constant(a: f64) -> Shape { Shape::Constant(a) }
+/// A named parameter. See [`Shape::Var`].
+///
+/// # Panics
+///
+/// Panics if `name` isn't a valid var name (see [`is_var_name`]), since it
+/// wouldn't print unambiguously.
+pub fn var(name: impl Into<String>) -> Shape {
+  let name = name.into();
+  assert!(is_var_name(&name), "invalid var name `{name}`");
+  Shape::Var(name)
+}
 
 pub fn add(lhs: impl Into<Shape>, rhs: impl Into<Shape>) -> Shape {
-  Shape::Add(Box::new(lhs.into()), Box::new(rhs.into()))
+  Shape::Add(SharedShape::new(lhs.into()), SharedShape::new(rhs.into()))
 }
 pub fn sub(lhs: impl Into<Shape>, rhs: impl Into<Shape>) -> Shape {
-  Shape::Sub(Box::new(lhs.into()), Box::new(rhs.into()))
+  Shape::Sub(SharedShape::new(lhs.into()), SharedShape::new(rhs.into()))
 }
 pub fn mul(lhs: impl Into<Shape>, rhs: impl Into<Shape>) -> Shape {
-  Shape::Mul(Box::new(lhs.into()), Box::new(rhs.into()))
+  Shape::Mul(SharedShape::new(lhs.into()), SharedShape::new(rhs.into()))
 }
 pub fn div(lhs: impl Into<Shape>, rhs: impl Into<Shape>) -> Shape {
-  Shape::Div(Box::new(lhs.into()), Box::new(rhs.into()))
+  Shape::Div(SharedShape::new(lhs.into()), SharedShape::new(rhs.into()))
 }
 pub fn min(lhs: impl Into<Shape>, rhs: impl Into<Shape>) -> Shape {
-  Shape::Min(Box::new(lhs.into()), Box::new(rhs.into()))
+  Shape::Min(SharedShape::new(lhs.into()), SharedShape::new(rhs.into()))
 }
 pub fn max(lhs: impl Into<Shape>, rhs: impl Into<Shape>) -> Shape {
-  Shape::Max(Box::new(lhs.into()), Box::new(rhs.into()))
+  Shape::Max(SharedShape::new(lhs.into()), SharedShape::new(rhs.into()))
 }
 
-pub fn neg(a: impl Into<Shape>) -> Shape { Shape::Neg(Box::new(a.into())) }
-pub fn exp(a: impl Into<Shape>) -> Shape { Shape::Exp(Box::new(a.into())) }
-pub fn sin(a: impl Into<Shape>) -> Shape { Shape::Sin(Box::new(a.into())) }
-pub fn cos(a: impl Into<Shape>) -> Shape { Shape::Cos(Box::new(a.into())) }
-pub fn recip(a: impl Into<Shape>) -> Shape { Shape::Recip(Box::new(a.into())) }
-pub fn abs(a: impl Into<Shape>) -> Shape { Shape::Abs(Box::new(a.into())) }
-pub fn sqrt(a: impl Into<Shape>) -> Shape { Shape::Sqrt(Box::new(a.into())) }
+pub fn neg(a: impl Into<Shape>) -> Shape {
+  Shape::Neg(SharedShape::new(a.into()))
+}
+pub fn exp(a: impl Into<Shape>) -> Shape {
+  Shape::Exp(SharedShape::new(a.into()))
+}
+pub fn sin(a: impl Into<Shape>) -> Shape {
+  Shape::Sin(SharedShape::new(a.into()))
+}
+pub fn cos(a: impl Into<Shape>) -> Shape {
+  Shape::Cos(SharedShape::new(a.into()))
+}
+pub fn recip(a: impl Into<Shape>) -> Shape {
+  Shape::Recip(SharedShape::new(a.into()))
+}
+pub fn abs(a: impl Into<Shape>) -> Shape {
+  Shape::Abs(SharedShape::new(a.into()))
+}
+pub fn sqrt(a: impl Into<Shape>) -> Shape {
+  Shape::Sqrt(SharedShape::new(a.into()))
+}
 pub fn square(a: impl Into<Shape>) -> Shape {
-  Shape::Square(Box::new(a.into()))
+  Shape::Square(SharedShape::new(a.into()))
 }
 
 pub fn remap(
@@ -47,26 +72,26 @@ pub fn remap(
   z: impl Into<Shape>,
 ) -> Shape {
   Shape::Remap {
-    root:  Box::new(root.into()),
-    new_x: Box::new(x.into()),
-    new_y: Box::new(y.into()),
-    new_z: Box::new(z.into()),
+    root:  SharedShape::new(root.into()),
+    new_x: SharedShape::new(x.into()),
+    new_y: SharedShape::new(y.into()),
+    new_z: SharedShape::new(z.into()),
   }
 }
 pub fn translate(root: impl Into<Shape>, x: f64, y: f64, z: f64) -> Shape {
   Shape::Remap {
-    root:  Box::new(root.into()),
-    new_x: Box::new(if x == 0.0 {
+    root:  SharedShape::new(root.into()),
+    new_x: SharedShape::new(if x == 0.0 {
       self::x()
     } else {
       sub(self::x(), x)
     }),
-    new_y: Box::new(if y == 0.0 {
+    new_y: SharedShape::new(if y == 0.0 {
       self::y()
     } else {
       sub(self::y(), y)
     }),
-    new_z: Box::new(if z == 0.0 {
+    new_z: SharedShape::new(if z == 0.0 {
       self::z()
     } else {
       sub(self::z(), z)
@@ -75,18 +100,18 @@ pub fn translate(root: impl Into<Shape>, x: f64, y: f64, z: f64) -> Shape {
 }
 pub fn scale(root: impl Into<Shape>, x: f64, y: f64, z: f64) -> Shape {
   Shape::Remap {
-    root:  Box::new(root.into()),
-    new_x: Box::new(if x == 1.0 {
+    root:  SharedShape::new(root.into()),
+    new_x: SharedShape::new(if x == 1.0 {
       self::x()
     } else {
       div(self::x(), x)
     }),
-    new_y: Box::new(if y == 1.0 {
+    new_y: SharedShape::new(if y == 1.0 {
       self::y()
     } else {
       div(self::y(), y)
     }),
-    new_z: Box::new(if z == 1.0 {
+    new_z: SharedShape::new(if z == 1.0 {
       self::z()
     } else {
       div(self::z(), z)
@@ -97,13 +122,13 @@ pub fn scale(root: impl Into<Shape>, x: f64, y: f64, z: f64) -> Shape {
 // extra
 pub fn sphere(r: impl Into<Shape>) -> Shape {
   Shape::Extra(compound::Compound::Sphere {
-    radius: Box::new(r.into()),
+    radius: SharedShape::new(r.into()),
   })
 }
 pub fn cylinder(r: impl Into<Shape>, h: impl Into<Shape>) -> Shape {
   Shape::Extra(compound::Compound::Cylinder {
-    height: Box::new(h.into()),
-    radius: Box::new(r.into()),
+    height: SharedShape::new(h.into()),
+    radius: SharedShape::new(r.into()),
   })
 }
 pub fn cuboid(
@@ -112,9 +137,66 @@ pub fn cuboid(
   z: impl Into<Shape>,
 ) -> Shape {
   Shape::Extra(compound::Compound::Cuboid {
-    x: Box::new(x.into()),
-    y: Box::new(y.into()),
-    z: Box::new(z.into()),
+    x: SharedShape::new(x.into()),
+    y: SharedShape::new(y.into()),
+    z: SharedShape::new(z.into()),
+  })
+}
+pub fn rounded_box(
+  x: impl Into<Shape>,
+  y: impl Into<Shape>,
+  z: impl Into<Shape>,
+  radius: impl Into<Shape>,
+) -> Shape {
+  Shape::Extra(compound::Compound::RoundedBox {
+    x:      SharedShape::new(x.into()),
+    y:      SharedShape::new(y.into()),
+    z:      SharedShape::new(z.into()),
+    radius: SharedShape::new(radius.into()),
+  })
+}
+pub fn exact_cuboid(
+  x: impl Into<Shape>,
+  y: impl Into<Shape>,
+  z: impl Into<Shape>,
+) -> Shape {
+  rounded_box(x, y, z, 0.0)
+}
+pub fn torus(major: impl Into<Shape>, minor: impl Into<Shape>) -> Shape {
+  Shape::Extra(compound::Compound::Torus {
+    major: SharedShape::new(major.into()),
+    minor: SharedShape::new(minor.into()),
+  })
+}
+pub fn capsule(h: impl Into<Shape>, r: impl Into<Shape>) -> Shape {
+  Shape::Extra(compound::Compound::Capsule {
+    height: SharedShape::new(h.into()),
+    radius: SharedShape::new(r.into()),
+  })
+}
+pub fn cone(h: impl Into<Shape>, r: impl Into<Shape>) -> Shape {
+  Shape::Extra(compound::Compound::Cone {
+    height: SharedShape::new(h.into()),
+    radius: SharedShape::new(r.into()),
+  })
+}
+pub fn plane(normal: [f64; 3], offset: impl Into<Shape>) -> Shape {
+  Shape::Extra(compound::Compound::Plane {
+    normal_x: SharedShape::new(normal[0].into()),
+    normal_y: SharedShape::new(normal[1].into()),
+    normal_z: SharedShape::new(normal[2].into()),
+    offset:   SharedShape::new(offset.into()),
+  })
+}
+pub fn ellipsoid(
+  x: impl Into<Shape>,
+  y: impl Into<Shape>,
+  z: impl Into<Shape>,
+) -> Shape {
+  Shape::Extra(compound::Compound::Ellipsoid {
+    x: SharedShape::new(x.into()),
+    y: SharedShape::new(y.into()),
+    z: SharedShape::new(z.into()),
   })
 }
 pub fn smooth_min_cubic(
@@ -123,14 +205,104 @@ pub fn smooth_min_cubic(
   k: impl Into<Shape>,
 ) -> Shape {
   Shape::Extra(compound::Compound::SmoothMinCubic {
-    lhs: Box::new(lhs.into()),
-    rhs: Box::new(rhs.into()),
-    k:   Box::new(k.into()),
+    lhs: SharedShape::new(lhs.into()),
+    rhs: SharedShape::new(rhs.into()),
+    k:   SharedShape::new(k.into()),
+  })
+}
+pub fn smooth_max_cubic(
+  lhs: impl Into<Shape>,
+  rhs: impl Into<Shape>,
+  k: impl Into<Shape>,
+) -> Shape {
+  Shape::Extra(compound::Compound::SmoothMaxCubic {
+    lhs: SharedShape::new(lhs.into()),
+    rhs: SharedShape::new(rhs.into()),
+    k:   SharedShape::new(k.into()),
+  })
+}
+pub fn smooth_difference_cubic(
+  lhs: impl Into<Shape>,
+  rhs: impl Into<Shape>,
+  k: impl Into<Shape>,
+) -> Shape {
+  smooth_max_cubic(lhs, neg(rhs), k)
+}
+pub fn smooth_min_quadratic(
+  lhs: impl Into<Shape>,
+  rhs: impl Into<Shape>,
+  k: impl Into<Shape>,
+) -> Shape {
+  Shape::Extra(compound::Compound::SmoothMinQuadratic {
+    lhs: SharedShape::new(lhs.into()),
+    rhs: SharedShape::new(rhs.into()),
+    k:   SharedShape::new(k.into()),
+  })
+}
+pub fn smooth_min_exponential(
+  lhs: impl Into<Shape>,
+  rhs: impl Into<Shape>,
+  k: impl Into<Shape>,
+) -> Shape {
+  Shape::Extra(compound::Compound::SmoothMinExponential {
+    lhs: SharedShape::new(lhs.into()),
+    rhs: SharedShape::new(rhs.into()),
+    k:   SharedShape::new(k.into()),
+  })
+}
+pub fn chamfer_min(
+  lhs: impl Into<Shape>,
+  rhs: impl Into<Shape>,
+  r: impl Into<Shape>,
+) -> Shape {
+  Shape::Extra(compound::Compound::ChamferMin {
+    lhs:    SharedShape::new(lhs.into()),
+    rhs:    SharedShape::new(rhs.into()),
+    radius: SharedShape::new(r.into()),
   })
 }
+pub fn chamfer_max(
+  lhs: impl Into<Shape>,
+  rhs: impl Into<Shape>,
+  r: impl Into<Shape>,
+) -> Shape {
+  neg(chamfer_min(neg(lhs), neg(rhs), r))
+}
+pub fn chamfer_difference(
+  lhs: impl Into<Shape>,
+  rhs: impl Into<Shape>,
+  r: impl Into<Shape>,
+) -> Shape {
+  chamfer_max(lhs, neg(rhs), r)
+}
+pub fn round_min(
+  lhs: impl Into<Shape>,
+  rhs: impl Into<Shape>,
+  r: impl Into<Shape>,
+) -> Shape {
+  Shape::Extra(compound::Compound::RoundMin {
+    lhs:    SharedShape::new(lhs.into()),
+    rhs:    SharedShape::new(rhs.into()),
+    radius: SharedShape::new(r.into()),
+  })
+}
+pub fn round_max(
+  lhs: impl Into<Shape>,
+  rhs: impl Into<Shape>,
+  r: impl Into<Shape>,
+) -> Shape {
+  neg(round_min(neg(lhs), neg(rhs), r))
+}
+pub fn round_difference(
+  lhs: impl Into<Shape>,
+  rhs: impl Into<Shape>,
+  r: impl Into<Shape>,
+) -> Shape {
+  round_max(lhs, neg(rhs), r)
+}
 pub fn transform(root: impl Into<Shape>, mat: impl Into<glam::Mat4>) -> Shape {
   Shape::Extra(compound::Compound::MatTransform {
-    root: Box::new(root.into()),
+    root: SharedShape::new(root.into()),
     mat:  mat.into(),
   })
 }
@@ -140,9 +312,9 @@ pub fn clamp(
   max: impl Into<Shape>,
 ) -> Shape {
   Shape::Extra(compound::Compound::Clamp {
-    root: Box::new(root.into()),
-    min:  Box::new(min.into()),
-    max:  Box::new(max.into()),
+    root: SharedShape::new(root.into()),
+    min:  SharedShape::new(min.into()),
+    max:  SharedShape::new(max.into()),
   })
 }
 pub fn map(
@@ -153,11 +325,68 @@ pub fn map(
   out_max: impl Into<Shape>,
 ) -> Shape {
   Shape::Extra(compound::Compound::Map {
-    root:    Box::new(root.into()),
-    in_min:  Box::new(in_min.into()),
-    in_max:  Box::new(in_max.into()),
-    out_min: Box::new(out_min.into()),
-    out_max: Box::new(out_max.into()),
+    root:    SharedShape::new(root.into()),
+    in_min:  SharedShape::new(in_min.into()),
+    in_max:  SharedShape::new(in_max.into()),
+    out_min: SharedShape::new(out_min.into()),
+    out_max: SharedShape::new(out_max.into()),
+  })
+}
+pub fn repeat(
+  root: impl Into<Shape>,
+  spacing: [f32; 3],
+  count: [u32; 3],
+) -> Shape {
+  Shape::Extra(compound::Compound::Repeat {
+    root: SharedShape::new(root.into()),
+    spacing,
+    count,
+  })
+}
+pub fn repeat_x(root: impl Into<Shape>, spacing: f32, count: u32) -> Shape {
+  repeat(root, [spacing, 0.0, 0.0], [count, 1, 1])
+}
+pub fn repeat_y(root: impl Into<Shape>, spacing: f32, count: u32) -> Shape {
+  repeat(root, [0.0, spacing, 0.0], [1, count, 1])
+}
+pub fn repeat_z(root: impl Into<Shape>, spacing: f32, count: u32) -> Shape {
+  repeat(root, [0.0, 0.0, spacing], [1, 1, count])
+}
+pub fn repeat_infinite(root: impl Into<Shape>, spacing: [f32; 3]) -> Shape {
+  Shape::Extra(compound::Compound::RepeatInfinite {
+    root: SharedShape::new(root.into()),
+    spacing,
+  })
+}
+pub fn repeat_infinite_x(root: impl Into<Shape>, spacing: f32) -> Shape {
+  repeat_infinite(root, [spacing, 0.0, 0.0])
+}
+pub fn repeat_infinite_y(root: impl Into<Shape>, spacing: f32) -> Shape {
+  repeat_infinite(root, [0.0, spacing, 0.0])
+}
+pub fn repeat_infinite_z(root: impl Into<Shape>, spacing: f32) -> Shape {
+  repeat_infinite(root, [0.0, 0.0, spacing])
+}
+pub fn mirror(root: impl Into<Shape>, normal: [f32; 3], offset: f32) -> Shape {
+  Shape::Extra(compound::Compound::Mirror {
+    root: SharedShape::new(root.into()),
+    normal,
+    offset,
+  })
+}
+pub fn mirror_x(root: impl Into<Shape>) -> Shape {
+  mirror(root, [1.0, 0.0, 0.0], 0.0)
+}
+pub fn mirror_y(root: impl Into<Shape>) -> Shape {
+  mirror(root, [0.0, 1.0, 0.0], 0.0)
+}
+pub fn mirror_z(root: impl Into<Shape>) -> Shape {
+  mirror(root, [0.0, 0.0, 1.0], 0.0)
+}
+pub fn polar_repeat(root: impl Into<Shape>, count: u32) -> Shape {
+  Shape::Extra(compound::Compound::PolarRepeat {
+    root: SharedShape::new(root.into()),
+    count,
   })
 }
 pub fn catmull_rom_spline(
@@ -166,8 +395,232 @@ pub fn catmull_rom_spline(
   tension: f32,
 ) -> Shape {
   Shape::Extra(compound::Compound::CatmullRomSpline {
-    root: Box::new(root.into()),
+    root: SharedShape::new(root.into()),
     points,
     tension,
   })
 }
+pub fn catmull_rom_tube(
+  points: Vec<[f32; 3]>,
+  tension: f32,
+  r: impl Into<Shape>,
+) -> Shape {
+  let profile = sub(sqrt(add(square(x()), square(y()))), r);
+  catmull_rom_spline(profile, points, tension)
+}
+pub fn rotate(root: impl Into<Shape>, rotation: glam::Quat) -> Shape {
+  // sample the original shape at the point rotated back the other way
+  let inverse = glam::Mat3::from_quat(rotation.inverse());
+  let row = |i: usize| {
+    let row = inverse.row(i);
+    super::simplify::affine_to_shape([
+      row.x.into(),
+      row.y.into(),
+      row.z.into(),
+      0.0,
+    ])
+  };
+  remap(root, row(0), row(1), row(2))
+}
+pub fn rotate_x(root: impl Into<Shape>, angle: f32) -> Shape {
+  rotate(root, glam::Quat::from_rotation_x(angle))
+}
+pub fn rotate_y(root: impl Into<Shape>, angle: f32) -> Shape {
+  rotate(root, glam::Quat::from_rotation_y(angle))
+}
+pub fn rotate_z(root: impl Into<Shape>, angle: f32) -> Shape {
+  rotate(root, glam::Quat::from_rotation_z(angle))
+}
+pub fn rotate_euler(
+  root: impl Into<Shape>,
+  order: glam::EulerRot,
+  a: f32,
+  b: f32,
+  c: f32,
+) -> Shape {
+  rotate(root, glam::Quat::from_euler(order, a, b, c))
+}
+pub fn twist(root: impl Into<Shape>, rate: impl Into<Shape>) -> Shape {
+  Shape::Extra(compound::Compound::Twist {
+    root: SharedShape::new(root.into()),
+    rate: SharedShape::new(rate.into()),
+  })
+}
+pub fn bend(root: impl Into<Shape>, rate: impl Into<Shape>) -> Shape {
+  Shape::Extra(compound::Compound::Bend {
+    root: SharedShape::new(root.into()),
+    rate: SharedShape::new(rate.into()),
+  })
+}
+pub fn taper(root: impl Into<Shape>, rate: impl Into<Shape>) -> Shape {
+  Shape::Extra(compound::Compound::Taper {
+    root: SharedShape::new(root.into()),
+    rate: SharedShape::new(rate.into()),
+  })
+}
+pub fn shear(
+  root: impl Into<Shape>,
+  x_rate: impl Into<Shape>,
+  z_rate: impl Into<Shape>,
+) -> Shape {
+  Shape::Extra(compound::Compound::Shear {
+    root:   SharedShape::new(root.into()),
+    x_rate: SharedShape::new(x_rate.into()),
+    z_rate: SharedShape::new(z_rate.into()),
+  })
+}
+/// Seeded gradient noise in `-1..1` on a lattice of cells `1 / frequency`
+/// wide. See [`nso_gradient_noise`](crate::nso::noise::nso_gradient_noise).
+pub fn noise(seed: u32, frequency: impl Into<Shape>) -> Shape {
+  Shape::Extra(compound::Compound::Noise {
+    seed,
+    frequency: SharedShape::new(frequency.into()),
+  })
+}
+/// Fractal Brownian motion over layers of [`noise`]'s gradient noise. See
+/// [`nso_fbm`](crate::nso::noise::nso_fbm).
+pub fn fbm(
+  seed: u32,
+  octaves: u32,
+  frequency: impl Into<Shape>,
+  lacunarity: impl Into<Shape>,
+  gain: impl Into<Shape>,
+) -> Shape {
+  Shape::Extra(compound::Compound::Fbm {
+    seed,
+    octaves,
+    frequency: SharedShape::new(frequency.into()),
+    lacunarity: SharedShape::new(lacunarity.into()),
+    gain: SharedShape::new(gain.into()),
+  })
+}
+pub fn material(root: impl Into<Shape>, id: u32) -> Shape {
+  Shape::Extra(compound::Compound::Material {
+    root: SharedShape::new(root.into()),
+    id,
+  })
+}
+pub fn offset(root: impl Into<Shape>, distance: impl Into<Shape>) -> Shape {
+  Shape::Extra(compound::Compound::Offset {
+    root:     SharedShape::new(root.into()),
+    distance: SharedShape::new(distance.into()),
+  })
+}
+/// Grows `root` by `radius`, rounding its convex edges and corners. To round
+/// a shape without growing it, build it `radius` smaller first.
+pub fn round(root: impl Into<Shape>, radius: impl Into<Shape>) -> Shape {
+  offset(root, radius)
+}
+pub fn shell(root: impl Into<Shape>, thickness: impl Into<Shape>) -> Shape {
+  Shape::Extra(compound::Compound::Shell {
+    root:      SharedShape::new(root.into()),
+    thickness: SharedShape::new(thickness.into()),
+  })
+}
+pub fn onion(
+  root: impl Into<Shape>,
+  thickness: impl Into<Shape>,
+  count: u32,
+) -> Shape {
+  Shape::Extra(compound::Compound::Onion {
+    root: SharedShape::new(root.into()),
+    thickness: SharedShape::new(thickness.into()),
+    count,
+  })
+}
+/// Extrudes a 2d shape from [`profile`] along Z, `depth` deep and centered on
+/// the XY plane.
+pub fn extrude(profile: impl Into<Shape>, depth: impl Into<Shape>) -> Shape {
+  Shape::Extra(compound::Compound::Extrude {
+    profile: SharedShape::new(profile.into()),
+    depth:   SharedShape::new(depth.into()),
+  })
+}
+/// Revolves a 2d shape from [`profile`] around `axis`. The profile's X is the
+/// distance from the axis and its Y is the distance along it.
+pub fn revolve(profile: impl Into<Shape>, axis: [f32; 3]) -> Shape {
+  Shape::Extra(compound::Compound::Revolve {
+    profile: SharedShape::new(profile.into()),
+    axis,
+  })
+}
+/// A grid of distance samples, trilinearly interpolated, centered on the
+/// origin with half-extents `extent`. `values` holds `size[0] * size[1] *
+/// size[2]` samples with X varying fastest, then Y, then Z. Grids of more than
+/// [`MAX_GRID_SAMPLES`](super::MAX_GRID_SAMPLES) samples are rejected, since
+/// evaluating them can cost every sample at every point.
+pub fn distance_grid(
+  size: [u32; 3],
+  extent: [f32; 3],
+  values: Vec<f32>,
+) -> Result<Shape, GridError> {
+  super::grid::check_samples(&size, values.len())?;
+  Ok(Shape::Extra(compound::Compound::DistanceGrid {
+    size,
+    extent,
+    values,
+  }))
+}
+/// A terrain at the bilinearly interpolated height of a grid of `heights`
+/// over the XZ plane, centered on the origin with half-extents `extent`.
+/// `heights` holds `size[0] * size[1]` samples with X varying fastest, and no
+/// more than [`MAX_GRID_SAMPLES`](super::MAX_GRID_SAMPLES) of them.
+pub fn heightmap(
+  size: [u32; 2],
+  extent: [f32; 2],
+  heights: Vec<f32>,
+) -> Result<Shape, GridError> {
+  super::grid::check_samples(&size, heights.len())?;
+  Ok(Shape::Extra(compound::Compound::Heightmap {
+    size,
+    extent,
+    heights,
+  }))
+}
+/// A [`heightmap`] read from a grayscale image, with image columns along X
+/// and rows along Z. Black is at height zero and white is at `height`. Images
+/// with more pixels than a grid may have samples are rejected.
+pub fn heightmap_from_image(
+  path: impl AsRef<std::path::Path>,
+  extent: [f32; 2],
+  height: f32,
+) -> Result<Shape, GridError> {
+  let (size, pixels) = super::grid::read_grayscale(path.as_ref())?;
+  heightmap(
+    size,
+    extent,
+    pixels.into_iter().map(|p| p * height).collect(),
+  )
+}
+
+/// 2d shapes in the XY plane, for [`extrude`] and [`revolve`]. They ignore Z,
+/// so used directly they're infinite prisms along it.
+pub mod profile {
+  use super::*;
+
+  pub fn circle(r: impl Into<Shape>) -> Shape {
+    Shape::Extra(compound::Compound::Circle {
+      radius: SharedShape::new(r.into()),
+    })
+  }
+  pub fn rectangle(x: impl Into<Shape>, y: impl Into<Shape>) -> Shape {
+    Shape::Extra(compound::Compound::Rectangle {
+      x: SharedShape::new(x.into()),
+      y: SharedShape::new(y.into()),
+    })
+  }
+  pub fn rounded_rectangle(
+    x: impl Into<Shape>,
+    y: impl Into<Shape>,
+    radius: impl Into<Shape>,
+  ) -> Shape {
+    Shape::Extra(compound::Compound::RoundedRectangle {
+      x:      SharedShape::new(x.into()),
+      y:      SharedShape::new(y.into()),
+      radius: SharedShape::new(radius.into()),
+    })
+  }
+  pub fn polygon(points: Vec<[f32; 2]>) -> Shape {
+    Shape::Extra(compound::Compound::Polygon { points })
+  }
+}
@@ -1,91 +1,233 @@
-use std::hash::Hasher;
+use std::{collections::HashMap, hash::Hasher};
 
 use bevy_reflect::Reflect;
 use decorum::hash::FloatHash;
 use educe::Educe;
-use fidget::{
-  context::{IntoNode, Node},
-  Context,
-};
+use fidget::{context::Node, Context};
 use serde::{Deserialize, Serialize};
 
-use super::Shape;
+use super::{CachedIntoNode, Shape, SharedShape};
 
 #[derive(Educe, Clone, Debug, Serialize, Deserialize, Reflect)]
 #[educe(Hash)]
 pub enum Compound {
   Sphere {
-    #[reflect(ignore)]
-    radius: Box<Shape>,
+    radius: SharedShape,
   },
   Cylinder {
-    #[reflect(ignore)]
-    height: Box<Shape>,
-    #[reflect(ignore)]
-    radius: Box<Shape>,
+    height: SharedShape,
+    radius: SharedShape,
   },
   Cuboid {
-    #[reflect(ignore)]
-    x: Box<Shape>,
-    #[reflect(ignore)]
-    y: Box<Shape>,
-    #[reflect(ignore)]
-    z: Box<Shape>,
+    x: SharedShape,
+    y: SharedShape,
+    z: SharedShape,
+  },
+  RoundedBox {
+    x:      SharedShape,
+    y:      SharedShape,
+    z:      SharedShape,
+    radius: SharedShape,
+  },
+  Torus {
+    major: SharedShape,
+    minor: SharedShape,
+  },
+  Capsule {
+    height: SharedShape,
+    radius: SharedShape,
+  },
+  Cone {
+    height: SharedShape,
+    radius: SharedShape,
+  },
+  Plane {
+    normal_x: SharedShape,
+    normal_y: SharedShape,
+    normal_z: SharedShape,
+    offset:   SharedShape,
+  },
+  Ellipsoid {
+    x: SharedShape,
+    y: SharedShape,
+    z: SharedShape,
   },
   SmoothMinCubic {
-    #[reflect(ignore)]
-    lhs: Box<Shape>,
-    #[reflect(ignore)]
-    rhs: Box<Shape>,
-    #[reflect(ignore)]
-    k:   Box<Shape>,
+    lhs: SharedShape,
+    rhs: SharedShape,
+    k:   SharedShape,
+  },
+  SmoothMaxCubic {
+    lhs: SharedShape,
+    rhs: SharedShape,
+    k:   SharedShape,
+  },
+  SmoothMinQuadratic {
+    lhs: SharedShape,
+    rhs: SharedShape,
+    k:   SharedShape,
+  },
+  SmoothMinExponential {
+    lhs: SharedShape,
+    rhs: SharedShape,
+    k:   SharedShape,
+  },
+  ChamferMin {
+    lhs:    SharedShape,
+    rhs:    SharedShape,
+    radius: SharedShape,
+  },
+  RoundMin {
+    lhs:    SharedShape,
+    rhs:    SharedShape,
+    radius: SharedShape,
   },
   MatTransform {
-    #[reflect(ignore)]
-    root: Box<Shape>,
+    root: SharedShape,
     #[educe(Hash(method = "hash_mat4"))]
     mat:  glam::Mat4,
   },
   Clamp {
-    #[reflect(ignore)]
-    root: Box<Shape>,
-    #[reflect(ignore)]
-    min:  Box<Shape>,
-    #[reflect(ignore)]
-    max:  Box<Shape>,
+    root: SharedShape,
+    min:  SharedShape,
+    max:  SharedShape,
   },
   Map {
-    #[reflect(ignore)]
-    root:    Box<Shape>,
-    #[reflect(ignore)]
-    in_min:  Box<Shape>,
-    #[reflect(ignore)]
-    in_max:  Box<Shape>,
-    #[reflect(ignore)]
-    out_min: Box<Shape>,
-    #[reflect(ignore)]
-    out_max: Box<Shape>,
+    root:    SharedShape,
+    in_min:  SharedShape,
+    in_max:  SharedShape,
+    out_min: SharedShape,
+    out_max: SharedShape,
+  },
+  Repeat {
+    root:    SharedShape,
+    #[educe(Hash(method = "hash_triplet_f32"))]
+    spacing: [f32; 3],
+    count:   [u32; 3],
+  },
+  RepeatInfinite {
+    root:    SharedShape,
+    #[educe(Hash(method = "hash_triplet_f32"))]
+    spacing: [f32; 3],
+  },
+  Mirror {
+    root:   SharedShape,
+    #[educe(Hash(method = "hash_triplet_f32"))]
+    normal: [f32; 3],
+    #[educe(Hash(trait = "FloatHash"))]
+    offset: f32,
+  },
+  PolarRepeat {
+    root:  SharedShape,
+    count: u32,
   },
   CatmullRomSpline {
-    #[reflect(ignore)]
-    root:    Box<Shape>,
+    root:    SharedShape,
     #[educe(Hash(method = "hash_vec_triplet_f32"))]
     points:  Vec<[f32; 3]>,
     #[educe(Hash(trait = "FloatHash"))]
     tension: f32,
   },
+  Twist {
+    root: SharedShape,
+    rate: SharedShape,
+  },
+  Bend {
+    root: SharedShape,
+    rate: SharedShape,
+  },
+  Taper {
+    root: SharedShape,
+    rate: SharedShape,
+  },
+  Shear {
+    root:   SharedShape,
+    x_rate: SharedShape,
+    z_rate: SharedShape,
+  },
+  Noise {
+    seed:      u32,
+    frequency: SharedShape,
+  },
+  Fbm {
+    seed:       u32,
+    octaves:    u32,
+    frequency:  SharedShape,
+    lacunarity: SharedShape,
+    gain:       SharedShape,
+  },
+  Material {
+    root: SharedShape,
+    id:   u32,
+  },
+  Offset {
+    root:     SharedShape,
+    distance: SharedShape,
+  },
+  Shell {
+    root:      SharedShape,
+    thickness: SharedShape,
+  },
+  Onion {
+    root:      SharedShape,
+    thickness: SharedShape,
+    count:     u32,
+  },
+  Circle {
+    radius: SharedShape,
+  },
+  Rectangle {
+    x: SharedShape,
+    y: SharedShape,
+  },
+  RoundedRectangle {
+    x:      SharedShape,
+    y:      SharedShape,
+    radius: SharedShape,
+  },
+  Polygon {
+    #[educe(Hash(method = "hash_vec_pair_f32"))]
+    points: Vec<[f32; 2]>,
+  },
+  Extrude {
+    profile: SharedShape,
+    depth:   SharedShape,
+  },
+  Revolve {
+    profile: SharedShape,
+    #[educe(Hash(method = "hash_triplet_f32"))]
+    axis:    [f32; 3],
+  },
+  DistanceGrid {
+    size:   [u32; 3],
+    #[educe(Hash(method = "hash_triplet_f32"))]
+    extent: [f32; 3],
+    #[educe(Hash(method = "hash_vec_f32"))]
+    values: Vec<f32>,
+  },
+  Heightmap {
+    size:    [u32; 2],
+    #[educe(Hash(method = "hash_pair_f32"))]
+    extent:  [f32; 2],
+    #[educe(Hash(method = "hash_vec_f32"))]
+    heights: Vec<f32>,
+  },
 }
 
-impl IntoNode for &Compound {
-  fn into_node(self, ctx: &mut Context) -> Result<Node, fidget::Error> {
+impl CachedIntoNode for Compound {
+  fn cached_into_node(
+    &self,
+    ctx: &mut Context,
+    cache: &mut HashMap<u64, Node>,
+  ) -> Result<Node, fidget::Error> {
     match self {
       Compound::Sphere { radius } => {
-        let r = radius.into_node(ctx)?;
+        let r = radius.cached_into_node(ctx, cache)?;
         crate::nso::volumes::nso_sphere(r, ctx)
       }
       Compound::Cylinder { height, radius } => {
-        let height = height.into_node(ctx)?;
-        let radius = radius.into_node(ctx)?;
+        let height = height.cached_into_node(ctx, cache)?;
+        let radius = radius.cached_into_node(ctx, cache)?;
         crate::nso::volumes::nso_cylinder(height, radius, ctx)
       }
       Compound::Cuboid {
@@ -93,25 +235,97 @@ impl IntoNode for &Compound {
         y: width,
         z: height,
       } => {
-        let length = length.into_node(ctx)?;
-        let width = width.into_node(ctx)?;
-        let height = height.into_node(ctx)?;
+        let length = length.cached_into_node(ctx, cache)?;
+        let width = width.cached_into_node(ctx, cache)?;
+        let height = height.cached_into_node(ctx, cache)?;
         crate::nso::volumes::nso_inexact_cuboid(length, width, height, ctx)
       }
+      Compound::RoundedBox { x, y, z, radius } => {
+        let x = x.cached_into_node(ctx, cache)?;
+        let y = y.cached_into_node(ctx, cache)?;
+        let z = z.cached_into_node(ctx, cache)?;
+        let radius = radius.cached_into_node(ctx, cache)?;
+        crate::nso::volumes::nso_rounded_box(x, y, z, radius, ctx)
+      }
+      Compound::Torus { major, minor } => {
+        let major = major.cached_into_node(ctx, cache)?;
+        let minor = minor.cached_into_node(ctx, cache)?;
+        crate::nso::volumes::nso_torus(major, minor, ctx)
+      }
+      Compound::Capsule { height, radius } => {
+        let height = height.cached_into_node(ctx, cache)?;
+        let radius = radius.cached_into_node(ctx, cache)?;
+        crate::nso::volumes::nso_capsule(height, radius, ctx)
+      }
+      Compound::Cone { height, radius } => {
+        let height = height.cached_into_node(ctx, cache)?;
+        let radius = radius.cached_into_node(ctx, cache)?;
+        crate::nso::volumes::nso_cone(height, radius, ctx)
+      }
+      Compound::Plane {
+        normal_x,
+        normal_y,
+        normal_z,
+        offset,
+      } => {
+        let normal_x = normal_x.cached_into_node(ctx, cache)?;
+        let normal_y = normal_y.cached_into_node(ctx, cache)?;
+        let normal_z = normal_z.cached_into_node(ctx, cache)?;
+        let offset = offset.cached_into_node(ctx, cache)?;
+        crate::nso::volumes::nso_plane(
+          normal_x, normal_y, normal_z, offset, ctx,
+        )
+      }
+      Compound::Ellipsoid { x, y, z } => {
+        let x = x.cached_into_node(ctx, cache)?;
+        let y = y.cached_into_node(ctx, cache)?;
+        let z = z.cached_into_node(ctx, cache)?;
+        crate::nso::volumes::nso_ellipsoid(x, y, z, ctx)
+      }
       Compound::SmoothMinCubic { lhs, rhs, k } => {
-        let lhs = lhs.into_node(ctx)?;
-        let rhs = rhs.into_node(ctx)?;
-        let k = k.into_node(ctx)?;
+        let lhs = lhs.cached_into_node(ctx, cache)?;
+        let rhs = rhs.cached_into_node(ctx, cache)?;
+        let k = k.cached_into_node(ctx, cache)?;
         crate::nso::smooth::nso_smooth_min_cubic(lhs, rhs, k, ctx)
       }
+      Compound::SmoothMaxCubic { lhs, rhs, k } => {
+        let lhs = lhs.cached_into_node(ctx, cache)?;
+        let rhs = rhs.cached_into_node(ctx, cache)?;
+        let k = k.cached_into_node(ctx, cache)?;
+        crate::nso::smooth::nso_smooth_max_cubic(lhs, rhs, k, ctx)
+      }
+      Compound::SmoothMinQuadratic { lhs, rhs, k } => {
+        let lhs = lhs.cached_into_node(ctx, cache)?;
+        let rhs = rhs.cached_into_node(ctx, cache)?;
+        let k = k.cached_into_node(ctx, cache)?;
+        crate::nso::smooth::nso_smooth_min_quadratic(lhs, rhs, k, ctx)
+      }
+      Compound::SmoothMinExponential { lhs, rhs, k } => {
+        let lhs = lhs.cached_into_node(ctx, cache)?;
+        let rhs = rhs.cached_into_node(ctx, cache)?;
+        let k = k.cached_into_node(ctx, cache)?;
+        crate::nso::smooth::nso_smooth_min_exponential(lhs, rhs, k, ctx)
+      }
+      Compound::ChamferMin { lhs, rhs, radius } => {
+        let lhs = lhs.cached_into_node(ctx, cache)?;
+        let rhs = rhs.cached_into_node(ctx, cache)?;
+        let radius = radius.cached_into_node(ctx, cache)?;
+        crate::nso::smooth::nso_chamfer_min(lhs, rhs, radius, ctx)
+      }
+      Compound::RoundMin { lhs, rhs, radius } => {
+        let lhs = lhs.cached_into_node(ctx, cache)?;
+        let rhs = rhs.cached_into_node(ctx, cache)?;
+        let radius = radius.cached_into_node(ctx, cache)?;
+        crate::nso::smooth::nso_round_min(lhs, rhs, radius, ctx)
+      }
       Compound::MatTransform { root, mat } => {
-        let root = root.into_node(ctx)?;
+        let root = root.cached_into_node(ctx, cache)?;
         crate::nso::regions::nso_matrix_transform(root, mat, ctx)
       }
       Compound::Clamp { root, min, max } => {
-        let root = root.into_node(ctx)?;
-        let min = min.into_node(ctx)?;
-        let max = max.into_node(ctx)?;
+        let root = root.cached_into_node(ctx, cache)?;
+        let min = min.cached_into_node(ctx, cache)?;
+        let max = max.cached_into_node(ctx, cache)?;
         crate::nso::other::nso_clamp(root, min, max, ctx)
       }
       Compound::Map {
@@ -121,23 +335,645 @@ impl IntoNode for &Compound {
         out_min,
         out_max,
       } => {
-        let root = root.into_node(ctx)?;
-        let in_min = in_min.into_node(ctx)?;
-        let in_max = in_max.into_node(ctx)?;
-        let out_min = out_min.into_node(ctx)?;
-        let out_max = out_max.into_node(ctx)?;
+        let root = root.cached_into_node(ctx, cache)?;
+        let in_min = in_min.cached_into_node(ctx, cache)?;
+        let in_max = in_max.cached_into_node(ctx, cache)?;
+        let out_min = out_min.cached_into_node(ctx, cache)?;
+        let out_max = out_max.cached_into_node(ctx, cache)?;
         crate::nso::other::nso_map(root, in_min, in_max, out_min, out_max, ctx)
       }
+      Compound::Repeat {
+        root,
+        spacing,
+        count,
+      } => {
+        let root = root.cached_into_node(ctx, cache)?;
+        crate::nso::repetition::nso_repeat(root, *spacing, *count, ctx)
+      }
+      Compound::RepeatInfinite { root, spacing } => {
+        let root = root.cached_into_node(ctx, cache)?;
+        crate::nso::repetition::nso_repeat_infinite(root, *spacing, ctx)
+      }
+      Compound::Mirror {
+        root,
+        normal,
+        offset,
+      } => {
+        let root = root.cached_into_node(ctx, cache)?;
+        crate::nso::repetition::nso_mirror(root, *normal, *offset, ctx)
+      }
+      Compound::PolarRepeat { root, count } => {
+        let root = root.cached_into_node(ctx, cache)?;
+        crate::nso::repetition::nso_polar_repeat(root, *count, ctx)
+      }
       Compound::CatmullRomSpline {
-        root: _,
-        points: _,
-        tension: _,
+        root,
+        points,
+        tension,
       } => {
-        // let root = root.into_node(ctx)?;
-        // crate::nso::spline::nso_catmull_rom_spline(root, points, *tension,
-        // ctx)
-        todo!()
+        let root = root.cached_into_node(ctx, cache)?;
+        crate::nso::spline::nso_catmull_rom_spline(root, points, *tension, ctx)
+      }
+      Compound::Twist { root, rate } => {
+        let root = root.cached_into_node(ctx, cache)?;
+        let rate = rate.cached_into_node(ctx, cache)?;
+        crate::nso::deform::nso_twist(root, rate, ctx)
       }
+      Compound::Bend { root, rate } => {
+        let root = root.cached_into_node(ctx, cache)?;
+        let rate = rate.cached_into_node(ctx, cache)?;
+        crate::nso::deform::nso_bend(root, rate, ctx)
+      }
+      Compound::Taper { root, rate } => {
+        let root = root.cached_into_node(ctx, cache)?;
+        let rate = rate.cached_into_node(ctx, cache)?;
+        crate::nso::deform::nso_taper(root, rate, ctx)
+      }
+      Compound::Shear {
+        root,
+        x_rate,
+        z_rate,
+      } => {
+        let root = root.cached_into_node(ctx, cache)?;
+        let x_rate = x_rate.cached_into_node(ctx, cache)?;
+        let z_rate = z_rate.cached_into_node(ctx, cache)?;
+        crate::nso::deform::nso_shear(root, x_rate, z_rate, ctx)
+      }
+      Compound::Noise { seed, frequency } => {
+        let frequency = frequency.cached_into_node(ctx, cache)?;
+        crate::nso::noise::nso_gradient_noise(*seed, frequency, ctx)
+      }
+      Compound::Fbm {
+        seed,
+        octaves,
+        frequency,
+        lacunarity,
+        gain,
+      } => {
+        let frequency = frequency.cached_into_node(ctx, cache)?;
+        let lacunarity = lacunarity.cached_into_node(ctx, cache)?;
+        let gain = gain.cached_into_node(ctx, cache)?;
+        crate::nso::noise::nso_fbm(
+          *seed, *octaves, frequency, lacunarity, gain, ctx,
+        )
+      }
+      // materials only matter to the mesher, which reads them from the tree
+      Compound::Material { root, .. } => root.cached_into_node(ctx, cache),
+      Compound::Offset { root, distance } => {
+        let root = root.cached_into_node(ctx, cache)?;
+        let distance = distance.cached_into_node(ctx, cache)?;
+        crate::nso::hollow::nso_offset(root, distance, ctx)
+      }
+      Compound::Shell { root, thickness } => {
+        let root = root.cached_into_node(ctx, cache)?;
+        let thickness = thickness.cached_into_node(ctx, cache)?;
+        crate::nso::hollow::nso_shell(root, thickness, ctx)
+      }
+      Compound::Onion {
+        root,
+        thickness,
+        count,
+      } => {
+        let root = root.cached_into_node(ctx, cache)?;
+        let thickness = thickness.cached_into_node(ctx, cache)?;
+        crate::nso::hollow::nso_onion(root, thickness, *count, ctx)
+      }
+      Compound::Circle { radius } => {
+        let radius = radius.cached_into_node(ctx, cache)?;
+        crate::nso::profile::nso_circle(radius, ctx)
+      }
+      Compound::Rectangle { x, y } => {
+        let x = x.cached_into_node(ctx, cache)?;
+        let y = y.cached_into_node(ctx, cache)?;
+        crate::nso::profile::nso_rectangle(x, y, ctx)
+      }
+      Compound::RoundedRectangle { x, y, radius } => {
+        let x = x.cached_into_node(ctx, cache)?;
+        let y = y.cached_into_node(ctx, cache)?;
+        let radius = radius.cached_into_node(ctx, cache)?;
+        crate::nso::profile::nso_rounded_rectangle(x, y, radius, ctx)
+      }
+      Compound::Polygon { points } => {
+        crate::nso::profile::nso_polygon(points, ctx)
+      }
+      Compound::Extrude { profile, depth } => {
+        let profile = profile.cached_into_node(ctx, cache)?;
+        let depth = depth.cached_into_node(ctx, cache)?;
+        crate::nso::profile::nso_extrude(profile, depth, ctx)
+      }
+      Compound::Revolve { profile, axis } => {
+        let profile = profile.cached_into_node(ctx, cache)?;
+        crate::nso::profile::nso_revolve(profile, *axis, ctx)
+      }
+      Compound::DistanceGrid {
+        size,
+        extent,
+        values,
+      } => crate::nso::grid::nso_distance_grid(*size, *extent, values, ctx),
+      Compound::Heightmap {
+        size,
+        extent,
+        heights,
+      } => crate::nso::grid::nso_heightmap(*size, *extent, heights, ctx),
+    }
+  }
+}
+
+impl Compound {
+  /// Returns a copy of this compound with `f` applied to each of its child
+  /// shapes.
+  pub fn map_children(&self, mut f: impl FnMut(&Shape) -> Shape) -> Compound {
+    self.map_shared_children(|s| SharedShape::new(f(s)))
+  }
+
+  /// Like [`Compound::map_children`], but `f` can return a child unchanged to
+  /// keep sharing it.
+  pub fn map_shared_children(
+    &self,
+    mut f: impl FnMut(&SharedShape) -> SharedShape,
+  ) -> Compound {
+    match self {
+      Compound::Sphere { radius } => Compound::Sphere { radius: f(radius) },
+      Compound::Cylinder { height, radius } => Compound::Cylinder {
+        height: f(height),
+        radius: f(radius),
+      },
+      Compound::Cuboid { x, y, z } => Compound::Cuboid {
+        x: f(x),
+        y: f(y),
+        z: f(z),
+      },
+      Compound::RoundedBox { x, y, z, radius } => Compound::RoundedBox {
+        x:      f(x),
+        y:      f(y),
+        z:      f(z),
+        radius: f(radius),
+      },
+      Compound::Torus { major, minor } => Compound::Torus {
+        major: f(major),
+        minor: f(minor),
+      },
+      Compound::Capsule { height, radius } => Compound::Capsule {
+        height: f(height),
+        radius: f(radius),
+      },
+      Compound::Cone { height, radius } => Compound::Cone {
+        height: f(height),
+        radius: f(radius),
+      },
+      Compound::Plane {
+        normal_x,
+        normal_y,
+        normal_z,
+        offset,
+      } => Compound::Plane {
+        normal_x: f(normal_x),
+        normal_y: f(normal_y),
+        normal_z: f(normal_z),
+        offset:   f(offset),
+      },
+      Compound::Ellipsoid { x, y, z } => Compound::Ellipsoid {
+        x: f(x),
+        y: f(y),
+        z: f(z),
+      },
+      Compound::SmoothMinCubic { lhs, rhs, k } => Compound::SmoothMinCubic {
+        lhs: f(lhs),
+        rhs: f(rhs),
+        k:   f(k),
+      },
+      Compound::SmoothMaxCubic { lhs, rhs, k } => Compound::SmoothMaxCubic {
+        lhs: f(lhs),
+        rhs: f(rhs),
+        k:   f(k),
+      },
+      Compound::SmoothMinQuadratic { lhs, rhs, k } => {
+        Compound::SmoothMinQuadratic {
+          lhs: f(lhs),
+          rhs: f(rhs),
+          k:   f(k),
+        }
+      }
+      Compound::SmoothMinExponential { lhs, rhs, k } => {
+        Compound::SmoothMinExponential {
+          lhs: f(lhs),
+          rhs: f(rhs),
+          k:   f(k),
+        }
+      }
+      Compound::ChamferMin { lhs, rhs, radius } => Compound::ChamferMin {
+        lhs:    f(lhs),
+        rhs:    f(rhs),
+        radius: f(radius),
+      },
+      Compound::RoundMin { lhs, rhs, radius } => Compound::RoundMin {
+        lhs:    f(lhs),
+        rhs:    f(rhs),
+        radius: f(radius),
+      },
+      Compound::MatTransform { root, mat } => Compound::MatTransform {
+        root: f(root),
+        mat:  *mat,
+      },
+      Compound::Clamp { root, min, max } => Compound::Clamp {
+        root: f(root),
+        min:  f(min),
+        max:  f(max),
+      },
+      Compound::Map {
+        root,
+        in_min,
+        in_max,
+        out_min,
+        out_max,
+      } => Compound::Map {
+        root:    f(root),
+        in_min:  f(in_min),
+        in_max:  f(in_max),
+        out_min: f(out_min),
+        out_max: f(out_max),
+      },
+      Compound::Repeat {
+        root,
+        spacing,
+        count,
+      } => Compound::Repeat {
+        root:    f(root),
+        spacing: *spacing,
+        count:   *count,
+      },
+      Compound::RepeatInfinite { root, spacing } => Compound::RepeatInfinite {
+        root:    f(root),
+        spacing: *spacing,
+      },
+      Compound::Mirror {
+        root,
+        normal,
+        offset,
+      } => Compound::Mirror {
+        root:   f(root),
+        normal: *normal,
+        offset: *offset,
+      },
+      Compound::PolarRepeat { root, count } => Compound::PolarRepeat {
+        root:  f(root),
+        count: *count,
+      },
+      Compound::CatmullRomSpline {
+        root,
+        points,
+        tension,
+      } => Compound::CatmullRomSpline {
+        root:    f(root),
+        points:  points.clone(),
+        tension: *tension,
+      },
+      Compound::Twist { root, rate } => Compound::Twist {
+        root: f(root),
+        rate: f(rate),
+      },
+      Compound::Bend { root, rate } => Compound::Bend {
+        root: f(root),
+        rate: f(rate),
+      },
+      Compound::Taper { root, rate } => Compound::Taper {
+        root: f(root),
+        rate: f(rate),
+      },
+      Compound::Shear {
+        root,
+        x_rate,
+        z_rate,
+      } => Compound::Shear {
+        root:   f(root),
+        x_rate: f(x_rate),
+        z_rate: f(z_rate),
+      },
+      Compound::Noise { seed, frequency } => Compound::Noise {
+        seed:      *seed,
+        frequency: f(frequency),
+      },
+      Compound::Fbm {
+        seed,
+        octaves,
+        frequency,
+        lacunarity,
+        gain,
+      } => Compound::Fbm {
+        seed:       *seed,
+        octaves:    *octaves,
+        frequency:  f(frequency),
+        lacunarity: f(lacunarity),
+        gain:       f(gain),
+      },
+      Compound::Material { root, id } => Compound::Material {
+        root: f(root),
+        id:   *id,
+      },
+      Compound::Offset { root, distance } => Compound::Offset {
+        root:     f(root),
+        distance: f(distance),
+      },
+      Compound::Shell { root, thickness } => Compound::Shell {
+        root:      f(root),
+        thickness: f(thickness),
+      },
+      Compound::Onion {
+        root,
+        thickness,
+        count,
+      } => Compound::Onion {
+        root:      f(root),
+        thickness: f(thickness),
+        count:     *count,
+      },
+      Compound::Circle { radius } => Compound::Circle { radius: f(radius) },
+      Compound::Rectangle { x, y } => Compound::Rectangle { x: f(x), y: f(y) },
+      Compound::RoundedRectangle { x, y, radius } => {
+        Compound::RoundedRectangle {
+          x:      f(x),
+          y:      f(y),
+          radius: f(radius),
+        }
+      }
+      Compound::Polygon { points } => Compound::Polygon {
+        points: points.clone(),
+      },
+      Compound::Extrude { profile, depth } => Compound::Extrude {
+        profile: f(profile),
+        depth:   f(depth),
+      },
+      Compound::Revolve { profile, axis } => Compound::Revolve {
+        profile: f(profile),
+        axis:    *axis,
+      },
+      Compound::DistanceGrid {
+        size,
+        extent,
+        values,
+      } => Compound::DistanceGrid {
+        size:   *size,
+        extent: *extent,
+        values: values.clone(),
+      },
+      Compound::Heightmap {
+        size,
+        extent,
+        heights,
+      } => Compound::Heightmap {
+        size:    *size,
+        extent:  *extent,
+        heights: heights.clone(),
+      },
+    }
+  }
+
+  /// The name of the variant, for diagnostics.
+  pub fn name(&self) -> &'static str {
+    match self {
+      Compound::Sphere { .. } => "Sphere",
+      Compound::Cylinder { .. } => "Cylinder",
+      Compound::Cuboid { .. } => "Cuboid",
+      Compound::RoundedBox { .. } => "RoundedBox",
+      Compound::Torus { .. } => "Torus",
+      Compound::Capsule { .. } => "Capsule",
+      Compound::Cone { .. } => "Cone",
+      Compound::Plane { .. } => "Plane",
+      Compound::Ellipsoid { .. } => "Ellipsoid",
+      Compound::SmoothMinCubic { .. } => "SmoothMinCubic",
+      Compound::SmoothMaxCubic { .. } => "SmoothMaxCubic",
+      Compound::SmoothMinQuadratic { .. } => "SmoothMinQuadratic",
+      Compound::SmoothMinExponential { .. } => "SmoothMinExponential",
+      Compound::ChamferMin { .. } => "ChamferMin",
+      Compound::RoundMin { .. } => "RoundMin",
+      Compound::MatTransform { .. } => "MatTransform",
+      Compound::Clamp { .. } => "Clamp",
+      Compound::Map { .. } => "Map",
+      Compound::Repeat { .. } => "Repeat",
+      Compound::RepeatInfinite { .. } => "RepeatInfinite",
+      Compound::Mirror { .. } => "Mirror",
+      Compound::PolarRepeat { .. } => "PolarRepeat",
+      Compound::CatmullRomSpline { .. } => "CatmullRomSpline",
+      Compound::Twist { .. } => "Twist",
+      Compound::Bend { .. } => "Bend",
+      Compound::Taper { .. } => "Taper",
+      Compound::Shear { .. } => "Shear",
+      Compound::Noise { .. } => "Noise",
+      Compound::Fbm { .. } => "Fbm",
+      Compound::Material { .. } => "Material",
+      Compound::Offset { .. } => "Offset",
+      Compound::Shell { .. } => "Shell",
+      Compound::Onion { .. } => "Onion",
+      Compound::Circle { .. } => "Circle",
+      Compound::Rectangle { .. } => "Rectangle",
+      Compound::RoundedRectangle { .. } => "RoundedRectangle",
+      Compound::Polygon { .. } => "Polygon",
+      Compound::Extrude { .. } => "Extrude",
+      Compound::Revolve { .. } => "Revolve",
+      Compound::DistanceGrid { .. } => "DistanceGrid",
+      Compound::Heightmap { .. } => "Heightmap",
+    }
+  }
+
+  /// Returns whether the child in `field` is the one shape that this compound
+  /// transforms, in space or in value, as opposed to a parameter or one of
+  /// several shapes that it combines. The compound's surface is then that
+  /// child's surface, moved, so a region tagged within the child can be
+  /// placed by wrapping it in the compound.
+  pub fn transforms_child(&self, field: &str) -> bool {
+    match self {
+      Compound::MatTransform { .. }
+      | Compound::Clamp { .. }
+      | Compound::Map { .. }
+      | Compound::Repeat { .. }
+      | Compound::RepeatInfinite { .. }
+      | Compound::Mirror { .. }
+      | Compound::PolarRepeat { .. }
+      | Compound::CatmullRomSpline { .. }
+      | Compound::Twist { .. }
+      | Compound::Bend { .. }
+      | Compound::Taper { .. }
+      | Compound::Shear { .. }
+      | Compound::Material { .. }
+      | Compound::Offset { .. }
+      | Compound::Shell { .. }
+      | Compound::Onion { .. } => field == "root",
+      Compound::Extrude { .. } | Compound::Revolve { .. } => field == "profile",
+      Compound::Sphere { .. }
+      | Compound::Cylinder { .. }
+      | Compound::Cuboid { .. }
+      | Compound::RoundedBox { .. }
+      | Compound::Torus { .. }
+      | Compound::Capsule { .. }
+      | Compound::Cone { .. }
+      | Compound::Plane { .. }
+      | Compound::Ellipsoid { .. }
+      | Compound::SmoothMinCubic { .. }
+      | Compound::SmoothMaxCubic { .. }
+      | Compound::SmoothMinQuadratic { .. }
+      | Compound::SmoothMinExponential { .. }
+      | Compound::ChamferMin { .. }
+      | Compound::RoundMin { .. }
+      | Compound::Noise { .. }
+      | Compound::Fbm { .. }
+      | Compound::Circle { .. }
+      | Compound::Rectangle { .. }
+      | Compound::RoundedRectangle { .. }
+      | Compound::Polygon { .. }
+      | Compound::DistanceGrid { .. }
+      | Compound::Heightmap { .. } => false,
+    }
+  }
+
+  /// Returns each of the child shapes along with the name of its field.
+  pub fn children(&self) -> Vec<(&'static str, &Shape)> {
+    match self {
+      Compound::Sphere { radius } => vec![("radius", radius.as_ref())],
+      Compound::Cylinder { height, radius } => {
+        vec![("height", height.as_ref()), ("radius", radius.as_ref())]
+      }
+      Compound::Cuboid { x, y, z } => {
+        vec![("x", x.as_ref()), ("y", y.as_ref()), ("z", z.as_ref())]
+      }
+      Compound::RoundedBox { x, y, z, radius } => vec![
+        ("x", x.as_ref()),
+        ("y", y.as_ref()),
+        ("z", z.as_ref()),
+        ("radius", radius.as_ref()),
+      ],
+      Compound::Torus { major, minor } => {
+        vec![("major", major.as_ref()), ("minor", minor.as_ref())]
+      }
+      Compound::Capsule { height, radius } => {
+        vec![("height", height.as_ref()), ("radius", radius.as_ref())]
+      }
+      Compound::Cone { height, radius } => {
+        vec![("height", height.as_ref()), ("radius", radius.as_ref())]
+      }
+      Compound::Plane {
+        normal_x,
+        normal_y,
+        normal_z,
+        offset,
+      } => vec![
+        ("normal_x", normal_x.as_ref()),
+        ("normal_y", normal_y.as_ref()),
+        ("normal_z", normal_z.as_ref()),
+        ("offset", offset.as_ref()),
+      ],
+      Compound::Ellipsoid { x, y, z } => {
+        vec![("x", x.as_ref()), ("y", y.as_ref()), ("z", z.as_ref())]
+      }
+      Compound::SmoothMinCubic { lhs, rhs, k } => vec![
+        ("lhs", lhs.as_ref()),
+        ("rhs", rhs.as_ref()),
+        ("k", k.as_ref()),
+      ],
+      Compound::SmoothMaxCubic { lhs, rhs, k } => vec![
+        ("lhs", lhs.as_ref()),
+        ("rhs", rhs.as_ref()),
+        ("k", k.as_ref()),
+      ],
+      Compound::SmoothMinQuadratic { lhs, rhs, k } => vec![
+        ("lhs", lhs.as_ref()),
+        ("rhs", rhs.as_ref()),
+        ("k", k.as_ref()),
+      ],
+      Compound::SmoothMinExponential { lhs, rhs, k } => vec![
+        ("lhs", lhs.as_ref()),
+        ("rhs", rhs.as_ref()),
+        ("k", k.as_ref()),
+      ],
+      Compound::ChamferMin { lhs, rhs, radius } => vec![
+        ("lhs", lhs.as_ref()),
+        ("rhs", rhs.as_ref()),
+        ("radius", radius.as_ref()),
+      ],
+      Compound::RoundMin { lhs, rhs, radius } => vec![
+        ("lhs", lhs.as_ref()),
+        ("rhs", rhs.as_ref()),
+        ("radius", radius.as_ref()),
+      ],
+      Compound::MatTransform { root, .. } => vec![("root", root.as_ref())],
+      Compound::Clamp { root, min, max } => vec![
+        ("root", root.as_ref()),
+        ("min", min.as_ref()),
+        ("max", max.as_ref()),
+      ],
+      Compound::Map {
+        root,
+        in_min,
+        in_max,
+        out_min,
+        out_max,
+      } => vec![
+        ("root", root.as_ref()),
+        ("in_min", in_min.as_ref()),
+        ("in_max", in_max.as_ref()),
+        ("out_min", out_min.as_ref()),
+        ("out_max", out_max.as_ref()),
+      ],
+      Compound::Repeat { root, .. } => vec![("root", root.as_ref())],
+      Compound::RepeatInfinite { root, .. } => vec![("root", root.as_ref())],
+      Compound::Mirror { root, .. } => vec![("root", root.as_ref())],
+      Compound::PolarRepeat { root, .. } => vec![("root", root.as_ref())],
+      Compound::CatmullRomSpline { root, .. } => vec![("root", root.as_ref())],
+      Compound::Twist { root, rate } => {
+        vec![("root", root.as_ref()), ("rate", rate.as_ref())]
+      }
+      Compound::Bend { root, rate } => {
+        vec![("root", root.as_ref()), ("rate", rate.as_ref())]
+      }
+      Compound::Taper { root, rate } => {
+        vec![("root", root.as_ref()), ("rate", rate.as_ref())]
+      }
+      Compound::Shear {
+        root,
+        x_rate,
+        z_rate,
+      } => vec![
+        ("root", root.as_ref()),
+        ("x_rate", x_rate.as_ref()),
+        ("z_rate", z_rate.as_ref()),
+      ],
+      Compound::Noise { frequency, .. } => {
+        vec![("frequency", frequency.as_ref())]
+      }
+      Compound::Fbm {
+        frequency,
+        lacunarity,
+        gain,
+        ..
+      } => vec![
+        ("frequency", frequency.as_ref()),
+        ("lacunarity", lacunarity.as_ref()),
+        ("gain", gain.as_ref()),
+      ],
+      Compound::Material { root, .. } => vec![("root", root.as_ref())],
+      Compound::Offset { root, distance } => {
+        vec![("root", root.as_ref()), ("distance", distance.as_ref())]
+      }
+      Compound::Shell { root, thickness } => {
+        vec![("root", root.as_ref()), ("thickness", thickness.as_ref())]
+      }
+      Compound::Onion {
+        root, thickness, ..
+      } => vec![("root", root.as_ref()), ("thickness", thickness.as_ref())],
+      Compound::Circle { radius } => vec![("radius", radius.as_ref())],
+      Compound::Rectangle { x, y } => {
+        vec![("x", x.as_ref()), ("y", y.as_ref())]
+      }
+      Compound::RoundedRectangle { x, y, radius } => vec![
+        ("x", x.as_ref()),
+        ("y", y.as_ref()),
+        ("radius", radius.as_ref()),
+      ],
+      Compound::Polygon { .. } => vec![],
+      Compound::Extrude { profile, depth } => {
+        vec![("profile", profile.as_ref()), ("depth", depth.as_ref())]
+      }
+      Compound::Revolve { profile, .. } => vec![("profile", profile.as_ref())],
+      Compound::DistanceGrid { .. } | Compound::Heightmap { .. } => vec![],
     }
   }
 }
@@ -147,9 +983,502 @@ fn hash_mat4<H: Hasher>(s: &glam::Mat4, state: &mut H) {
     .iter()
     .for_each(|v| decorum::hash::FloatHash::float_hash(v, state));
 }
+fn hash_triplet_f32<H: Hasher>(s: &[f32; 3], state: &mut H) {
+  s.iter()
+    .for_each(|v| decorum::hash::FloatHash::float_hash(v, state));
+}
 fn hash_vec_triplet_f32<H: Hasher>(s: &[[f32; 3]], state: &mut H) {
   s.iter().for_each(|a| {
     a.iter()
       .for_each(|v| decorum::hash::FloatHash::float_hash(v, state))
   })
 }
+fn hash_pair_f32<H: Hasher>(s: &[f32; 2], state: &mut H) {
+  s.iter()
+    .for_each(|v| decorum::hash::FloatHash::float_hash(v, state));
+}
+fn hash_vec_f32<H: Hasher>(s: &[f32], state: &mut H) {
+  s.iter()
+    .for_each(|v| decorum::hash::FloatHash::float_hash(v, state));
+}
+fn hash_vec_pair_f32<H: Hasher>(s: &[[f32; 2]], state: &mut H) {
+  s.iter().for_each(|a| {
+    a.iter()
+      .for_each(|v| decorum::hash::FloatHash::float_hash(v, state))
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use fidget::{context::IntoNode, Context};
+  use float_cmp::approx_eq;
+
+  use crate::shape::{builder, Shape, SharedShape};
+
+  fn eval(shape: &Shape, x: f64, y: f64, z: f64) -> f64 {
+    let mut ctx = Context::new();
+    let node = shape.into_node(&mut ctx).unwrap();
+    ctx.eval_xyz(node, x, y, z).unwrap()
+  }
+
+  fn assert_dist(shape: &Shape, point: [f64; 3], expected: f64) {
+    let actual = eval(shape, point[0], point[1], point[2]);
+    assert!(
+      approx_eq!(f64, actual, expected, epsilon = 1e-5),
+      "expected {expected} at {point:?}, got {actual}"
+    );
+  }
+
+  #[test]
+  fn rounded_box_is_exact() {
+    let shape = builder::rounded_box(1.0, 2.0, 3.0, 0.5);
+    assert_dist(&shape, [0.0, 0.0, 0.0], -1.0);
+    assert_dist(&shape, [2.0, 0.0, 0.0], 1.0);
+    assert_dist(&shape, [0.0, 0.0, -4.0], 1.0);
+    // off the rounded xy edge, whose center line is at (0.5, 1.5)
+    assert_dist(&shape, [1.0, 2.0, 0.0], 0.5_f64.sqrt() - 0.5);
+    assert_dist(&shape, [1.5, 2.5, 0.0], 2.0_f64.sqrt() - 0.5);
+  }
+
+  #[test]
+  fn exact_cuboid_is_exact_at_corners() {
+    let shape = builder::exact_cuboid(1.0, 1.0, 1.0);
+    assert_dist(&shape, [0.0, 0.0, 0.0], -1.0);
+    assert_dist(&shape, [2.0, 2.0, 1.0], 2.0_f64.sqrt());
+    assert_dist(&shape, [2.0, 2.0, 2.0], 3.0_f64.sqrt());
+  }
+
+  #[test]
+  fn torus_is_exact() {
+    let shape = builder::torus(2.0, 0.5);
+    assert_dist(&shape, [2.0, 0.0, 0.0], -0.5);
+    assert_dist(&shape, [0.0, 0.0, -2.0], -0.5);
+    assert_dist(&shape, [0.0, 0.0, 0.0], 1.5);
+    assert_dist(&shape, [2.0, 1.5, 0.0], 1.0);
+    assert_dist(&shape, [0.0, 3.0, 0.0], 13.0_f64.sqrt() - 0.5);
+  }
+
+  #[test]
+  fn capsule_is_exact() {
+    let shape = builder::capsule(2.0, 0.5);
+    assert_dist(&shape, [0.0, 0.0, 0.0], -0.5);
+    assert_dist(&shape, [1.0, 0.5, 0.0], 0.5);
+    assert_dist(&shape, [0.0, 3.0, 0.0], 1.5);
+    assert_dist(&shape, [0.0, -1.0, 0.0], -0.5);
+    assert_dist(&shape, [3.0, 5.0, 0.0], 4.5);
+  }
+
+  #[test]
+  fn cone_is_exact() {
+    let shape = builder::cone(2.0, 1.0);
+    // below the base
+    assert_dist(&shape, [0.0, -2.0, 0.0], 1.0);
+    // above the tip
+    assert_dist(&shape, [0.0, 3.0, 0.0], 2.0);
+    // outside the rim
+    assert_dist(&shape, [2.0, -1.0, 0.0], 1.0);
+    // on the base, inside the disk
+    assert_dist(&shape, [0.0, -1.0, 0.5], 0.0);
+    // on the axis, the nearest surface is the slanted side
+    assert_dist(&shape, [0.0, 0.0, 0.0], -1.0 / 5.0_f64.sqrt());
+    // perpendicular off the middle of the slanted side
+    let n = 5.0_f64.sqrt();
+    assert_dist(&shape, [0.5 + 2.0 / n, 2.0 / n / 2.0, 0.0], 1.0);
+  }
+
+  #[test]
+  fn plane_is_exact() {
+    let shape = builder::plane([0.0, 2.0, 0.0], 1.0);
+    assert_dist(&shape, [5.0, 1.0, -3.0], 0.0);
+    assert_dist(&shape, [0.0, 4.0, 0.0], 3.0);
+    assert_dist(&shape, [0.0, -1.0, 7.0], -2.0);
+
+    let shape = builder::plane([1.0, 1.0, 0.0], 0.0);
+    assert_dist(&shape, [1.0, 1.0, 0.0], 2.0_f64.sqrt());
+  }
+
+  #[test]
+  fn ellipsoid_is_exact_on_axes() {
+    let shape = builder::ellipsoid(1.0, 2.0, 3.0);
+    assert_dist(&shape, [2.0, 0.0, 0.0], 1.0);
+    assert_dist(&shape, [0.0, -4.0, 0.0], 2.0);
+    assert_dist(&shape, [0.0, 0.0, 6.0], 3.0);
+    assert!(eval(&shape, 0.0, 0.0, 1.5) < 0.0);
+    assert!(eval(&shape, 0.5, 0.5, 0.5) < 0.0);
+    assert!(eval(&shape, 0.9, 1.9, 0.0) > 0.0);
+
+    let sphere = builder::ellipsoid(1.5, 1.5, 1.5);
+    assert_dist(&sphere, [1.0, 2.0, 2.0], 1.5);
+  }
+
+  #[test]
+  fn ellipsoid_is_inside_at_its_center() {
+    let shape = builder::ellipsoid(1.0, 2.0, 3.0);
+    assert_dist(&shape, [0.0, 0.0, 0.0], -1.0);
+    assert!(eval(&shape, 1e-6, 0.0, 0.0) < 0.0);
+  }
+
+  #[test]
+  fn catmull_rom_tube_sweeps_a_circle() {
+    let shape = builder::catmull_rom_tube(
+      vec![[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 2.0, 0.0]],
+      0.5,
+      0.5,
+    );
+    assert_dist(&shape, [0.0, 1.0, 0.0], -0.5);
+    assert_dist(&shape, [1.0, 1.5, 0.0], 0.5);
+    assert_dist(&shape, [0.0, 3.0, 0.0], 1.0);
+  }
+
+  #[test]
+  fn shell_hollows_a_solid() {
+    let shape = builder::shell(builder::sphere(2.0), 0.5);
+    assert_dist(&shape, [0.0, 0.0, 0.0], 1.75);
+    assert_dist(&shape, [2.0, 0.0, 0.0], -0.25);
+    assert!(eval(&shape, 0.0, 1.8, 0.0) < 0.0);
+    assert!(eval(&shape, 0.0, 0.0, 1.5) > 0.0);
+    assert!(eval(&shape, 3.0, 0.0, 0.0) > 0.0);
+  }
+
+  #[test]
+  fn onion_nests_walls() {
+    // walls 0.2 thick, centered 0.2 either side of the sphere's surface
+    let shape = builder::onion(builder::sphere(2.0), 0.2, 2);
+    assert_dist(&shape, [2.0, 0.0, 0.0], 0.1);
+    assert_dist(&shape, [2.2, 0.0, 0.0], -0.1);
+    assert_dist(&shape, [0.0, 1.8, 0.0], -0.1);
+    assert_dist(&shape, [0.0, 0.0, 0.0], 1.7);
+    assert!(eval(&shape, 2.5, 0.0, 0.0) > 0.0);
+
+    let single = builder::onion(builder::sphere(2.0), 0.5, 1);
+    assert_dist(&single, [0.0, 0.0, 0.0], 1.75);
+
+    // four walls, centered 0.2 and 0.6 either side of the surface
+    let shape = builder::onion(builder::sphere(2.0), 0.2, 3);
+    for r in [1.4, 1.8, 2.2, 2.6] {
+      assert_dist(&shape, [r, 0.0, 0.0], -0.1);
+    }
+    for r in [1.6, 2.0, 2.4] {
+      assert_dist(&shape, [r, 0.0, 0.0], 0.1);
+    }
+    assert_dist(&shape, [0.0, 0.0, 0.0], 1.3);
+    assert_dist(&shape, [3.0, 0.0, 0.0], 0.3);
+  }
+
+  #[test]
+  fn offset_grows_and_rounds() {
+    let shape = builder::round(builder::exact_cuboid(1.0, 1.0, 1.0), 0.5);
+    assert_dist(&shape, [1.5, 0.0, 0.0], 0.0);
+    assert!(eval(&shape, 1.4, 0.0, 0.0) < 0.0);
+    assert!(eval(&shape, 1.6, 0.0, 0.0) > 0.0);
+    // the corner is rounded off rather than extended
+    assert!(eval(&shape, 1.25, 1.25, 1.25) < 0.0);
+    assert!(eval(&shape, 1.35, 1.35, 1.35) > 0.0);
+
+    let shrunk = builder::offset(builder::sphere(2.0), -0.5);
+    assert_dist(&shrunk, [1.6, 0.0, 0.0], 0.1);
+    assert!(eval(&shrunk, 0.0, 1.4, 0.0) < 0.0);
+  }
+
+  #[test]
+  fn repeat_places_bounded_copies() {
+    let shape = builder::repeat_x(builder::sphere(0.25), 1.0, 3);
+    assert_dist(&shape, [-1.0, 0.0, 0.0], -0.25);
+    assert_dist(&shape, [0.0, 0.0, 0.0], -0.25);
+    assert_dist(&shape, [1.0, 0.0, 0.0], -0.25);
+    assert_dist(&shape, [0.5, 0.0, 0.0], 0.25);
+    assert_dist(&shape, [-0.7, 0.0, 0.0], 0.05);
+    // there are only three copies
+    assert_dist(&shape, [2.0, 0.0, 0.0], 0.75);
+    assert_dist(&shape, [-3.0, 0.0, 0.0], 1.75);
+
+    let shape =
+      builder::repeat(builder::sphere(0.25), [1.0, 2.0, 1.0], [2, 2, 1]);
+    assert_dist(&shape, [0.5, 1.0, 0.0], -0.25);
+    assert_dist(&shape, [-0.5, -1.0, 0.0], -0.25);
+    assert_dist(&shape, [0.5, 1.0, 1.0], 0.75);
+  }
+
+  #[test]
+  fn repeat_infinite_places_endless_copies() {
+    let shape = builder::repeat_infinite_x(builder::sphere(0.25), 1.0);
+    assert_dist(&shape, [0.0, 0.0, 0.0], -0.25);
+    assert_dist(&shape, [0.5, 0.0, 0.0], 0.25);
+    assert_dist(&shape, [-0.7, 0.0, 0.0], 0.05);
+    assert_dist(&shape, [-1.0, 0.0, 0.0], -0.25);
+    assert_dist(&shape, [37.0, 0.0, 0.0], -0.25);
+    assert_dist(&shape, [-1000.3, 0.0, 0.0], 0.05);
+    assert_dist(&shape, [20.0, 1.0, 0.0], 0.75);
+
+    // an asymmetric instance keeps its orientation on both sides
+    let shape = builder::repeat_infinite(
+      builder::translate(builder::sphere(0.25), 0.2, 0.0, 0.0),
+      [1.0, 0.0, 2.0],
+    );
+    assert_dist(&shape, [-2.8, 0.0, 4.0], -0.25);
+    assert_dist(&shape, [3.2, 0.0, -6.0], -0.25);
+  }
+
+  #[test]
+  fn mirror_reflects_the_positive_side() {
+    let shape = builder::mirror_x(builder::translate(
+      builder::sphere(0.5),
+      1.0,
+      0.0,
+      0.0,
+    ));
+    assert_dist(&shape, [1.0, 0.0, 0.0], -0.5);
+    assert_dist(&shape, [-1.0, 0.0, 0.0], -0.5);
+    assert_dist(&shape, [-2.0, 0.0, 0.0], 0.5);
+
+    let shape = builder::mirror(
+      builder::translate(builder::sphere(1.0), 0.0, 3.0, 0.0),
+      [0.0, 1.0, 0.0],
+      2.0,
+    );
+    assert_dist(&shape, [0.0, 3.0, 0.0], -1.0);
+    assert_dist(&shape, [0.0, 1.0, 0.0], -1.0);
+    assert_dist(&shape, [0.0, -1.0, 0.0], 1.0);
+  }
+
+  #[test]
+  fn polar_repeat_places_copies_around_y() {
+    let shape = builder::polar_repeat(
+      builder::translate(builder::sphere(0.5), 2.0, 0.0, 0.0),
+      6,
+    );
+    for i in 0..6 {
+      let angle = i as f64 * std::f64::consts::TAU / 6.0;
+      let (sin, cos) = angle.sin_cos();
+      assert_dist(&shape, [2.0 * cos, 0.0, 2.0 * sin], -0.5);
+      assert_dist(&shape, [3.0 * cos, 1.0, 3.0 * sin], 2.0_f64.sqrt() - 0.5);
+    }
+    assert_dist(&shape, [0.0, 0.0, 0.0], 1.5);
+  }
+
+  #[test]
+  fn blended_intersections_mirror_unions() {
+    let shape = builder::round_max(builder::x(), builder::y(), 1.0);
+    assert_dist(&shape, [0.0, 0.0, 0.0], 2.0_f64.sqrt() - 1.0);
+    assert_dist(&shape, [-2.0, -2.0, 0.0], -2.0);
+    assert_dist(&shape, [3.0, -1.0, 0.0], 3.0);
+
+    let shape = builder::chamfer_difference(builder::x(), builder::y(), 1.0);
+    assert_dist(&shape, [0.0, 0.0, 0.0], std::f64::consts::FRAC_1_SQRT_2);
+    assert_dist(&shape, [-3.0, 3.0, 0.0], -3.0);
+
+    let shape = builder::smooth_difference_cubic(
+      builder::sphere(1.0),
+      builder::translate(builder::sphere(1.0), 1.0, 0.0, 0.0),
+      0.1,
+    );
+    assert_dist(&shape, [-0.8, 0.0, 0.0], -0.2);
+    assert_dist(&shape, [0.5, 0.0, 0.0], 0.5);
+  }
+
+  #[test]
+  fn rotations_agree() {
+    let bar = builder::cuboid(2.0, 0.5, 0.5);
+    let shapes = [
+      builder::rotate_z(bar.clone(), std::f32::consts::FRAC_PI_2),
+      builder::rotate_euler(
+        bar.clone(),
+        glam::EulerRot::XYZ,
+        0.0,
+        0.0,
+        std::f32::consts::FRAC_PI_2,
+      ),
+      builder::rotate(
+        bar,
+        glam::Quat::from_axis_angle(glam::Vec3::Z, std::f32::consts::FRAC_PI_2),
+      ),
+    ];
+    for shape in &shapes {
+      assert_dist(shape, [0.0, 1.5, 0.0], -0.5);
+      assert_dist(shape, [1.5, 0.0, 0.0], 1.0);
+    }
+  }
+
+  #[test]
+  fn twist_turns_cross_section() {
+    let slab = builder::cuboid(1.0, 2.0, 0.25);
+    let shape = builder::twist(slab.clone(), std::f64::consts::FRAC_PI_2);
+    // a quarter turn up, the slab lies along Z instead of X
+    assert_dist(&shape, [0.0, 1.0, 0.9], -0.1);
+    assert_dist(&slab, [0.0, 1.0, 0.9], 0.65);
+    assert_dist(&shape, [0.9, 0.0, 0.0], -0.1);
+  }
+
+  #[test]
+  fn bend_curves_y_axis_towards_x() {
+    let column = builder::cuboid(0.25, 2.0, 0.25);
+    let shape = builder::bend(column.clone(), 0.5);
+    let (sin, cos) = 0.5_f64.sin_cos();
+    assert!(eval(&shape, 0.5_f64.tan(), 1.0, 0.0) < 0.0);
+    assert!(eval(&column, 0.5_f64.tan(), 1.0, 0.0) > 0.0);
+    assert_dist(&shape, [0.2, 1.0, 0.0], (cos * 0.2 - sin).abs() - 0.25);
+  }
+
+  #[test]
+  fn taper_and_shear_move_cross_section() {
+    let shape = builder::taper(builder::cuboid(1.0, 1.0, 1.0), 0.5);
+    assert_dist(&shape, [1.2, 0.5, 0.0], 1.2 / 1.25 - 1.0);
+    assert_dist(&shape, [0.0, 0.5, 0.0], -0.5);
+
+    let shape = builder::shear(builder::sphere(1.0), 1.0, 0.0);
+    assert_dist(&shape, [1.0, 1.0, 0.0], 0.0);
+    assert_dist(&shape, [1.0, 0.5, 0.0], 0.5_f64.sqrt() - 1.0);
+  }
+
+  #[test]
+  fn profiles_are_exact() {
+    // profiles ignore z
+    let shape = builder::profile::circle(1.0);
+    assert_dist(&shape, [0.0, 0.0, 0.0], -1.0);
+    assert_dist(&shape, [2.0, 0.0, 5.0], 1.0);
+
+    let shape = builder::profile::rectangle(2.0, 1.0);
+    assert_dist(&shape, [0.0, 0.0, 0.0], -1.0);
+    assert_dist(&shape, [1.5, 0.5, 0.0], -0.5);
+    assert_dist(&shape, [3.0, 0.0, 0.0], 1.0);
+    assert_dist(&shape, [3.0, 2.0, 0.0], 2.0_f64.sqrt());
+
+    let shape = builder::profile::rounded_rectangle(2.0, 1.0, 0.5);
+    assert_dist(&shape, [3.0, 0.0, 0.0], 1.0);
+    assert_dist(&shape, [2.5, 1.5, 0.0], 2.0_f64.sqrt() - 0.5);
+  }
+
+  #[test]
+  fn polygons_are_signed_in_either_winding() {
+    // an L, whose notch is outside
+    let points = vec![
+      [0.0, 0.0],
+      [2.0, 0.0],
+      [2.0, 1.0],
+      [1.0, 1.0],
+      [1.0, 2.0],
+      [0.0, 2.0],
+    ];
+    let reversed = points.iter().rev().copied().collect();
+    for shape in [
+      builder::profile::polygon(points),
+      builder::profile::polygon(reversed),
+    ] {
+      assert_dist(&shape, [0.5, 0.5, 0.0], -0.5);
+      assert_dist(&shape, [1.5, 0.5, 0.0], -0.5);
+      assert_dist(&shape, [1.5, 1.5, 0.0], 0.5);
+      assert_dist(&shape, [3.0, 0.5, 0.0], 1.0);
+      // level with vertices
+      assert_dist(&shape, [0.5, 1.0, 0.0], -0.5);
+      assert_dist(&shape, [3.0, 1.0, 0.0], 1.0);
+      assert_dist(&shape, [-1.0, 1.0, 0.0], 1.0);
+    }
+
+    let shape = builder::profile::polygon(vec![[0.0, 0.0], [1.0, 0.0]]);
+    assert!(eval(&shape, 0.5, 0.0, 0.0) > 0.0);
+  }
+
+  #[test]
+  fn extrude_caps_profiles() {
+    let shape = builder::extrude(builder::profile::circle(1.0), 2.0);
+    assert_dist(&shape, [0.0, 0.0, 0.0], -1.0);
+    assert_dist(&shape, [0.0, 0.0, 0.5], -0.5);
+    assert_dist(&shape, [0.0, 0.0, -2.0], 1.0);
+    assert_dist(&shape, [3.0, 0.0, 0.0], 2.0);
+    // off the rim
+    assert_dist(&shape, [2.0, 0.0, 2.0], 2.0_f64.sqrt());
+  }
+
+  #[test]
+  fn revolve_sweeps_around_the_axis() {
+    let profile =
+      builder::translate(builder::profile::circle(0.5), 2.0, 0.0, 0.0);
+    let shape = builder::revolve(profile.clone(), [0.0, 1.0, 0.0]);
+    let torus = builder::torus(2.0, 0.5);
+    for point in [[2.0, 0.0, 0.0], [0.0, 0.5, -2.0], [1.0, 1.0, 1.0], [
+      0.0, 0.0, 0.0,
+    ]] {
+      let expected = eval(&torus, point[0], point[1], point[2]);
+      assert_dist(&shape, point, expected);
+    }
+
+    // the profile's y runs along the axis
+    let shape = builder::revolve(profile, [2.0, 0.0, 0.0]);
+    assert_dist(&shape, [0.0, 2.0, 0.0], -0.5);
+    assert_dist(&shape, [0.0, 0.0, -2.0], -0.5);
+    assert_dist(&shape, [1.0, 0.0, 2.0], 0.5);
+  }
+
+  #[test]
+  fn distance_grids_interpolate_linearly() {
+    // trilinear interpolation reproduces linear fields exactly
+    let field = |p: [f32; 3]| p[0] + 2.0 * p[1] - p[2];
+    let mut values = Vec::new();
+    for z in [-1.0, 0.0, 1.0] {
+      for y in [-1.0, 0.0, 1.0] {
+        for x in [-1.0, 0.0, 1.0] {
+          values.push(field([x, y, z]));
+        }
+      }
+    }
+    let shape =
+      builder::distance_grid([3, 3, 3], [1.0, 1.0, 1.0], values).unwrap();
+    assert_dist(&shape, [0.5, -0.25, 0.25], -0.25);
+    assert_dist(&shape, [1.0, 1.0, -1.0], 4.0);
+    // outside, the nearest boundary value grows with the distance to it
+    assert_dist(&shape, [2.0, 0.0, 0.0], 2.0);
+  }
+
+  #[test]
+  fn heightmaps_measure_height_above_the_terrain() {
+    let shape =
+      builder::heightmap([2, 2], [1.0, 1.0], vec![0.0, 1.0, 2.0, 3.0]).unwrap();
+    // the terrain is the plane 1.5 + x / 2 + z
+    assert_dist(&shape, [0.0, 5.0, 0.0], 3.5);
+    assert_dist(&shape, [0.5, 0.0, -0.5], -1.25);
+    // the edges carry on past the grid
+    assert_dist(&shape, [3.0, 0.0, 0.0], -2.0);
+  }
+
+  #[test]
+  fn grid_bricks_join_seamlessly() {
+    // 17 samples span three bricks along X. Samples alternate between 0 and
+    // 10, so a wrong brick winning anywhere would show
+    let heights = (0..17).map(|i| (i % 2 * 10) as f32).collect();
+    let shape = builder::heightmap([17, 1], [4.0, 1.0], heights).unwrap();
+    // samples are half a unit apart
+    assert_dist(&shape, [-4.0, 0.0, 0.0], 0.0);
+    assert_dist(&shape, [-0.5, 0.0, 0.0], -10.0);
+    assert_dist(&shape, [0.0, 0.0, 0.0], 0.0);
+    assert_dist(&shape, [1.75, 0.0, 0.0], -5.0);
+    assert_dist(&shape, [3.75, 0.0, 0.0], -5.0);
+
+    // bilinear interpolation reproduces linear heights exactly
+    let mut heights = Vec::new();
+    for j in 0..12 {
+      for i in 0..20 {
+        heights.push(0.5 * i as f32 + 0.25 * j as f32);
+      }
+    }
+    let shape = builder::heightmap([20, 12], [9.5, 5.5], heights).unwrap();
+    // sample indices are the coordinates plus the half-extents
+    assert_dist(&shape, [2.85, 0.0, -4.95], -6.3125);
+    assert_dist(&shape, [9.5, 2.0, 5.5], -10.25);
+  }
+
+  #[test]
+  fn grids_hash_their_samples() {
+    let hash = |heights| {
+      let shape = builder::heightmap([2, 2], [1.0, 1.0], heights).unwrap();
+      SharedShape::new(shape).content_hash()
+    };
+    assert_eq!(
+      hash(vec![0.0, 1.0, 2.0, 3.0]),
+      hash(vec![0.0, 1.0, 2.0, 3.0])
+    );
+    assert_ne!(
+      hash(vec![0.0, 1.0, 2.0, 3.0]),
+      hash(vec![0.0, 1.0, 2.0, 3.5])
+    );
+  }
+}
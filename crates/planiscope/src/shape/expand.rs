@@ -0,0 +1,481 @@
+//! Compounds rewritten as trees of core [`Shape`]s.
+//!
+//! Each expansion builds the same field as the compound's function in
+//! [`nso`](crate::nso), operation for operation, but out of shapes rather
+//! than fidget nodes, so that the rest of the crate can see inside it. The
+//! compound's children are reused as they are, so shared subtrees stay
+//! shared.
+
+use super::{
+  builder::{
+    abs, add, cos, div, exp, max, min, mul, neg, remap, sin, sqrt, square, sub,
+    x, y, z,
+  },
+  compound::Compound,
+  Shape, SharedShape,
+};
+use crate::nso::repetition::INFINITE_REPEAT_DOUBLINGS;
+
+/// The slope of the steep steps that stand in for comparisons, as in
+/// [`nso_steep_step`](crate::nso::other::nso_steep_step).
+const STEEPNESS: f64 = 1.0e6;
+
+impl Compound {
+  /// Rewrites the compound as the core shapes its node is built from, with
+  /// the same field. Materials are dropped along the way.
+  ///
+  /// Returns `None` for the compounds that are only practical to build as
+  /// nodes: polar repetition, splines, noise, polygons and sampled grids,
+  /// which are made of generated constants or many steps per point.
+  pub fn expand(&self) -> Option<Shape> {
+    let expanded = match self {
+      Compound::Sphere { radius } => {
+        sub(length([x(), y(), z()]), child(radius))
+      }
+      Compound::Cylinder { height, radius } => {
+        let half = div(child(height), 2.0);
+        let v1 = sub(child(radius), length([x(), z()]));
+        let v2 = sub(half, abs(y()));
+        neg(min(v1, v2))
+      }
+      Compound::Cuboid { x: l, y: w, z: h } => {
+        let q_x = sub(abs(x()), child(l));
+        let q_y = sub(abs(y()), child(w));
+        let q_z = sub(abs(z()), child(h));
+        max(max(q_x, q_y), q_z)
+      }
+      Compound::RoundedBox {
+        x: l,
+        y: w,
+        z: h,
+        radius,
+      } => {
+        let q = [(x(), l), (y(), w), (z(), h)].map(|(coord, extent)| {
+          sub(abs(coord), sub(child(extent), child(radius)))
+        });
+        let [q_x, q_y, q_z] = q.clone();
+        let outside = length(q.map(|q| max(q, 0.0)));
+        let inside = min(max(q_x, max(q_y, q_z)), 0.0);
+        sub(add(outside, inside), child(radius))
+      }
+      Compound::Torus { major, minor } => {
+        let q_x = sub(length([x(), z()]), child(major));
+        sub(length([q_x, y()]), child(minor))
+      }
+      Compound::Capsule { height, radius } => {
+        let half = div(child(height), 2.0);
+        let clamped = clamp(y(), neg(half.clone()), half);
+        let new_y = sub(y(), clamped);
+        sub(length([x(), new_y, z()]), child(radius))
+      }
+      Compound::Cone { height, radius } => {
+        let (height, radius) = (child(height), child(radius));
+        let w_x = length([x(), z()]);
+        let w_y = sub(y(), div(height.clone(), 2.0));
+        let q_x = radius.clone();
+        let q_y = neg(height.clone());
+
+        // distance to the slanted side
+        let w_dot_q =
+          dot([w_x.clone(), w_y.clone()], [q_x.clone(), q_y.clone()]);
+        let q_dot_q =
+          dot([q_x.clone(), q_y.clone()], [q_x.clone(), q_y.clone()]);
+        let t = min(max(div(w_dot_q, q_dot_q), 0.0), 1.0);
+        let a_x = sub(w_x.clone(), mul(q_x.clone(), t.clone()));
+        let a_y = sub(w_y.clone(), mul(q_y.clone(), t));
+
+        // distance to the base disk
+        let t = min(max(div(w_x.clone(), q_x.clone()), 0.0), 1.0);
+        let b_x = sub(w_x.clone(), mul(q_x, t));
+        let b_y = sub(w_y.clone(), q_y);
+
+        let a_dot_a = dot([a_x.clone(), a_y.clone()], [a_x, a_y]);
+        let b_dot_b = dot([b_x.clone(), b_y.clone()], [b_x, b_y]);
+        let d = sqrt(min(a_dot_a, b_dot_b));
+
+        let side = add(mul(w_x, height.clone()), mul(w_y.clone(), radius));
+        let base = neg(add(w_y, height));
+        mul(d, steep_sign(max(side, base)))
+      }
+      Compound::Plane {
+        normal_x,
+        normal_y,
+        normal_z,
+        offset,
+      } => {
+        let normal = [child(normal_x), child(normal_y), child(normal_z)];
+        let d = div(dot([x(), y(), z()], normal.clone()), length(normal));
+        sub(d, child(offset))
+      }
+      Compound::Ellipsoid {
+        x: r_x,
+        y: r_y,
+        z: r_z,
+      } => {
+        let radii = [child(r_x), child(r_y), child(r_z)];
+        let p_0 = [x(), y(), z()];
+        let p_0 = [0, 1, 2].map(|i| div(p_0[i].clone(), radii[i].clone()));
+        let p_1 = [0, 1, 2].map(|i| div(p_0[i].clone(), radii[i].clone()));
+        let k_0 = length(p_0);
+        let k_1 = max(length(p_1), 1.0e-9);
+        let [r_x, r_y, r_z] = radii;
+        let smallest = min(min(r_x.clone(), r_y.clone()), r_z.clone());
+        let largest = max(max(r_x, r_y), r_z);
+        let ratio = clamp(div(k_0.clone(), k_1), smallest, largest);
+        mul(sub(k_0, 1.0), ratio)
+      }
+      Compound::SmoothMinCubic { lhs, rhs, k } => {
+        smooth_min_cubic(child(lhs), child(rhs), child(k))
+      }
+      Compound::SmoothMaxCubic { lhs, rhs, k } => {
+        neg(smooth_min_cubic(neg(child(lhs)), neg(child(rhs)), child(k)))
+      }
+      Compound::SmoothMinQuadratic { lhs, rhs, k } => {
+        let (lhs, rhs, k) = (child(lhs), child(rhs), child(k));
+        let h = blend(lhs.clone(), rhs.clone(), k.clone());
+        let v = mul(mul(square(h), k), 0.25);
+        sub(min(lhs, rhs), v)
+      }
+      Compound::SmoothMinExponential { lhs, rhs, k } => {
+        let (lhs, rhs, k) = (child(lhs), child(rhs), child(k));
+        let v = neg(div(abs(sub(lhs.clone(), rhs.clone())), k.clone()));
+        let v = mul(ln_1p_unit(exp(v)), k);
+        sub(min(lhs, rhs), v)
+      }
+      Compound::ChamferMin { lhs, rhs, radius } => {
+        let (lhs, rhs) = (child(lhs), child(rhs));
+        let v = sub(add(lhs.clone(), rhs.clone()), child(radius));
+        let v = mul(v, std::f64::consts::FRAC_1_SQRT_2);
+        min(min(lhs, rhs), v)
+      }
+      Compound::RoundMin { lhs, rhs, radius } => {
+        let (lhs, rhs, radius) = (child(lhs), child(rhs), child(radius));
+        let u_x = max(sub(radius.clone(), lhs.clone()), 0.0);
+        let u_y = max(sub(radius.clone(), rhs.clone()), 0.0);
+        let v = max(min(lhs, rhs), radius);
+        sub(v, length([u_x, u_y]))
+      }
+      Compound::MatTransform { root, mat } => {
+        let mat = mat.inverse();
+        let [new_x, new_y, new_z] = [0, 1, 2].map(|i| {
+          let a = mul(mat.x_axis[i], x());
+          let b = mul(mat.y_axis[i], y());
+          let c = mul(mat.z_axis[i], z());
+          add(add(add(a, b), c), mat.w_axis[i])
+        });
+        remap(child(root), new_x, new_y, new_z)
+      }
+      Compound::Clamp {
+        root,
+        min: lower,
+        max: upper,
+      } => clamp(child(root), child(lower), child(upper)),
+      Compound::Map {
+        root,
+        in_min,
+        in_max,
+        out_min,
+        out_max,
+      } => {
+        let a = sub(child(root), child(in_min));
+        let b = sub(child(in_max), child(in_min));
+        let d = sub(child(out_max), child(out_min));
+        add(mul(div(a, b), d), child(out_min))
+      }
+      Compound::Repeat {
+        root,
+        spacing,
+        count,
+      } => {
+        let mut coords = [x(), y(), z()];
+        for i in 0..3 {
+          if count[i] > 1 {
+            let offset = staircase(coords[i].clone(), spacing[i], count[i]);
+            coords[i] = sub(coords[i].clone(), offset);
+          }
+        }
+        let [new_x, new_y, new_z] = coords;
+        remap(child(root), new_x, new_y, new_z)
+      }
+      Compound::RepeatInfinite { root, spacing } => {
+        let mut coords = [x(), y(), z()];
+        for i in 0..3 {
+          if spacing[i] > 0.0 {
+            coords[i] = fold(coords[i].clone(), spacing[i]);
+          }
+        }
+        let [new_x, new_y, new_z] = coords;
+        remap(child(root), new_x, new_y, new_z)
+      }
+      Compound::Mirror {
+        root,
+        normal,
+        offset,
+      } => {
+        let normal = glam::Vec3A::from_array(*normal).normalize_or_zero();
+        let d = add(
+          add(mul(x(), normal.x), mul(y(), normal.y)),
+          mul(z(), normal.z),
+        );
+        let d = sub(d, *offset);
+        // only points behind the plane get reflected
+        let behind = mul(min(d, 0.0), 2.0);
+        let [new_x, new_y, new_z] =
+          [(x(), normal.x), (y(), normal.y), (z(), normal.z)]
+            .map(|(coord, n)| sub(coord, mul(behind.clone(), n)));
+        remap(child(root), new_x, new_y, new_z)
+      }
+      Compound::Twist { root, rate } => {
+        let angle = mul(y(), child(rate));
+        let (cos, sin) = (cos(angle.clone()), sin(angle));
+        let new_x = add(mul(x(), cos.clone()), mul(z(), sin.clone()));
+        let new_z = sub(mul(z(), cos), mul(x(), sin));
+        remap(child(root), new_x, y(), new_z)
+      }
+      Compound::Bend { root, rate } => {
+        let angle = mul(y(), child(rate));
+        let (cos, sin) = (cos(angle.clone()), sin(angle));
+        let new_x = sub(mul(x(), cos.clone()), mul(y(), sin.clone()));
+        let new_y = add(mul(x(), sin), mul(y(), cos));
+        remap(child(root), new_x, new_y, z())
+      }
+      Compound::Taper { root, rate } => {
+        let factor = add(mul(y(), child(rate)), 1.0);
+        remap(child(root), div(x(), factor.clone()), y(), div(z(), factor))
+      }
+      Compound::Shear {
+        root,
+        x_rate,
+        z_rate,
+      } => {
+        let new_x = sub(x(), mul(y(), child(x_rate)));
+        let new_z = sub(z(), mul(y(), child(z_rate)));
+        remap(child(root), new_x, y(), new_z)
+      }
+      Compound::Material { root, .. } => child(root),
+      Compound::Offset { root, distance } => sub(child(root), child(distance)),
+      Compound::Shell { root, thickness } => {
+        sub(abs(child(root)), div(child(thickness), 2.0))
+      }
+      Compound::Onion {
+        root,
+        thickness,
+        count,
+      } => {
+        let mut shape = child(root);
+        for pass in 0..*count {
+          let half =
+            mul(child(thickness), 2.0_f32.powi((count - pass) as i32 - 2));
+          shape = sub(abs(shape), half);
+        }
+        shape
+      }
+      Compound::Circle { radius } => sub(length([x(), y()]), child(radius)),
+      Compound::Rectangle { x: w, y: h } => rectangle(child(w), child(h)),
+      Compound::RoundedRectangle { x: w, y: h, radius } => {
+        let radius = child(radius);
+        let rectangle = rectangle(
+          sub(child(w), radius.clone()),
+          sub(child(h), radius.clone()),
+        );
+        sub(rectangle, radius)
+      }
+      Compound::Extrude { profile, depth } => {
+        let profile = child(profile);
+        let cap = sub(abs(z()), div(child(depth), 2.0));
+        let outside =
+          length([max(profile.clone(), 0.0), max(cap.clone(), 0.0)]);
+        let inside = min(max(profile, cap), 0.0);
+        add(outside, inside)
+      }
+      Compound::Revolve { profile, axis } => {
+        let axis = glam::Vec3A::from_array(*axis)
+          .try_normalize()
+          .unwrap_or(glam::Vec3A::Y);
+        let along =
+          add(add(mul(x(), axis.x), mul(y(), axis.y)), mul(z(), axis.z));
+        let radial = length(
+          [(x(), axis.x), (y(), axis.y), (z(), axis.z)]
+            .map(|(coord, a)| sub(coord, mul(along.clone(), a))),
+        );
+        remap(child(profile), radial, along, 0.0)
+      }
+      Compound::PolarRepeat { .. }
+      | Compound::CatmullRomSpline { .. }
+      | Compound::Noise { .. }
+      | Compound::Fbm { .. }
+      | Compound::Polygon { .. }
+      | Compound::DistanceGrid { .. }
+      | Compound::Heightmap { .. } => return None,
+    };
+    Some(expanded)
+  }
+}
+
+/// The child's shape. Cloning a shape only copies its top node, so the
+/// child's own subtrees stay shared.
+fn child(shape: &SharedShape) -> Shape { shape.as_ref().clone() }
+
+/// The length of a vector, as
+/// [`nso_magnitude_3d`](crate::nso::vectors::nso_magnitude_3d) builds it.
+fn length<const N: usize>(v: [Shape; N]) -> Shape {
+  let mut squares = v.into_iter().map(square);
+  let first = squares.next().expect("vectors have components");
+  sqrt(squares.fold(first, add))
+}
+
+fn dot<const N: usize>(a: [Shape; N], b: [Shape; N]) -> Shape {
+  let mut products = a.into_iter().zip(b).map(|(a, b)| mul(a, b));
+  let first = products.next().expect("vectors have components");
+  products.fold(first, add)
+}
+
+fn clamp(shape: Shape, lower: Shape, upper: Shape) -> Shape {
+  max(min(shape, upper), lower)
+}
+
+fn steep_step(shape: Shape) -> Shape {
+  max(min(mul(shape, STEEPNESS), 1.0), 0.0)
+}
+
+fn steep_sign(shape: Shape) -> Shape {
+  max(min(mul(shape, STEEPNESS), 1.0), -1.0)
+}
+
+/// The blend weight of the polynomial smooth minimums, one at the seam and
+/// zero `k` away from it.
+fn blend(lhs: Shape, rhs: Shape, k: Shape) -> Shape {
+  div(max(sub(k.clone(), abs(sub(lhs, rhs))), 0.0), k)
+}
+
+fn smooth_min_cubic(lhs: Shape, rhs: Shape, k: Shape) -> Shape {
+  let h = blend(lhs.clone(), rhs.clone(), k.clone());
+  let v = mul(mul(mul(square(h.clone()), h), k), 1.0 / 6.0);
+  sub(min(lhs, rhs), v)
+}
+
+/// `ln(1 + u)` for `u` in `[0, 1]`, by the same series as the exponential
+/// smooth minimum's node.
+fn ln_1p_unit(u: Shape) -> Shape {
+  let s = div(u.clone(), add(u, 2.0));
+  let s2 = square(s.clone());
+  let mut sum = Shape::Constant(1.0 / 13.0);
+  for n in [11.0, 9.0, 7.0, 5.0, 3.0, 1.0] {
+    sum = add(mul(sum, s2.clone()), 1.0 / n);
+  }
+  mul(mul(sum, s), 2.0)
+}
+
+fn rectangle(width: Shape, height: Shape) -> Shape {
+  let q_x = sub(abs(x()), width);
+  let q_y = sub(abs(y()), height);
+  let outside = length([max(q_x.clone(), 0.0), max(q_y.clone(), 0.0)]);
+  let inside = min(max(q_x, q_y), 0.0);
+  add(outside, inside)
+}
+
+/// The offset of the nearest of `count` instances along `coord`, as
+/// [`Compound::Repeat`]'s node builds it.
+fn staircase(coord: Shape, spacing: f32, count: u32) -> Shape {
+  let first = -(count as f32 - 1.0) / 2.0 * spacing;
+  let mut offset = Shape::from(first);
+  for k in 1..count {
+    let boundary = (k as f32 - count as f32 / 2.0) * spacing;
+    let step = steep_step(sub(coord.clone(), boundary));
+    offset = add(offset, mul(step, spacing));
+  }
+  offset
+}
+
+/// `coord` folded into the cell of width `spacing` around the nearest
+/// multiple of it, as [`Compound::RepeatInfinite`]'s node builds it.
+fn fold(coord: Shape, spacing: f32) -> Shape {
+  let sign = steep_sign(coord.clone());
+  let mut shifted = add(abs(coord), spacing / 2.0);
+  for k in (0..INFINITE_REPEAT_DOUBLINGS).rev() {
+    let width = spacing * 2.0_f32.powi(k);
+    let step = steep_step(sub(shifted.clone(), width));
+    shifted = sub(shifted, mul(step, width));
+  }
+  mul(sub(shifted, spacing / 2.0), sign)
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::Vec3A;
+
+  use super::*;
+  use crate::shape::builder;
+
+  #[test]
+  fn expansions_match_their_compounds() {
+    let root =
+      builder::translate(builder::cuboid(0.4, 0.3, 0.2), 0.1, 0.0, 0.0);
+    let shapes = [
+      builder::sphere(1.0),
+      builder::cylinder(0.5, 2.0),
+      builder::cuboid(1.0, 0.5, 0.25),
+      builder::rounded_box(1.0, 0.5, 0.75, 0.1),
+      builder::torus(1.0, 0.25),
+      builder::capsule(1.0, 0.5),
+      builder::cone(1.5, 0.5),
+      builder::plane([1.0, 2.0, -0.5], 0.25),
+      builder::ellipsoid(1.0, 0.5, 0.75),
+      builder::smooth_min_cubic(root.clone(), builder::sphere(0.5), 0.3),
+      builder::smooth_max_cubic(root.clone(), builder::sphere(0.5), 0.3),
+      builder::smooth_min_quadratic(root.clone(), builder::sphere(0.5), 0.3),
+      builder::smooth_min_exponential(root.clone(), builder::sphere(0.5), 0.3),
+      builder::chamfer_min(root.clone(), builder::sphere(0.5), 0.2),
+      builder::round_min(root.clone(), builder::sphere(0.5), 0.2),
+      builder::transform(
+        root.clone(),
+        glam::Mat4::from_rotation_translation(
+          glam::Quat::from_rotation_y(0.7),
+          glam::Vec3::new(0.2, -0.1, 0.3),
+        ),
+      ),
+      builder::clamp(root.clone(), -0.1, 0.2),
+      builder::map(root.clone(), -1.0, 1.0, 0.0, 3.0),
+      builder::repeat(root.clone(), [1.0, 0.0, 0.5], [3, 1, 2]),
+      builder::repeat_infinite(root.clone(), [1.5, 0.0, 0.0]),
+      builder::mirror(root.clone(), [1.0, 1.0, 0.0], 0.1),
+      builder::twist(root.clone(), 0.8),
+      builder::bend(root.clone(), 0.5),
+      builder::taper(root.clone(), 0.3),
+      builder::shear(root.clone(), 0.2, -0.4),
+      builder::material(root.clone(), 3),
+      builder::offset(root.clone(), 0.1),
+      builder::shell(root.clone(), 0.05),
+      builder::onion(root.clone(), 0.05, 3),
+      builder::profile::circle(0.5),
+      builder::profile::rectangle(0.5, 0.25),
+      builder::profile::rounded_rectangle(0.5, 0.25, 0.1),
+      builder::extrude(builder::profile::circle(0.5), 1.0),
+      builder::revolve(
+        builder::translate(builder::profile::circle(0.2), 0.6, 0.0, 0.0),
+        [0.0, 1.0, 1.0],
+      ),
+    ];
+    let points = [
+      Vec3A::new(0.3, -0.2, 0.1),
+      Vec3A::new(-1.1, 0.7, 0.4),
+      Vec3A::new(0.05, 1.3, -0.8),
+      Vec3A::new(2.2, -0.4, 1.7),
+    ];
+    for shape in shapes {
+      let Shape::Extra(compound) = &shape else {
+        panic!("{shape:?} isn't a compound");
+      };
+      let expanded = compound.expand().unwrap();
+      for point in points {
+        let expected = shape.eval(point).unwrap();
+        let actual = expanded.eval(point).unwrap();
+        assert!(
+          (actual - expected).abs() <= 1e-5 * expected.abs().max(1.0),
+          "{} at {point}: expected {expected}, got {actual}",
+          compound.name()
+        );
+      }
+    }
+  }
+}
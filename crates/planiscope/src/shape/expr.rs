@@ -0,0 +1,1210 @@
+//! Conversion between [`Shape`]s and rhai-style expression strings.
+//!
+//! The grammar is the subset of rhai that fidget's engine understands: the
+//! variables `x`, `y` and `z`, number literals, `+ - * /`, unary negation,
+//! parentheses, and the functions `min`, `max`, `neg`, `exp`, `sin`, `cos`,
+//! `recip`, `abs`, `sqrt` and `square`. On top of that, any other identifier
+//! is a [`Shape::Var`], `remap(root, x, y, z)` is a remap, and every
+//! [`Compound`] variant can be called by its snake-case name, with the variant'
+//! s fields as arguments in declaration order. Non-shape fields are written as
+//! number literals or `[..]` lists.
+
+use std::{fmt, str::FromStr};
+
+use super::{compound::Compound, Shape, SharedShape};
+
+/// An error encountered while parsing an expression into a [`Shape`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+  /// The byte offset in the expression at which the error occurred.
+  pub position: usize,
+  pub message:  String,
+}
+
+impl fmt::Display for ParseError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} at position {}", self.message, self.position)
+  }
+}
+
+impl std::error::Error for ParseError {}
+
+fn error<T>(
+  position: usize,
+  message: impl Into<String>,
+) -> Result<T, ParseError> {
+  Err(ParseError {
+    position,
+    message: message.into(),
+  })
+}
+
+impl Shape {
+  /// Parses an expression into a native shape tree.
+  pub fn parse(expr: &str) -> Result<Shape, ParseError> {
+    let mut parser = Parser {
+      tokens: tokenize(expr)?,
+      index:  0,
+      end:    expr.len(),
+    };
+    let shape = parser.expr()?;
+    match parser.peek() {
+      None => Ok(shape),
+      Some((token, position)) => {
+        error(position, format!("unexpected `{}`", token))
+      }
+    }
+  }
+}
+
+impl FromStr for Shape {
+  type Err = ParseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> { Shape::parse(s) }
+}
+
+// printing
+
+/// Operator precedence, for deciding where parentheses are needed.
+fn precedence(shape: &Shape) -> u8 {
+  match shape {
+    Shape::Add(..) | Shape::Sub(..) => 1,
+    Shape::Mul(..) | Shape::Div(..) => 2,
+    Shape::Neg(_) => 3,
+    Shape::Constant(c) if c.is_sign_negative() => 3,
+    _ => 4,
+  }
+}
+
+fn write_operand(
+  f: &mut fmt::Formatter<'_>,
+  shape: &Shape,
+  parenthesize: bool,
+) -> fmt::Result {
+  if parenthesize {
+    write!(f, "({shape})")
+  } else {
+    write!(f, "{shape}")
+  }
+}
+
+fn write_binary(
+  f: &mut fmt::Formatter<'_>,
+  lhs: &Shape,
+  op: &str,
+  rhs: &Shape,
+  precedence_of_op: u8,
+) -> fmt::Result {
+  // operators are left-associative, so an operand of equal precedence on the
+  // right needs parentheses to keep the tree intact
+  write_operand(f, lhs, precedence(lhs) < precedence_of_op)?;
+  write!(f, " {op} ")?;
+  write_operand(f, rhs, precedence(rhs) <= precedence_of_op)
+}
+
+fn write_call(
+  f: &mut fmt::Formatter<'_>,
+  name: &str,
+  args: &[&Shape],
+) -> fmt::Result {
+  write!(f, "{name}(")?;
+  for (i, arg) in args.iter().enumerate() {
+    if i > 0 {
+      write!(f, ", ")?;
+    }
+    write!(f, "{arg}")?;
+  }
+  write!(f, ")")
+}
+
+/// Writes the shape as an expression that [`Shape::parse`] turns back into
+/// the same tree. Opaque [`Shape::Expression`]s are written out verbatim, so
+/// they come back as native trees.
+impl fmt::Display for Shape {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Shape::Expression { expr } => write!(f, "({expr})"),
+      Shape::XNode => write!(f, "x"),
+      Shape::YNode => write!(f, "y"),
+      Shape::ZNode => write!(f, "z"),
+      Shape::Constant(c) => write!(f, "{c:?}"),
+      Shape::Add(lhs, rhs) => write_binary(f, lhs, "+", rhs, 1),
+      Shape::Sub(lhs, rhs) => write_binary(f, lhs, "-", rhs, 1),
+      Shape::Mul(lhs, rhs) => write_binary(f, lhs, "*", rhs, 2),
+      Shape::Div(lhs, rhs) => write_binary(f, lhs, "/", rhs, 2),
+      Shape::Min(lhs, rhs) => write_call(f, "min", &[lhs, rhs]),
+      Shape::Max(lhs, rhs) => write_call(f, "max", &[lhs, rhs]),
+      Shape::Neg(a) => {
+        // `-1.0` would parse as a negative constant rather than a negation
+        write!(f, "-")?;
+        write_operand(
+          f,
+          a,
+          precedence(a) <= 3 || matches!(**a, Shape::Constant(_)),
+        )
+      }
+      Shape::Exp(a) => write_call(f, "exp", &[a]),
+      Shape::Sin(a) => write_call(f, "sin", &[a]),
+      Shape::Cos(a) => write_call(f, "cos", &[a]),
+      Shape::Recip(a) => write_call(f, "recip", &[a]),
+      Shape::Abs(a) => write_call(f, "abs", &[a]),
+      Shape::Sqrt(a) => write_call(f, "sqrt", &[a]),
+      Shape::Square(a) => write_call(f, "square", &[a]),
+      Shape::Remap {
+        root,
+        new_x,
+        new_y,
+        new_z,
+      } => write_call(f, "remap", &[root, new_x, new_y, new_z]),
+      Shape::Extra(compound) => {
+        let (name, args) = compound_args(compound);
+        write!(f, "{name}({})", args.join(", "))
+      }
+      Shape::Var(name) => write!(f, "{name}"),
+    }
+  }
+}
+
+/// Returns the call name of a compound and its printed arguments.
+fn compound_args(compound: &Compound) -> (&'static str, Vec<String>) {
+  match compound {
+    Compound::Sphere { radius } => ("sphere", vec![radius.to_string()]),
+    Compound::Cylinder { height, radius } => {
+      ("cylinder", vec![height.to_string(), radius.to_string()])
+    }
+    Compound::Cuboid { x, y, z } => {
+      ("cuboid", vec![x.to_string(), y.to_string(), z.to_string()])
+    }
+    Compound::RoundedBox { x, y, z, radius } => ("rounded_box", vec![
+      x.to_string(),
+      y.to_string(),
+      z.to_string(),
+      radius.to_string(),
+    ]),
+    Compound::Torus { major, minor } => {
+      ("torus", vec![major.to_string(), minor.to_string()])
+    }
+    Compound::Capsule { height, radius } => {
+      ("capsule", vec![height.to_string(), radius.to_string()])
+    }
+    Compound::Cone { height, radius } => {
+      ("cone", vec![height.to_string(), radius.to_string()])
+    }
+    Compound::Plane {
+      normal_x,
+      normal_y,
+      normal_z,
+      offset,
+    } => ("plane", vec![
+      normal_x.to_string(),
+      normal_y.to_string(),
+      normal_z.to_string(),
+      offset.to_string(),
+    ]),
+    Compound::Ellipsoid { x, y, z } => ("ellipsoid", vec![
+      x.to_string(),
+      y.to_string(),
+      z.to_string(),
+    ]),
+    Compound::SmoothMinCubic { lhs, rhs, k } => ("smooth_min_cubic", vec![
+      lhs.to_string(),
+      rhs.to_string(),
+      k.to_string(),
+    ]),
+    Compound::SmoothMaxCubic { lhs, rhs, k } => ("smooth_max_cubic", vec![
+      lhs.to_string(),
+      rhs.to_string(),
+      k.to_string(),
+    ]),
+    Compound::SmoothMinQuadratic { lhs, rhs, k } => {
+      ("smooth_min_quadratic", vec![
+        lhs.to_string(),
+        rhs.to_string(),
+        k.to_string(),
+      ])
+    }
+    Compound::SmoothMinExponential { lhs, rhs, k } => {
+      ("smooth_min_exponential", vec![
+        lhs.to_string(),
+        rhs.to_string(),
+        k.to_string(),
+      ])
+    }
+    Compound::ChamferMin { lhs, rhs, radius } => ("chamfer_min", vec![
+      lhs.to_string(),
+      rhs.to_string(),
+      radius.to_string(),
+    ]),
+    Compound::RoundMin { lhs, rhs, radius } => ("round_min", vec![
+      lhs.to_string(),
+      rhs.to_string(),
+      radius.to_string(),
+    ]),
+    Compound::MatTransform { root, mat } => ("mat_transform", vec![
+      root.to_string(),
+      format!("{:?}", mat.to_cols_array()),
+    ]),
+    Compound::Clamp { root, min, max } => ("clamp", vec![
+      root.to_string(),
+      min.to_string(),
+      max.to_string(),
+    ]),
+    Compound::Map {
+      root,
+      in_min,
+      in_max,
+      out_min,
+      out_max,
+    } => ("map", vec![
+      root.to_string(),
+      in_min.to_string(),
+      in_max.to_string(),
+      out_min.to_string(),
+      out_max.to_string(),
+    ]),
+    Compound::Repeat {
+      root,
+      spacing,
+      count,
+    } => ("repeat", vec![
+      root.to_string(),
+      format!("{:?}", spacing),
+      format!("{:?}", count),
+    ]),
+    Compound::RepeatInfinite { root, spacing } => ("repeat_infinite", vec![
+      root.to_string(),
+      format!("{:?}", spacing),
+    ]),
+    Compound::Mirror {
+      root,
+      normal,
+      offset,
+    } => ("mirror", vec![
+      root.to_string(),
+      format!("{:?}", normal),
+      format!("{:?}", offset),
+    ]),
+    Compound::PolarRepeat { root, count } => ("polar_repeat", vec![
+      root.to_string(),
+      format!("{:?}", count),
+    ]),
+    Compound::CatmullRomSpline {
+      root,
+      points,
+      tension,
+    } => ("catmull_rom_spline", vec![
+      root.to_string(),
+      format!("{:?}", points),
+      format!("{:?}", tension),
+    ]),
+    Compound::Twist { root, rate } => {
+      ("twist", vec![root.to_string(), rate.to_string()])
+    }
+    Compound::Bend { root, rate } => {
+      ("bend", vec![root.to_string(), rate.to_string()])
+    }
+    Compound::Taper { root, rate } => {
+      ("taper", vec![root.to_string(), rate.to_string()])
+    }
+    Compound::Shear {
+      root,
+      x_rate,
+      z_rate,
+    } => ("shear", vec![
+      root.to_string(),
+      x_rate.to_string(),
+      z_rate.to_string(),
+    ]),
+    Compound::Noise { seed, frequency } => {
+      ("noise", vec![format!("{:?}", seed), frequency.to_string()])
+    }
+    Compound::Fbm {
+      seed,
+      octaves,
+      frequency,
+      lacunarity,
+      gain,
+    } => ("fbm", vec![
+      format!("{:?}", seed),
+      format!("{:?}", octaves),
+      frequency.to_string(),
+      lacunarity.to_string(),
+      gain.to_string(),
+    ]),
+    Compound::Material { root, id } => {
+      ("material", vec![root.to_string(), format!("{:?}", id)])
+    }
+    Compound::Offset { root, distance } => {
+      ("offset", vec![root.to_string(), distance.to_string()])
+    }
+    Compound::Shell { root, thickness } => {
+      ("shell", vec![root.to_string(), thickness.to_string()])
+    }
+    Compound::Onion {
+      root,
+      thickness,
+      count,
+    } => ("onion", vec![
+      root.to_string(),
+      thickness.to_string(),
+      format!("{:?}", count),
+    ]),
+    Compound::Circle { radius } => ("circle", vec![radius.to_string()]),
+    Compound::Rectangle { x, y } => {
+      ("rectangle", vec![x.to_string(), y.to_string()])
+    }
+    Compound::RoundedRectangle { x, y, radius } => ("rounded_rectangle", vec![
+      x.to_string(),
+      y.to_string(),
+      radius.to_string(),
+    ]),
+    Compound::Polygon { points } => ("polygon", vec![format!("{:?}", points)]),
+    Compound::Extrude { profile, depth } => {
+      ("extrude", vec![profile.to_string(), depth.to_string()])
+    }
+    Compound::Revolve { profile, axis } => {
+      ("revolve", vec![profile.to_string(), format!("{:?}", axis)])
+    }
+    Compound::DistanceGrid {
+      size,
+      extent,
+      values,
+    } => ("distance_grid", vec![
+      format!("{:?}", size),
+      format!("{:?}", extent),
+      format!("{:?}", values),
+    ]),
+    Compound::Heightmap {
+      size,
+      extent,
+      heights,
+    } => ("heightmap", vec![
+      format!("{:?}", size),
+      format!("{:?}", extent),
+      format!("{:?}", heights),
+    ]),
+  }
+}
+
+// tokenizing
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+  Number(f64),
+  Ident(String),
+  Symbol(char),
+}
+
+impl fmt::Display for Token {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Token::Number(n) => write!(f, "{n:?}"),
+      Token::Ident(ident) => write!(f, "{ident}"),
+      Token::Symbol(c) => write!(f, "{c}"),
+    }
+  }
+}
+
+fn tokenize(expr: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+  let mut tokens = Vec::new();
+  let mut chars = expr.char_indices().peekable();
+  while let Some(&(start, c)) = chars.peek() {
+    if c.is_whitespace() {
+      chars.next();
+    } else if c.is_ascii_digit() || c == '.' {
+      let mut end = start;
+      let mut prev = c;
+      while let Some(&(i, c)) = chars.peek() {
+        let exponent_sign = (c == '+' || c == '-') && matches!(prev, 'e' | 'E');
+        if !(c.is_ascii_alphanumeric() || c == '.' || c == '_' || exponent_sign)
+        {
+          break;
+        }
+        end = i + c.len_utf8();
+        prev = c;
+        chars.next();
+      }
+      let literal = expr[start..end].replace('_', "");
+      match literal.parse::<f64>() {
+        Ok(n) => tokens.push((Token::Number(n), start)),
+        Err(_) => {
+          return error(
+            start,
+            format!("invalid number `{}`", &expr[start..end]),
+          )
+        }
+      }
+    } else if c.is_alphabetic() || c == '_' {
+      let mut end = start;
+      while let Some(&(i, c)) = chars.peek() {
+        if !(c.is_alphanumeric() || c == '_') {
+          break;
+        }
+        end = i + c.len_utf8();
+        chars.next();
+      }
+      tokens.push((Token::Ident(expr[start..end].to_string()), start));
+    } else if "+-*/(),[]".contains(c) {
+      tokens.push((Token::Symbol(c), start));
+      chars.next();
+    } else {
+      return error(start, format!("unexpected character `{c}`"));
+    }
+  }
+  Ok(tokens)
+}
+
+// parsing
+
+/// A function argument, which may be a list literal for non-shape parameters.
+enum Arg {
+  Shape(Shape, usize),
+  List(Vec<Arg>, usize),
+}
+
+impl Arg {
+  fn position(&self) -> usize {
+    match self {
+      Arg::Shape(_, position) | Arg::List(_, position) => *position,
+    }
+  }
+
+  fn shape(self) -> Result<SharedShape, ParseError> {
+    match self {
+      Arg::Shape(shape, _) => Ok(SharedShape::new(shape)),
+      Arg::List(_, position) => {
+        error(position, "expected a shape, found a list")
+      }
+    }
+  }
+
+  fn f32(self) -> Result<f32, ParseError> {
+    let position = self.position();
+    match self.shape()?.simplify() {
+      Shape::Constant(c) => Ok(c as f32),
+      _ => error(position, "expected a constant number"),
+    }
+  }
+
+  fn u32(self) -> Result<u32, ParseError> {
+    let position = self.position();
+    let value = self.f32()?;
+    if value < 0.0 || value.fract() != 0.0 {
+      return error(position, "expected a non-negative integer");
+    }
+    Ok(value as u32)
+  }
+
+  fn list(self) -> Result<Vec<Arg>, ParseError> {
+    match self {
+      Arg::List(items, _) => Ok(items),
+      Arg::Shape(_, position) => error(position, "expected a list"),
+    }
+  }
+
+  fn array<const N: usize>(self) -> Result<[Arg; N], ParseError> {
+    let position = self.position();
+    self
+      .list()?
+      .try_into()
+      .or_else(|_| error(position, format!("expected a list of {N} items")))
+  }
+
+  fn f32s(self) -> Result<Vec<f32>, ParseError> {
+    self.list()?.into_iter().map(Arg::f32).collect()
+  }
+
+  fn u32_pair(self) -> Result<[u32; 2], ParseError> {
+    let [a, b] = self.array()?;
+    Ok([a.u32()?, b.u32()?])
+  }
+
+  fn f32_pair(self) -> Result<[f32; 2], ParseError> {
+    let [a, b] = self.array()?;
+    Ok([a.f32()?, b.f32()?])
+  }
+
+  fn f32_pairs(self) -> Result<Vec<[f32; 2]>, ParseError> {
+    self.list()?.into_iter().map(Arg::f32_pair).collect()
+  }
+
+  fn f32_triplet(self) -> Result<[f32; 3], ParseError> {
+    let [a, b, c] = self.array()?;
+    Ok([a.f32()?, b.f32()?, c.f32()?])
+  }
+
+  fn u32_triplet(self) -> Result<[u32; 3], ParseError> {
+    let [a, b, c] = self.array()?;
+    Ok([a.u32()?, b.u32()?, c.u32()?])
+  }
+
+  fn f32_triplets(self) -> Result<Vec<[f32; 3]>, ParseError> {
+    self.list()?.into_iter().map(Arg::f32_triplet).collect()
+  }
+
+  fn mat4(self) -> Result<glam::Mat4, ParseError> {
+    let values = self.array::<16>()?;
+    let mut cols = [0.0; 16];
+    for (col, value) in cols.iter_mut().zip(values) {
+      *col = value.f32()?;
+    }
+    Ok(glam::Mat4::from_cols_array(&cols))
+  }
+}
+
+/// Splits the arguments of a call to `name` into exactly `N` arguments.
+fn take<const N: usize>(
+  name: &str,
+  args: Vec<Arg>,
+  position: usize,
+) -> Result<[Arg; N], ParseError> {
+  let count = args.len();
+  args.try_into().or_else(|_| {
+    error(
+      position,
+      format!("`{name}` takes {N} arguments but {count} were given"),
+    )
+  })
+}
+
+/// Builds the compound called `name`, or returns `None` if there isn't one.
+fn parse_compound(
+  name: &str,
+  args: Vec<Arg>,
+  position: usize,
+) -> Option<Result<Compound, ParseError>> {
+  let compound = match name {
+    "sphere" => take(name, args, position).and_then(|[radius]| {
+      Ok(Compound::Sphere {
+        radius: radius.shape()?,
+      })
+    }),
+    "cylinder" => take(name, args, position).and_then(|[height, radius]| {
+      Ok(Compound::Cylinder {
+        height: height.shape()?,
+        radius: radius.shape()?,
+      })
+    }),
+    "cuboid" => take(name, args, position).and_then(|[x, y, z]| {
+      Ok(Compound::Cuboid {
+        x: x.shape()?,
+        y: y.shape()?,
+        z: z.shape()?,
+      })
+    }),
+    "rounded_box" => take(name, args, position).and_then(|[x, y, z, radius]| {
+      Ok(Compound::RoundedBox {
+        x:      x.shape()?,
+        y:      y.shape()?,
+        z:      z.shape()?,
+        radius: radius.shape()?,
+      })
+    }),
+    "torus" => take(name, args, position).and_then(|[major, minor]| {
+      Ok(Compound::Torus {
+        major: major.shape()?,
+        minor: minor.shape()?,
+      })
+    }),
+    "capsule" => take(name, args, position).and_then(|[height, radius]| {
+      Ok(Compound::Capsule {
+        height: height.shape()?,
+        radius: radius.shape()?,
+      })
+    }),
+    "cone" => take(name, args, position).and_then(|[height, radius]| {
+      Ok(Compound::Cone {
+        height: height.shape()?,
+        radius: radius.shape()?,
+      })
+    }),
+    "plane" => take(name, args, position).and_then(
+      |[normal_x, normal_y, normal_z, offset]| {
+        Ok(Compound::Plane {
+          normal_x: normal_x.shape()?,
+          normal_y: normal_y.shape()?,
+          normal_z: normal_z.shape()?,
+          offset:   offset.shape()?,
+        })
+      },
+    ),
+    "ellipsoid" => take(name, args, position).and_then(|[x, y, z]| {
+      Ok(Compound::Ellipsoid {
+        x: x.shape()?,
+        y: y.shape()?,
+        z: z.shape()?,
+      })
+    }),
+    "smooth_min_cubic" => {
+      take(name, args, position).and_then(|[lhs, rhs, k]| {
+        Ok(Compound::SmoothMinCubic {
+          lhs: lhs.shape()?,
+          rhs: rhs.shape()?,
+          k:   k.shape()?,
+        })
+      })
+    }
+    "smooth_max_cubic" => {
+      take(name, args, position).and_then(|[lhs, rhs, k]| {
+        Ok(Compound::SmoothMaxCubic {
+          lhs: lhs.shape()?,
+          rhs: rhs.shape()?,
+          k:   k.shape()?,
+        })
+      })
+    }
+    "smooth_min_quadratic" => {
+      take(name, args, position).and_then(|[lhs, rhs, k]| {
+        Ok(Compound::SmoothMinQuadratic {
+          lhs: lhs.shape()?,
+          rhs: rhs.shape()?,
+          k:   k.shape()?,
+        })
+      })
+    }
+    "smooth_min_exponential" => {
+      take(name, args, position).and_then(|[lhs, rhs, k]| {
+        Ok(Compound::SmoothMinExponential {
+          lhs: lhs.shape()?,
+          rhs: rhs.shape()?,
+          k:   k.shape()?,
+        })
+      })
+    }
+    "chamfer_min" => {
+      take(name, args, position).and_then(|[lhs, rhs, radius]| {
+        Ok(Compound::ChamferMin {
+          lhs:    lhs.shape()?,
+          rhs:    rhs.shape()?,
+          radius: radius.shape()?,
+        })
+      })
+    }
+    "round_min" => take(name, args, position).and_then(|[lhs, rhs, radius]| {
+      Ok(Compound::RoundMin {
+        lhs:    lhs.shape()?,
+        rhs:    rhs.shape()?,
+        radius: radius.shape()?,
+      })
+    }),
+    "mat_transform" => take(name, args, position).and_then(|[root, mat]| {
+      Ok(Compound::MatTransform {
+        root: root.shape()?,
+        mat:  mat.mat4()?,
+      })
+    }),
+    "clamp" => take(name, args, position).and_then(|[root, min, max]| {
+      Ok(Compound::Clamp {
+        root: root.shape()?,
+        min:  min.shape()?,
+        max:  max.shape()?,
+      })
+    }),
+    "map" => take(name, args, position).and_then(
+      |[root, in_min, in_max, out_min, out_max]| {
+        Ok(Compound::Map {
+          root:    root.shape()?,
+          in_min:  in_min.shape()?,
+          in_max:  in_max.shape()?,
+          out_min: out_min.shape()?,
+          out_max: out_max.shape()?,
+        })
+      },
+    ),
+    "repeat" => take(name, args, position).and_then(|[root, spacing, count]| {
+      Ok(Compound::Repeat {
+        root:    root.shape()?,
+        spacing: spacing.f32_triplet()?,
+        count:   count.u32_triplet()?,
+      })
+    }),
+    "repeat_infinite" => {
+      take(name, args, position).and_then(|[root, spacing]| {
+        Ok(Compound::RepeatInfinite {
+          root:    root.shape()?,
+          spacing: spacing.f32_triplet()?,
+        })
+      })
+    }
+    "mirror" => take(name, args, position).and_then(|[root, normal, offset]| {
+      Ok(Compound::Mirror {
+        root:   root.shape()?,
+        normal: normal.f32_triplet()?,
+        offset: offset.f32()?,
+      })
+    }),
+    "polar_repeat" => take(name, args, position).and_then(|[root, count]| {
+      Ok(Compound::PolarRepeat {
+        root:  root.shape()?,
+        count: count.u32()?,
+      })
+    }),
+    "catmull_rom_spline" => {
+      take(name, args, position).and_then(|[root, points, tension]| {
+        Ok(Compound::CatmullRomSpline {
+          root:    root.shape()?,
+          points:  points.f32_triplets()?,
+          tension: tension.f32()?,
+        })
+      })
+    }
+    "twist" => take(name, args, position).and_then(|[root, rate]| {
+      Ok(Compound::Twist {
+        root: root.shape()?,
+        rate: rate.shape()?,
+      })
+    }),
+    "bend" => take(name, args, position).and_then(|[root, rate]| {
+      Ok(Compound::Bend {
+        root: root.shape()?,
+        rate: rate.shape()?,
+      })
+    }),
+    "taper" => take(name, args, position).and_then(|[root, rate]| {
+      Ok(Compound::Taper {
+        root: root.shape()?,
+        rate: rate.shape()?,
+      })
+    }),
+    "shear" => take(name, args, position).and_then(|[root, x_rate, z_rate]| {
+      Ok(Compound::Shear {
+        root:   root.shape()?,
+        x_rate: x_rate.shape()?,
+        z_rate: z_rate.shape()?,
+      })
+    }),
+    "noise" => take(name, args, position).and_then(|[seed, frequency]| {
+      Ok(Compound::Noise {
+        seed:      seed.u32()?,
+        frequency: frequency.shape()?,
+      })
+    }),
+    "fbm" => take(name, args, position).and_then(
+      |[seed, octaves, frequency, lacunarity, gain]| {
+        Ok(Compound::Fbm {
+          seed:       seed.u32()?,
+          octaves:    octaves.u32()?,
+          frequency:  frequency.shape()?,
+          lacunarity: lacunarity.shape()?,
+          gain:       gain.shape()?,
+        })
+      },
+    ),
+    "material" => take(name, args, position).and_then(|[root, id]| {
+      Ok(Compound::Material {
+        root: root.shape()?,
+        id:   id.u32()?,
+      })
+    }),
+    "offset" => take(name, args, position).and_then(|[root, distance]| {
+      Ok(Compound::Offset {
+        root:     root.shape()?,
+        distance: distance.shape()?,
+      })
+    }),
+    "shell" => take(name, args, position).and_then(|[root, thickness]| {
+      Ok(Compound::Shell {
+        root:      root.shape()?,
+        thickness: thickness.shape()?,
+      })
+    }),
+    "onion" => {
+      take(name, args, position).and_then(|[root, thickness, count]| {
+        Ok(Compound::Onion {
+          root:      root.shape()?,
+          thickness: thickness.shape()?,
+          count:     count.u32()?,
+        })
+      })
+    }
+    "circle" => take(name, args, position).and_then(|[radius]| {
+      Ok(Compound::Circle {
+        radius: radius.shape()?,
+      })
+    }),
+    "rectangle" => take(name, args, position).and_then(|[x, y]| {
+      Ok(Compound::Rectangle {
+        x: x.shape()?,
+        y: y.shape()?,
+      })
+    }),
+    "rounded_rectangle" => {
+      take(name, args, position).and_then(|[x, y, radius]| {
+        Ok(Compound::RoundedRectangle {
+          x:      x.shape()?,
+          y:      y.shape()?,
+          radius: radius.shape()?,
+        })
+      })
+    }
+    "polygon" => take(name, args, position).and_then(|[points]| {
+      Ok(Compound::Polygon {
+        points: points.f32_pairs()?,
+      })
+    }),
+    "extrude" => take(name, args, position).and_then(|[profile, depth]| {
+      Ok(Compound::Extrude {
+        profile: profile.shape()?,
+        depth:   depth.shape()?,
+      })
+    }),
+    "revolve" => take(name, args, position).and_then(|[profile, axis]| {
+      Ok(Compound::Revolve {
+        profile: profile.shape()?,
+        axis:    axis.f32_triplet()?,
+      })
+    }),
+    "distance_grid" => {
+      take(name, args, position).and_then(|[size, extent, values]| {
+        Ok(Compound::DistanceGrid {
+          size:   size.u32_triplet()?,
+          extent: extent.f32_triplet()?,
+          values: values.f32s()?,
+        })
+      })
+    }
+    "heightmap" => {
+      take(name, args, position).and_then(|[size, extent, heights]| {
+        Ok(Compound::Heightmap {
+          size:    size.u32_pair()?,
+          extent:  extent.f32_pair()?,
+          heights: heights.f32s()?,
+        })
+      })
+    }
+    _ => return None,
+  };
+  Some(compound)
+}
+
+struct Parser {
+  tokens: Vec<(Token, usize)>,
+  index:  usize,
+  /// The length of the expression, for reporting errors at its end.
+  end:    usize,
+}
+
+impl Parser {
+  fn peek(&self) -> Option<(&Token, usize)> {
+    self
+      .tokens
+      .get(self.index)
+      .map(|(token, position)| (token, *position))
+  }
+
+  fn next(&mut self) -> Result<(Token, usize), ParseError> {
+    match self.tokens.get(self.index) {
+      Some(token) => {
+        self.index += 1;
+        Ok(token.clone())
+      }
+      None => error(self.end, "unexpected end of expression"),
+    }
+  }
+
+  fn eat(&mut self, symbol: char) -> bool {
+    if matches!(self.peek(), Some((Token::Symbol(c), _)) if *c == symbol) {
+      self.index += 1;
+      true
+    } else {
+      false
+    }
+  }
+
+  fn expect(&mut self, symbol: char) -> Result<(), ParseError> {
+    match self.next()? {
+      (Token::Symbol(c), _) if c == symbol => Ok(()),
+      (token, position) => {
+        error(position, format!("expected `{symbol}`, found `{token}`"))
+      }
+    }
+  }
+
+  fn expr(&mut self) -> Result<Shape, ParseError> {
+    let mut lhs = self.term()?;
+    loop {
+      if self.eat('+') {
+        lhs = Shape::Add(SharedShape::new(lhs), SharedShape::new(self.term()?));
+      } else if self.eat('-') {
+        lhs = Shape::Sub(SharedShape::new(lhs), SharedShape::new(self.term()?));
+      } else {
+        return Ok(lhs);
+      }
+    }
+  }
+
+  fn term(&mut self) -> Result<Shape, ParseError> {
+    let mut lhs = self.unary()?;
+    loop {
+      if self.eat('*') {
+        lhs =
+          Shape::Mul(SharedShape::new(lhs), SharedShape::new(self.unary()?));
+      } else if self.eat('/') {
+        lhs =
+          Shape::Div(SharedShape::new(lhs), SharedShape::new(self.unary()?));
+      } else {
+        return Ok(lhs);
+      }
+    }
+  }
+
+  fn unary(&mut self) -> Result<Shape, ParseError> {
+    if !self.eat('-') {
+      return self.primary();
+    }
+    // a negative literal is a constant rather than a negation
+    if let Some((Token::Number(n), _)) = self.peek() {
+      let n = *n;
+      self.index += 1;
+      return Ok(Shape::Constant(-n));
+    }
+    Ok(Shape::Neg(SharedShape::new(self.unary()?)))
+  }
+
+  fn primary(&mut self) -> Result<Shape, ParseError> {
+    match self.next()? {
+      (Token::Number(n), _) => Ok(Shape::Constant(n)),
+      (Token::Symbol('('), _) => {
+        let shape = self.expr()?;
+        self.expect(')')?;
+        Ok(shape)
+      }
+      (Token::Ident(name), position) => {
+        if self.eat('(') {
+          let args = self.args(')')?;
+          call(&name, args, position)
+        } else {
+          variable(&name)
+        }
+      }
+      (token, position) => error(position, format!("unexpected `{token}`")),
+    }
+  }
+
+  /// Parses comma-separated arguments up to and including `close`.
+  fn args(&mut self, close: char) -> Result<Vec<Arg>, ParseError> {
+    let mut args = Vec::new();
+    if self.eat(close) {
+      return Ok(args);
+    }
+    loop {
+      let position = self.peek().map_or(self.end, |(_, position)| position);
+      if self.eat('[') {
+        args.push(Arg::List(self.args(']')?, position));
+      } else {
+        args.push(Arg::Shape(self.expr()?, position));
+      }
+      if self.eat(close) {
+        return Ok(args);
+      }
+      self.expect(',')?;
+    }
+  }
+}
+
+/// Identifiers that parse as something other than a [`Shape::Var`].
+const RESERVED_NAMES: [&str; 5] = ["x", "y", "z", "inf", "NaN"];
+
+/// Returns whether `name` can name a [`Shape::Var`]. It has to be an
+/// identifier that doesn't already mean something else, so that the var
+/// prints as its name and parses back.
+pub fn is_var_name(name: &str) -> bool {
+  let mut chars = name.chars();
+  matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_')
+    && chars.all(|c| c.is_alphanumeric() || c == '_')
+    && !RESERVED_NAMES.contains(&name)
+}
+
+fn variable(name: &str) -> Result<Shape, ParseError> {
+  match name {
+    "x" => Ok(Shape::XNode),
+    "y" => Ok(Shape::YNode),
+    "z" => Ok(Shape::ZNode),
+    // these are written out by the printer for non-finite constants
+    "inf" => Ok(Shape::Constant(f64::INFINITY)),
+    "NaN" => Ok(Shape::Constant(f64::NAN)),
+    _ => Ok(Shape::Var(name.to_string())),
+  }
+}
+
+fn unary(
+  name: &str,
+  args: Vec<Arg>,
+  position: usize,
+  op: fn(SharedShape) -> Shape,
+) -> Result<Shape, ParseError> {
+  let [a] = take(name, args, position)?;
+  Ok(op(a.shape()?))
+}
+
+fn binary(
+  name: &str,
+  args: Vec<Arg>,
+  position: usize,
+  op: fn(SharedShape, SharedShape) -> Shape,
+) -> Result<Shape, ParseError> {
+  let [a, b] = take(name, args, position)?;
+  Ok(op(a.shape()?, b.shape()?))
+}
+
+fn call(
+  name: &str,
+  args: Vec<Arg>,
+  position: usize,
+) -> Result<Shape, ParseError> {
+  match name {
+    "min" => binary(name, args, position, Shape::Min),
+    "max" => binary(name, args, position, Shape::Max),
+    "neg" => unary(name, args, position, Shape::Neg),
+    "exp" => unary(name, args, position, Shape::Exp),
+    "sin" => unary(name, args, position, Shape::Sin),
+    "cos" => unary(name, args, position, Shape::Cos),
+    "recip" => unary(name, args, position, Shape::Recip),
+    "abs" => unary(name, args, position, Shape::Abs),
+    "sqrt" => unary(name, args, position, Shape::Sqrt),
+    "square" => unary(name, args, position, Shape::Square),
+    "remap" => {
+      let [root, new_x, new_y, new_z] = take(name, args, position)?;
+      Ok(Shape::Remap {
+        root:  root.shape()?,
+        new_x: new_x.shape()?,
+        new_y: new_y.shape()?,
+        new_z: new_z.shape()?,
+      })
+    }
+    _ => match parse_compound(name, args, position) {
+      Some(compound) => Ok(Shape::Extra(compound?)),
+      None => error(position, format!("unknown function `{name}`")),
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::shape::builder;
+
+  fn assert_round_trips(shape: &Shape) {
+    let printed = shape.to_string();
+    let parsed = Shape::parse(&printed).unwrap();
+    assert_eq!(format!("{:?}", shape), format!("{:?}", parsed), "{printed}");
+  }
+
+  #[test]
+  fn parses_expressions() {
+    let shape = Shape::parse("sqrt(square(x) + square(y)) - 2 * -z").unwrap();
+    let expected = builder::sub(
+      builder::sqrt(builder::add(
+        builder::square(builder::x()),
+        builder::square(builder::y()),
+      )),
+      builder::mul(2.0, builder::neg(builder::z())),
+    );
+    assert_eq!(format!("{:?}", shape), format!("{:?}", expected));
+  }
+
+  #[test]
+  fn prints_minimal_parentheses() {
+    let shape = builder::div(
+      builder::sub(builder::x(), builder::sub(builder::y(), 1.0)),
+      builder::mul(builder::z(), 2.0),
+    );
+    assert_eq!(shape.to_string(), "(x - (y - 1.0)) / (z * 2.0)");
+  }
+
+  #[test]
+  fn shapes_round_trip() {
+    assert_round_trips(&builder::neg(builder::neg(builder::constant(1.0))));
+    assert_round_trips(&builder::add(builder::x(), -1.5));
+    assert_round_trips(&builder::translate(
+      builder::smooth_min_cubic(
+        builder::sphere(1.0),
+        builder::cuboid(1.0, 2.0, 0.5),
+        0.25,
+      ),
+      1.0,
+      0.0,
+      -3.0,
+    ));
+    assert_round_trips(&builder::repeat(
+      builder::torus(2.0, 0.5),
+      [4.0, 0.0, 4.0],
+      [3, 1, 3],
+    ));
+    assert_round_trips(&builder::repeat_infinite(builder::sphere(0.5), [
+      2.0, 0.0, 2.0,
+    ]));
+    assert_round_trips(&builder::catmull_rom_tube(
+      vec![[0.0, 0.0, 0.0], [1.0, 2.0, 0.0], [2.0, 0.0, 1.0]],
+      0.5,
+      0.1,
+    ));
+    assert_round_trips(&builder::fbm(3, 4, 0.05, 2.0, builder::var("gain")));
+    assert_round_trips(&builder::extrude(
+      builder::profile::polygon(vec![[0.0, 0.0], [2.0, 0.0], [0.0, 1.5]]),
+      0.5,
+    ));
+    assert_round_trips(&builder::revolve(
+      builder::profile::rounded_rectangle(1.0, 0.5, 0.1),
+      [0.0, 1.0, 0.0],
+    ));
+    assert_round_trips(&Shape::Extra(Compound::Heightmap {
+      size:    [2, 2],
+      extent:  [4.0, 4.0],
+      heights: vec![0.0, 0.5, -1.25, 1e-7],
+    }));
+  }
+
+  #[test]
+  fn unknown_identifiers_are_vars() {
+    let shape = Shape::parse("sphere(radius) + offset").unwrap();
+    let expected = builder::add(
+      builder::sphere(builder::var("radius")),
+      builder::var("offset"),
+    );
+    assert_eq!(format!("{:?}", shape), format!("{:?}", expected));
+    assert_round_trips(&expected);
+  }
+
+  #[test]
+  fn var_names_are_identifiers() {
+    for name in ["radius", "_gain", "x2", "inf_height", "höhe"] {
+      assert!(is_var_name(name), "{name}");
+      assert_round_trips(&builder::add(builder::var(name), builder::x()));
+    }
+    for name in ["x", "y", "z", "inf", "NaN", "", "2x", "a b", "a-b", "(x)"] {
+      assert!(!is_var_name(name), "{name}");
+    }
+  }
+
+  #[test]
+  #[should_panic(expected = "invalid var name `x`")]
+  fn vars_cannot_shadow_coordinates() { builder::var("x"); }
+
+  #[test]
+  fn reports_errors_with_positions() {
+    let error = Shape::parse("x + foo(1)").unwrap_err();
+    assert_eq!(error.position, 4);
+    let error = Shape::parse("sphere(1, 2)").unwrap_err();
+    assert_eq!(error.position, 0);
+    let error = Shape::parse("(x + 1").unwrap_err();
+    assert_eq!(error.position, 6);
+  }
+
+  #[test]
+  fn parsed_expressions_match_rhai() {
+    let expr = "(sqrt(square(x) + square(y + 50) + square(z)) - 50) + ((sin(x \
+                / 20.0) + sin(y / 20.0)) * 4.0)";
+    let parsed = Shape::parse(expr).unwrap();
+    let original = Shape::new_expr(expr);
+    for point in [[0.0, 0.0, 0.0], [10.0, -3.0, 2.5], [-40.0, 12.0, 7.0]] {
+      let mut ctx = fidget::Context::new();
+      let a = fidget::context::IntoNode::into_node(&parsed, &mut ctx).unwrap();
+      let b =
+        fidget::context::IntoNode::into_node(&original, &mut ctx).unwrap();
+      let a = ctx.eval_xyz(a, point[0], point[1], point[2]).unwrap();
+      let b = ctx.eval_xyz(b, point[0], point[1], point[2]).unwrap();
+      assert!((a - b).abs() < 1e-9);
+    }
+  }
+}
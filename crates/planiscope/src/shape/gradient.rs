@@ -0,0 +1,365 @@
+//! Symbolic gradients of [`Shape`]s.
+//!
+//! Each partial derivative is built as its own shape with the usual rules of
+//! differentiation, reusing the original subtrees wherever the rules refer
+//! back to them. `min`, `max` and `abs` aren't differentiable where their
+//! branches meet, so their derivatives pick a branch with a steep step, like
+//! [`nso_steep_sign`](crate::nso::other::nso_steep_sign).
+//!
+//! Compounds are differentiated through their
+//! [`expand`](super::compound::Compound::expand)ed form, the same field
+//! written out in core shapes, so their derivatives are symbolic too. Only
+//! the few that can't be expanded (polar repetition, splines, noise,
+//! polygons and sampled grids) and expressions that can't be parsed fall back
+//! to central differences over [`FINITE_DIFFERENCE_STEP`], which are only
+//! as accurate as the step allows and cost two evaluations of the subtree.
+
+use std::collections::HashMap;
+
+use super::{builder, Shape, SharedShape};
+
+/// The step used to differentiate numerically, where a shape has no symbolic
+/// derivative.
+pub const FINITE_DIFFERENCE_STEP: f64 = 1.0e-3;
+
+/// The slope of the steps used to pick a branch of `min`, `max` and `abs`.
+const BRANCH_STEEPNESS: f64 = 1.0e6;
+
+impl Shape {
+  /// Returns the partial derivatives of the shape along X, Y and Z, as
+  /// shapes. Subtrees shared within the shape are only differentiated once,
+  /// and their derivatives are shared in turn.
+  ///
+  /// For an exact distance field the gradient has a length of one, so fields
+  /// like `|grad| - 1` measure how far a shape is from being one.
+  pub fn gradient_shapes(&self) -> [Shape; 3] {
+    let root = SharedShape::new(self.clone());
+    let mut derivatives = Derivatives::default();
+    [0, 1, 2].map(|axis| derivatives.of(&root, axis).simplify())
+  }
+}
+
+/// Differentiates shapes, remembering each subtree's derivative along each
+/// axis by the subtree's hash.
+#[derive(Default)]
+struct Derivatives {
+  cache: [HashMap<u64, SharedShape>; 3],
+}
+
+impl Derivatives {
+  fn of(&mut self, shape: &SharedShape, axis: usize) -> SharedShape {
+    let hash = shape.content_hash();
+    if let Some(derivative) = self.cache[axis].get(&hash) {
+      return derivative.clone();
+    }
+    let derivative = SharedShape::new(self.uncached(shape, axis));
+    self.cache[axis].insert(hash, derivative.clone());
+    derivative
+  }
+
+  fn uncached(&mut self, shape: &SharedShape, axis: usize) -> Shape {
+    let coord = |coord_axis: usize| {
+      Shape::Constant(if coord_axis == axis { 1.0 } else { 0.0 })
+    };
+
+    match shape.as_ref() {
+      Shape::Expression { expr } => match Shape::parse(expr) {
+        Ok(parsed) => self.of(&SharedShape::new(parsed), axis).into_inner(),
+        Err(_) => finite_difference(shape, axis),
+      },
+      Shape::XNode => coord(0),
+      Shape::YNode => coord(1),
+      Shape::ZNode => coord(2),
+      Shape::Constant(_) | Shape::Var(_) => Shape::Constant(0.0),
+      Shape::Add(a, b) => add(self.of(a, axis), self.of(b, axis)),
+      Shape::Sub(a, b) => sub(self.of(a, axis), self.of(b, axis)),
+      Shape::Mul(a, b) => {
+        let da = self.of(a, axis);
+        let db = self.of(b, axis);
+        add(mul(da, b.clone()), mul(a.clone(), db))
+      }
+      Shape::Div(a, b) => {
+        // (a' b - a b') / b^2
+        let da = self.of(a, axis);
+        let db = self.of(b, axis);
+        let numerator = sub(mul(da, b.clone()), mul(a.clone(), db));
+        div(numerator, Shape::Square(b.clone()))
+      }
+      Shape::Min(a, b) => {
+        let pick_a = step(Shape::Sub(b.clone(), a.clone()));
+        self.branch(pick_a, a, b, axis)
+      }
+      Shape::Max(a, b) => {
+        let pick_a = step(Shape::Sub(a.clone(), b.clone()));
+        self.branch(pick_a, a, b, axis)
+      }
+      Shape::Neg(a) => neg(self.of(a, axis)),
+      Shape::Exp(a) => mul(shape.clone(), self.of(a, axis)),
+      Shape::Sin(a) => mul(Shape::Cos(a.clone()), self.of(a, axis)),
+      Shape::Cos(a) => neg(mul(Shape::Sin(a.clone()), self.of(a, axis))),
+      Shape::Recip(a) => neg(div(self.of(a, axis), Shape::Square(a.clone()))),
+      Shape::Abs(a) => {
+        let sign = mul(a.clone(), Shape::Constant(BRANCH_STEEPNESS));
+        let sign = builder::max(builder::min(sign, 1.0), -1.0);
+        mul(sign, self.of(a, axis))
+      }
+      Shape::Sqrt(a) => {
+        div(self.of(a, axis), mul(shape.clone(), Shape::Constant(2.0)))
+      }
+      Shape::Square(a) => {
+        mul(mul(a.clone(), Shape::Constant(2.0)), self.of(a, axis))
+      }
+      Shape::Remap {
+        root,
+        new_x,
+        new_y,
+        new_z,
+      } => {
+        // the chain rule, with the root's gradient sampled where the root is
+        let mut sum = Shape::Constant(0.0);
+        for (root_axis, coord) in [new_x, new_y, new_z].into_iter().enumerate()
+        {
+          let d_coord = self.of(coord, axis);
+          if is_zero(&d_coord) {
+            continue;
+          }
+          let d_root = Shape::Remap {
+            root:  self.of(root, root_axis),
+            new_x: new_x.clone(),
+            new_y: new_y.clone(),
+            new_z: new_z.clone(),
+          };
+          sum = add(sum, mul(d_root, d_coord));
+        }
+        sum
+      }
+      Shape::Extra(compound) => match compound.expand() {
+        Some(expanded) => {
+          self.of(&SharedShape::new(expanded), axis).into_inner()
+        }
+        None => finite_difference(shape, axis),
+      },
+    }
+  }
+
+  /// The derivative of a branch that takes `a` where `pick_a` is one and `b`
+  /// where it's zero.
+  fn branch(
+    &mut self,
+    pick_a: Shape,
+    a: &SharedShape,
+    b: &SharedShape,
+    axis: usize,
+  ) -> Shape {
+    let da = self.of(a, axis);
+    let db = self.of(b, axis);
+    add(db.clone(), mul(pick_a, sub(da, db)))
+  }
+}
+
+/// A central difference of `shape` along `axis`.
+fn finite_difference(shape: &SharedShape, axis: usize) -> Shape {
+  let h = FINITE_DIFFERENCE_STEP;
+  let shifted = |offset: f64| {
+    let mut coords = [builder::x(), builder::y(), builder::z()];
+    coords[axis] = builder::add(coords[axis].clone(), offset);
+    let [new_x, new_y, new_z] = coords.map(SharedShape::new);
+    Shape::Remap {
+      root: shape.clone(),
+      new_x,
+      new_y,
+      new_z,
+    }
+  };
+  builder::div(builder::sub(shifted(h), shifted(-h)), 2.0 * h)
+}
+
+/// A step from zero where `v <= 0` to one where `v` is just above zero.
+fn step(v: Shape) -> Shape {
+  let steep = builder::mul(v, BRANCH_STEEPNESS);
+  builder::max(builder::min(steep, 1.0), 0.0)
+}
+
+fn is_zero(shape: &Shape) -> bool {
+  matches!(shape, Shape::Constant(c) if *c == 0.0)
+}
+
+// These fold away the zeros that most derivatives are full of. Unlike
+// `Shape::simplify`, they treat `0 * x` as zero even where `x` isn't finite.
+// They take shared subtrees as they are, so that a derivative used in several
+// places stays one subtree.
+
+fn add(a: impl Into<SharedShape>, b: impl Into<SharedShape>) -> Shape {
+  let (a, b) = (a.into(), b.into());
+  if is_zero(&b) {
+    a.into_inner()
+  } else if is_zero(&a) {
+    b.into_inner()
+  } else {
+    Shape::Add(a, b)
+  }
+}
+
+fn sub(a: impl Into<SharedShape>, b: impl Into<SharedShape>) -> Shape {
+  let (a, b) = (a.into(), b.into());
+  if is_zero(&b) {
+    a.into_inner()
+  } else if is_zero(&a) {
+    Shape::Neg(b)
+  } else {
+    Shape::Sub(a, b)
+  }
+}
+
+fn mul(a: impl Into<SharedShape>, b: impl Into<SharedShape>) -> Shape {
+  let (a, b) = (a.into(), b.into());
+  if is_zero(&a) || is_zero(&b) {
+    Shape::Constant(0.0)
+  } else {
+    Shape::Mul(a, b)
+  }
+}
+
+fn div(a: impl Into<SharedShape>, b: impl Into<SharedShape>) -> Shape {
+  let (a, b) = (a.into(), b.into());
+  if is_zero(&a) {
+    Shape::Constant(0.0)
+  } else {
+    Shape::Div(a, b)
+  }
+}
+
+fn neg(a: impl Into<SharedShape>) -> Shape {
+  let a = a.into();
+  if is_zero(&a) {
+    Shape::Constant(0.0)
+  } else {
+    Shape::Neg(a)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use float_cmp::approx_eq;
+  use glam::Vec3A;
+
+  use super::*;
+
+  fn gradient_at(gradient: &[Shape; 3], point: Vec3A) -> Vec3A {
+    let [x, y, z] = gradient.each_ref().map(|d| d.eval(point).unwrap());
+    Vec3A::new(x, y, z)
+  }
+
+  fn assert_close(actual: Vec3A, expected: Vec3A, epsilon: f32) {
+    assert!(
+      (actual - expected).length() < epsilon,
+      "{actual} is not {expected}"
+    );
+  }
+
+  #[test]
+  fn expressions_are_differentiated_symbolically() {
+    let shape = builder::expr("sqrt(square(x) + square(y) + square(z)) - 1");
+    let gradient = shape.gradient_shapes();
+    for point in [Vec3A::new(3.0, 0.0, 4.0), Vec3A::new(-1.0, 2.0, 0.5)] {
+      assert_close(gradient_at(&gradient, point), point.normalize(), 1e-5);
+    }
+  }
+
+  #[test]
+  fn remaps_follow_the_chain_rule() {
+    let plane = builder::sub(builder::x(), 1.0);
+    let rotation = glam::Quat::from_rotation_z(0.5);
+    let shape =
+      builder::translate(builder::rotate(plane, rotation), 1.0, 2.0, 3.0);
+    let gradient = shape.gradient_shapes();
+    let point = Vec3A::new(0.3, -0.7, 2.0);
+    assert_close(
+      gradient_at(&gradient, point),
+      Vec3A::from(rotation * glam::Vec3::X),
+      1e-5,
+    );
+  }
+
+  #[test]
+  fn min_takes_the_nearer_branch() {
+    let left = builder::translate(builder::expr("x"), -1.0, 0.0, 0.0);
+    let right = builder::neg(builder::expr("y"));
+    let gradient = builder::min(left, right).gradient_shapes();
+    assert_close(
+      gradient_at(&gradient, Vec3A::new(-2.0, 0.0, 0.0)),
+      Vec3A::X,
+      1e-5,
+    );
+    assert_close(
+      gradient_at(&gradient, Vec3A::new(0.0, 1.0, 0.0)),
+      -Vec3A::Y,
+      1e-5,
+    );
+  }
+
+  #[test]
+  fn compounds_have_unit_gradients_where_exact() {
+    let shape = builder::translate(builder::sphere(1.0), 0.5, 0.0, 0.0);
+    let gradient = shape.gradient_shapes();
+    let length = builder::sqrt(builder::add(
+      builder::add(
+        builder::square(gradient[0].clone()),
+        builder::square(gradient[1].clone()),
+      ),
+      builder::square(gradient[2].clone()),
+    ));
+    let error = builder::sub(length, 1.0);
+    for point in [Vec3A::new(2.0, 1.0, 0.0), Vec3A::new(0.0, -0.3, 0.2)] {
+      assert!(approx_eq!(
+        f32,
+        error.eval(point).unwrap(),
+        0.0,
+        epsilon = 1e-3
+      ));
+    }
+    let point = Vec3A::new(2.5, 0.0, 0.0);
+    assert_close(gradient_at(&gradient, point), Vec3A::X, 1e-3);
+  }
+
+  #[test]
+  fn compounds_are_differentiated_symbolically() {
+    // central differences in f32 are off by around 1e-4, so this only passes
+    // for exact derivatives
+    let shape = builder::torus(1.0, 0.25);
+    let gradient = shape.gradient_shapes();
+    let point = Vec3A::new(2.0, 0.5, 0.0);
+    assert_close(
+      gradient_at(&gradient, point),
+      Vec3A::new(1.0, 0.5, 0.0).normalize(),
+      1e-5,
+    );
+
+    // outside the +X face of a twisted box, the field is the untwisted X
+    // minus one, and the twist angle is half of Y
+    let shape = builder::twist(builder::cuboid(1.0, 2.0, 1.0), 0.5);
+    let gradient = shape.gradient_shapes();
+    let (sin, cos) = 0.25_f32.sin_cos();
+    assert_close(
+      gradient_at(&gradient, Vec3A::new(1.5, 0.5, 0.0)),
+      Vec3A::new(cos, -0.75 * sin, sin),
+      1e-5,
+    );
+  }
+
+  #[test]
+  fn shared_subtrees_are_differentiated_once() {
+    // a tree that's exponentially large if its shared subtrees are expanded
+    let mut shape = builder::expr("square(x) + y");
+    for _ in 0..40 {
+      shape = builder::min(shape.clone(), shape);
+    }
+    let gradient = shape.gradient_shapes();
+    let point = Vec3A::new(2.0, 1.0, 0.0);
+    assert_close(
+      gradient_at(&gradient, point),
+      Vec3A::new(4.0, 1.0, 0.0),
+      1e-5,
+    );
+  }
+}
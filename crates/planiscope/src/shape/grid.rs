@@ -0,0 +1,153 @@
+//! Building sampled grid shapes from buffers and images.
+
+use std::{fmt, path::Path};
+
+use crate::nso::grid::sample_count;
+pub use crate::nso::grid::MAX_GRID_SAMPLES;
+
+/// An error encountered while building a grid shape.
+#[derive(Debug)]
+pub enum GridError {
+  /// The number of samples doesn't match the grid's size.
+  WrongSampleCount {
+    expected: usize,
+    found:    usize,
+  },
+  /// The grid has no samples along some axis.
+  Empty,
+  /// The grid has more than [`MAX_GRID_SAMPLES`] samples. Its size is given
+  /// per axis, since the count may not fit in a `usize`.
+  TooLarge {
+    size: Vec<u32>,
+  },
+  Image(image::ImageError),
+}
+
+impl fmt::Display for GridError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::WrongSampleCount { expected, found } => {
+        write!(f, "expected {expected} grid samples, found {found}")
+      }
+      Self::Empty => write!(f, "grid has no samples along an axis"),
+      Self::TooLarge { size } => write!(
+        f,
+        "grid of {size:?} samples is larger than the limit of \
+         {MAX_GRID_SAMPLES}"
+      ),
+      Self::Image(error) => write!(f, "couldn't read heightmap image: {error}"),
+    }
+  }
+}
+
+impl std::error::Error for GridError {}
+
+impl From<image::ImageError> for GridError {
+  fn from(error: image::ImageError) -> Self { Self::Image(error) }
+}
+
+/// Checks that a grid of `size` is nonempty and within [`MAX_GRID_SAMPLES`],
+/// returning its number of samples.
+pub(crate) fn check_size(size: &[u32]) -> Result<usize, GridError> {
+  if size.contains(&0) {
+    return Err(GridError::Empty);
+  }
+  match sample_count(size) {
+    Some(count) if count <= MAX_GRID_SAMPLES => Ok(count),
+    _ => Err(GridError::TooLarge {
+      size: size.to_vec(),
+    }),
+  }
+}
+
+/// Checks that a grid of `size` can be built from `count` samples.
+pub(crate) fn check_samples(
+  size: &[u32],
+  count: usize,
+) -> Result<(), GridError> {
+  let expected = check_size(size)?;
+  if count != expected {
+    return Err(GridError::WrongSampleCount {
+      expected,
+      found: count,
+    });
+  }
+  Ok(())
+}
+
+/// Reads an image as grayscale, returning its size and its pixels in the
+/// range `[0, 1]`, row by row. Images too large for a grid are rejected
+/// before they're decoded.
+pub(crate) fn read_grayscale(
+  path: &Path,
+) -> Result<([u32; 2], Vec<f32>), GridError> {
+  let (width, height) = image::image_dimensions(path)?;
+  check_size(&[width, height])?;
+  let image = image::open(path)?.into_luma16();
+  let size = [image.width(), image.height()];
+  let pixels = image
+    .into_raw()
+    .into_iter()
+    .map(|p| p as f32 / u16::MAX as f32)
+    .collect();
+  Ok((size, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::shape::builder;
+
+  #[test]
+  fn sample_counts_are_checked() {
+    assert!(matches!(
+      builder::heightmap([2, 2], [1.0, 1.0], vec![0.0; 3]),
+      Err(GridError::WrongSampleCount {
+        expected: 4,
+        found:    3,
+      })
+    ));
+    assert!(matches!(
+      builder::distance_grid([2, 0, 2], [1.0; 3], Vec::new()),
+      Err(GridError::Empty)
+    ));
+  }
+
+  #[test]
+  fn oversized_grids_are_rejected() {
+    assert!(matches!(
+      builder::heightmap([1024, 1024], [1.0, 1.0], Vec::new()),
+      Err(GridError::TooLarge { .. })
+    ));
+    // the sample count would overflow
+    let size = [u32::MAX; 3];
+    assert!(matches!(
+      builder::distance_grid(size, [1.0; 3], Vec::new()),
+      Err(GridError::TooLarge { size: found }) if found == size
+    ));
+  }
+
+  #[test]
+  fn heightmaps_load_from_images() {
+    let path = std::env::temp_dir()
+      .join(format!("planiscope-heightmap-{}.png", std::process::id()));
+    image::ImageBuffer::<image::Luma<u16>, _>::from_raw(2, 2, vec![
+      0,
+      u16::MAX,
+      u16::MAX,
+      0,
+    ])
+    .unwrap()
+    .save(&path)
+    .unwrap();
+
+    let shape = builder::heightmap_from_image(&path, [1.0, 1.0], 4.0);
+    std::fs::remove_file(&path).unwrap();
+    let expected =
+      builder::heightmap([2, 2], [1.0, 1.0], vec![0.0, 4.0, 4.0, 0.0]);
+    assert_eq!(
+      format!("{:?}", shape.unwrap()),
+      format!("{:?}", expected.unwrap())
+    );
+  }
+}
@@ -0,0 +1,172 @@
+//! Material regions of [`Shape`]s, tagged with [`Compound::Material`].
+//!
+//! Materials don't change the distance field. Instead, the mesher asks for
+//! [`Shape::material_fields`] and gives each vertex the material whose field
+//! is nearest to zero there, i.e. whose surface the vertex lies on.
+
+use super::{compound::Compound, Shape, SharedShape};
+
+/// The material of any part of a shape that isn't inside a
+/// [`Compound::Material`].
+pub const DEFAULT_MATERIAL: u32 = 0;
+
+impl Shape {
+  /// Returns the field of each material region in the shape, along with the
+  /// material's ID. Shapes without materials return nothing.
+  ///
+  /// Each field is the tagged subtree placed back under its ancestors'
+  /// coordinate transforms, so its zero set is that region's surface. Nested
+  /// materials come before the ones they're nested in, and the untagged rest
+  /// of the shape comes last as [`DEFAULT_MATERIAL`], so that ties go to the
+  /// innermost tag.
+  pub fn material_fields(&self) -> Vec<(u32, Shape)> {
+    let shape = self.simplify();
+    let mut fields = Vec::new();
+    collect_fields(&shape, &|s| s, &mut fields);
+    if fields.is_empty() {
+      return fields;
+    }
+
+    let rest = strip_materials(&shape).simplify();
+    if !matches!(rest, Shape::Constant(_)) {
+      fields.push((DEFAULT_MATERIAL, rest));
+    }
+    fields
+  }
+}
+
+/// Pushes the field of every material region within `shape`. `place` puts a
+/// subtree of `shape` back under the transforms of its ancestors.
+fn collect_fields(
+  shape: &Shape,
+  place: &dyn Fn(Shape) -> Shape,
+  fields: &mut Vec<(u32, Shape)>,
+) {
+  match shape {
+    Shape::Remap {
+      root,
+      new_x,
+      new_y,
+      new_z,
+    } => {
+      let place = |s: Shape| {
+        place(Shape::Remap {
+          root:  SharedShape::new(s),
+          new_x: new_x.clone(),
+          new_y: new_y.clone(),
+          new_z: new_z.clone(),
+        })
+      };
+      collect_fields(root, &place, fields);
+    }
+    Shape::Extra(Compound::Material { root, id }) => {
+      collect_fields(root, place, fields);
+      fields.push((*id, place(root.as_ref().clone())));
+    }
+    Shape::Extra(compound) => {
+      for (name, child) in compound.children() {
+        if !compound.transforms_child(name) {
+          collect_fields(child, place, fields);
+          continue;
+        }
+        let place = |s: Shape| {
+          place(Shape::Extra(compound.map_children(|c| {
+            if std::ptr::eq(c, child) {
+              s.clone()
+            } else {
+              c.clone()
+            }
+          })))
+        };
+        collect_fields(child, &place, fields);
+      }
+    }
+    _ => {
+      for child in shape.children() {
+        collect_fields(child, place, fields);
+      }
+    }
+  }
+}
+
+/// Replaces every material region with empty space.
+fn strip_materials(shape: &Shape) -> Shape {
+  match shape {
+    Shape::Extra(Compound::Material { .. }) => Shape::Constant(f64::INFINITY),
+    _ => shape.map_children(strip_materials),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::shape::builder;
+
+  #[test]
+  fn shapes_without_materials_have_no_fields() {
+    assert!(builder::sphere(1.0).material_fields().is_empty());
+  }
+
+  #[test]
+  fn fields_keep_their_transforms() {
+    let shape = builder::min(
+      builder::translate(
+        builder::material(builder::sphere(1.0), 2),
+        3.0,
+        0.0,
+        0.0,
+      ),
+      builder::cuboid(1.0, 1.0, 1.0),
+    );
+    let fields = shape.material_fields();
+    let ids = fields.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+    assert_eq!(ids, vec![2, DEFAULT_MATERIAL]);
+
+    let on_sphere = glam::Vec3A::new(4.0, 0.0, 0.0);
+    let on_cuboid = glam::Vec3A::new(0.0, 1.0, 0.0);
+    assert!(fields[0].1.eval(on_sphere).unwrap().abs() < 1e-5);
+    assert!(fields[0].1.eval(on_cuboid).unwrap().abs() > 0.5);
+    assert!(fields[1].1.eval(on_cuboid).unwrap().abs() < 1e-5);
+  }
+
+  #[test]
+  fn profile_materials_are_extruded() {
+    let shape = builder::extrude(
+      builder::translate(
+        builder::material(builder::profile::circle(1.0), 4),
+        2.0,
+        0.0,
+        0.0,
+      ),
+      2.0,
+    );
+    let fields = shape.material_fields();
+    assert_eq!(fields[0].0, 4);
+
+    // the field is the extruded cylinder, so it's capped along z
+    let on_side = glam::Vec3A::new(3.0, 0.0, 0.5);
+    let on_cap = glam::Vec3A::new(2.0, 0.0, 1.0);
+    let past_cap = glam::Vec3A::new(2.0, 0.0, 2.0);
+    assert!(fields[0].1.eval(on_side).unwrap().abs() < 1e-5);
+    assert!(fields[0].1.eval(on_cap).unwrap().abs() < 1e-5);
+    assert!((fields[0].1.eval(past_cap).unwrap() - 1.0).abs() < 1e-5);
+  }
+
+  #[test]
+  fn nested_materials_come_first() {
+    let shape = builder::material(
+      builder::min(
+        builder::material(builder::sphere(1.0), 3),
+        builder::translate(builder::sphere(1.0), 3.0, 0.0, 0.0),
+      ),
+      1,
+    );
+    let ids = shape
+      .material_fields()
+      .into_iter()
+      .map(|(id, _)| id)
+      .collect::<Vec<_>>();
+    // everything is tagged, so there's no untagged rest
+    assert_eq!(ids, vec![3, 1]);
+  }
+}
@@ -1,5 +1,15 @@
+mod bounds;
 pub mod builder;
 pub mod compound;
+mod expand;
+mod expr;
+mod gradient;
+pub(crate) mod grid;
+mod material;
+mod reflect;
+mod shared;
+mod simplify;
+mod validate;
 
 use std::{
   collections::{hash_map::DefaultHasher, HashMap},
@@ -17,6 +27,16 @@ use fidget::{
 };
 use serde::{Deserialize, Serialize};
 
+pub use self::{
+  bounds::{Bounds, SEARCH_HALF_EXTENT},
+  expr::{is_var_name, ParseError},
+  gradient::FINITE_DIFFERENCE_STEP,
+  grid::{GridError, MAX_GRID_SAMPLES},
+  material::DEFAULT_MATERIAL,
+  shared::SharedShape,
+  validate::{ValidationError, ValidationErrorKind, ValidationReport},
+};
+
 pub trait CachedIntoNode: Clone + Hash {
   fn cached_into_node(
     &self,
@@ -42,7 +62,7 @@ impl CachedIntoNode for Shape {
       return Ok(*node);
     }
 
-    let node = self.into_node(ctx)?;
+    let node = self.into_node_uncached(ctx, cache)?;
     cache.insert(hash, node);
     Ok(node)
   }
@@ -58,31 +78,31 @@ pub enum Shape {
   YNode,
   ZNode,
   Constant(#[educe(Hash(trait = "FloatHash"))] f64),
-  Add(#[reflect(ignore)] Box<Shape>, #[reflect(ignore)] Box<Shape>),
-  Sub(#[reflect(ignore)] Box<Shape>, #[reflect(ignore)] Box<Shape>),
-  Mul(#[reflect(ignore)] Box<Shape>, #[reflect(ignore)] Box<Shape>),
-  Div(#[reflect(ignore)] Box<Shape>, #[reflect(ignore)] Box<Shape>),
-  Min(#[reflect(ignore)] Box<Shape>, #[reflect(ignore)] Box<Shape>),
-  Max(#[reflect(ignore)] Box<Shape>, #[reflect(ignore)] Box<Shape>),
-  Neg(#[reflect(ignore)] Box<Shape>),
-  Exp(#[reflect(ignore)] Box<Shape>),
-  Sin(#[reflect(ignore)] Box<Shape>),
-  Cos(#[reflect(ignore)] Box<Shape>),
-  Recip(#[reflect(ignore)] Box<Shape>),
-  Abs(#[reflect(ignore)] Box<Shape>),
-  Sqrt(#[reflect(ignore)] Box<Shape>),
-  Square(#[reflect(ignore)] Box<Shape>),
+  Add(SharedShape, SharedShape),
+  Sub(SharedShape, SharedShape),
+  Mul(SharedShape, SharedShape),
+  Div(SharedShape, SharedShape),
+  Min(SharedShape, SharedShape),
+  Max(SharedShape, SharedShape),
+  Neg(SharedShape),
+  Exp(SharedShape),
+  Sin(SharedShape),
+  Cos(SharedShape),
+  Recip(SharedShape),
+  Abs(SharedShape),
+  Sqrt(SharedShape),
+  Square(SharedShape),
   Remap {
-    #[reflect(ignore)]
-    root:  Box<Shape>,
-    #[reflect(ignore)]
-    new_x: Box<Shape>,
-    #[reflect(ignore)]
-    new_y: Box<Shape>,
-    #[reflect(ignore)]
-    new_z: Box<Shape>,
+    root:  SharedShape,
+    new_x: SharedShape,
+    new_y: SharedShape,
+    new_z: SharedShape,
   },
   Extra(compound::Compound),
+  /// A named parameter, bound to a value at evaluation time. See
+  /// [`MesherInputs::bindings`](crate::mesher::MesherInputs::bindings). The
+  /// name has to pass [`is_var_name`].
+  Var(String),
 }
 
 impl Default for Shape {
@@ -97,15 +117,17 @@ impl From<f32> for Shape {
   fn from(value: f32) -> Self { Shape::Constant(value.into()) }
 }
 
-impl_op_ex!(+ |lhs: &Shape, rhs: &Shape| -> Shape { Shape::Add(Box::new(lhs.clone()), Box::new(rhs.clone())) });
+impl_op_ex!(+ |lhs: &Shape, rhs: &Shape| -> Shape { Shape::Add(SharedShape::new(lhs.clone()), SharedShape::new(rhs.clone())) });
 impl_op_ex!(-|lhs: &Shape, rhs: &Shape| -> Shape {
-  Shape::Sub(Box::new(lhs.clone()), Box::new(rhs.clone()))
+  Shape::Sub(SharedShape::new(lhs.clone()), SharedShape::new(rhs.clone()))
 });
 impl_op_ex!(*|lhs: &Shape, rhs: &Shape| -> Shape {
-  Shape::Mul(Box::new(lhs.clone()), Box::new(rhs.clone()))
+  Shape::Mul(SharedShape::new(lhs.clone()), SharedShape::new(rhs.clone()))
+});
+impl_op_ex!(/ |lhs: &Shape, rhs: &Shape| -> Shape { Shape::Div(SharedShape::new(lhs.clone()), SharedShape::new(rhs.clone())) });
+impl_op_ex!(-|lhs: &Shape| -> Shape {
+  Shape::Neg(SharedShape::new(lhs.clone()))
 });
-impl_op_ex!(/ |lhs: &Shape, rhs: &Shape| -> Shape { Shape::Div(Box::new(lhs.clone()), Box::new(rhs.clone())) });
-impl_op_ex!(-|lhs: &Shape| -> Shape { Shape::Neg(Box::new(lhs.clone())) });
 
 impl Shape {
   pub fn new_expr(expr: &str) -> Self {
@@ -113,10 +135,111 @@ impl Shape {
       expr: expr.to_string(),
     }
   }
+
+  /// Returns the direct children of this shape. Expressions are leaves.
+  pub fn children(&self) -> Vec<&Shape> {
+    match self {
+      Shape::Expression { .. }
+      | Shape::XNode
+      | Shape::YNode
+      | Shape::ZNode
+      | Shape::Constant(_)
+      | Shape::Var(_) => Vec::new(),
+      Shape::Add(lhs, rhs)
+      | Shape::Sub(lhs, rhs)
+      | Shape::Mul(lhs, rhs)
+      | Shape::Div(lhs, rhs)
+      | Shape::Min(lhs, rhs)
+      | Shape::Max(lhs, rhs) => vec![lhs.as_ref(), rhs.as_ref()],
+      Shape::Neg(a)
+      | Shape::Exp(a)
+      | Shape::Sin(a)
+      | Shape::Cos(a)
+      | Shape::Recip(a)
+      | Shape::Abs(a)
+      | Shape::Sqrt(a)
+      | Shape::Square(a) => vec![a.as_ref()],
+      Shape::Remap {
+        root,
+        new_x,
+        new_y,
+        new_z,
+      } => vec![
+        root.as_ref(),
+        new_x.as_ref(),
+        new_y.as_ref(),
+        new_z.as_ref(),
+      ],
+      Shape::Extra(compound) => compound
+        .children()
+        .into_iter()
+        .map(|(_, child)| child)
+        .collect(),
+    }
+  }
+
+  /// Returns a copy of this shape with `f` applied to each of its direct
+  /// children. Leaves are cloned as they are.
+  pub fn map_children(&self, mut f: impl FnMut(&Shape) -> Shape) -> Shape {
+    self.map_shared_children(|s| SharedShape::new(f(s)))
+  }
+
+  /// Like [`Shape::map_children`], but `f` can return a child unchanged to
+  /// keep sharing it.
+  pub fn map_shared_children(
+    &self,
+    mut f: impl FnMut(&SharedShape) -> SharedShape,
+  ) -> Shape {
+    match self {
+      Shape::Expression { .. }
+      | Shape::XNode
+      | Shape::YNode
+      | Shape::ZNode
+      | Shape::Constant(_)
+      | Shape::Var(_) => self.clone(),
+      Shape::Add(lhs, rhs) => Shape::Add(f(lhs), f(rhs)),
+      Shape::Sub(lhs, rhs) => Shape::Sub(f(lhs), f(rhs)),
+      Shape::Mul(lhs, rhs) => Shape::Mul(f(lhs), f(rhs)),
+      Shape::Div(lhs, rhs) => Shape::Div(f(lhs), f(rhs)),
+      Shape::Min(lhs, rhs) => Shape::Min(f(lhs), f(rhs)),
+      Shape::Max(lhs, rhs) => Shape::Max(f(lhs), f(rhs)),
+      Shape::Neg(a) => Shape::Neg(f(a)),
+      Shape::Exp(a) => Shape::Exp(f(a)),
+      Shape::Sin(a) => Shape::Sin(f(a)),
+      Shape::Cos(a) => Shape::Cos(f(a)),
+      Shape::Recip(a) => Shape::Recip(f(a)),
+      Shape::Abs(a) => Shape::Abs(f(a)),
+      Shape::Sqrt(a) => Shape::Sqrt(f(a)),
+      Shape::Square(a) => Shape::Square(f(a)),
+      Shape::Remap {
+        root,
+        new_x,
+        new_y,
+        new_z,
+      } => Shape::Remap {
+        root:  f(root),
+        new_x: f(new_x),
+        new_y: f(new_y),
+        new_z: f(new_z),
+      },
+      Shape::Extra(compound) => Shape::Extra(compound.map_shared_children(f)),
+    }
+  }
 }
 
 impl IntoNode for &Shape {
   fn into_node(self, ctx: &mut Context) -> Result<Node, fidget::Error> {
+    self.eval_root_cached(ctx)
+  }
+}
+
+impl Shape {
+  /// Converts this shape's top node, converting its children through `cache`.
+  fn into_node_uncached(
+    &self,
+    ctx: &mut Context,
+    cache: &mut HashMap<u64, Node>,
+  ) -> Result<Node, fidget::Error> {
     match self {
       Shape::Expression { expr } => {
         let mut engine = Engine::new(Some(ctx.clone()));
@@ -128,33 +251,82 @@ impl IntoNode for &Shape {
       Shape::YNode => Ok(ctx.y()),
       Shape::ZNode => Ok(ctx.z()),
       Shape::Constant(c) => Ok(ctx.constant(*c)),
-      Shape::Add(lhs, rhs) => ctx.add(lhs.as_ref(), rhs.as_ref()),
-      Shape::Sub(lhs, rhs) => ctx.sub(lhs.as_ref(), rhs.as_ref()),
-      Shape::Mul(lhs, rhs) => ctx.mul(lhs.as_ref(), rhs.as_ref()),
-      Shape::Div(lhs, rhs) => ctx.div(lhs.as_ref(), rhs.as_ref()),
-      Shape::Min(lhs, rhs) => ctx.min(lhs.as_ref(), rhs.as_ref()),
-      Shape::Max(lhs, rhs) => ctx.max(lhs.as_ref(), rhs.as_ref()),
-      Shape::Neg(lhs) => ctx.neg(lhs.as_ref()),
-      Shape::Exp(lhs) => ctx.exp(lhs.as_ref()),
-      Shape::Sin(lhs) => ctx.sin(lhs.as_ref()),
-      Shape::Cos(lhs) => ctx.cos(lhs.as_ref()),
-      Shape::Recip(lhs) => ctx.recip(lhs.as_ref()),
-      Shape::Abs(lhs) => ctx.abs(lhs.as_ref()),
-      Shape::Sqrt(lhs) => ctx.sqrt(lhs.as_ref()),
-      Shape::Square(lhs) => ctx.square(lhs.as_ref()),
+      Shape::Add(lhs, rhs) => {
+        let lhs = lhs.cached_into_node(ctx, cache)?;
+        let rhs = rhs.cached_into_node(ctx, cache)?;
+        ctx.add(lhs, rhs)
+      }
+      Shape::Sub(lhs, rhs) => {
+        let lhs = lhs.cached_into_node(ctx, cache)?;
+        let rhs = rhs.cached_into_node(ctx, cache)?;
+        ctx.sub(lhs, rhs)
+      }
+      Shape::Mul(lhs, rhs) => {
+        let lhs = lhs.cached_into_node(ctx, cache)?;
+        let rhs = rhs.cached_into_node(ctx, cache)?;
+        ctx.mul(lhs, rhs)
+      }
+      Shape::Div(lhs, rhs) => {
+        let lhs = lhs.cached_into_node(ctx, cache)?;
+        let rhs = rhs.cached_into_node(ctx, cache)?;
+        ctx.div(lhs, rhs)
+      }
+      Shape::Min(lhs, rhs) => {
+        let lhs = lhs.cached_into_node(ctx, cache)?;
+        let rhs = rhs.cached_into_node(ctx, cache)?;
+        ctx.min(lhs, rhs)
+      }
+      Shape::Max(lhs, rhs) => {
+        let lhs = lhs.cached_into_node(ctx, cache)?;
+        let rhs = rhs.cached_into_node(ctx, cache)?;
+        ctx.max(lhs, rhs)
+      }
+      Shape::Neg(lhs) => {
+        let lhs = lhs.cached_into_node(ctx, cache)?;
+        ctx.neg(lhs)
+      }
+      Shape::Exp(lhs) => {
+        let lhs = lhs.cached_into_node(ctx, cache)?;
+        ctx.exp(lhs)
+      }
+      Shape::Sin(lhs) => {
+        let lhs = lhs.cached_into_node(ctx, cache)?;
+        ctx.sin(lhs)
+      }
+      Shape::Cos(lhs) => {
+        let lhs = lhs.cached_into_node(ctx, cache)?;
+        ctx.cos(lhs)
+      }
+      Shape::Recip(lhs) => {
+        let lhs = lhs.cached_into_node(ctx, cache)?;
+        ctx.recip(lhs)
+      }
+      Shape::Abs(lhs) => {
+        let lhs = lhs.cached_into_node(ctx, cache)?;
+        ctx.abs(lhs)
+      }
+      Shape::Sqrt(lhs) => {
+        let lhs = lhs.cached_into_node(ctx, cache)?;
+        ctx.sqrt(lhs)
+      }
+      Shape::Square(lhs) => {
+        let lhs = lhs.cached_into_node(ctx, cache)?;
+        ctx.square(lhs)
+      }
       Shape::Remap {
         root,
         new_x,
         new_y,
         new_z,
       } => {
-        let root_node = root.as_ref().into_node(ctx)?;
-        let new_x_node = new_x.as_ref().into_node(ctx)?;
-        let new_y_node = new_y.as_ref().into_node(ctx)?;
-        let new_z_node = new_z.as_ref().into_node(ctx)?;
+        let root_node = root.cached_into_node(ctx, cache)?;
+        let new_x_node = new_x.cached_into_node(ctx, cache)?;
+        let new_y_node = new_y.cached_into_node(ctx, cache)?;
+        let new_z_node = new_z.cached_into_node(ctx, cache)?;
         ctx.remap_xyz(root_node, [new_x_node, new_y_node, new_z_node])
       }
-      Shape::Extra(extra) => extra.into_node(ctx),
+      Shape::Extra(extra) => extra.cached_into_node(ctx, cache),
+      Shape::Var(name) => ctx.var(name),
     }
   }
 }
@@ -198,4 +370,15 @@ mod tests {
     let eval_result = ctx.eval_xyz(x_plus_one_times_y, 2.0, 3.0, 0.0).unwrap();
     assert_eq!(eval_result, 9.0);
   }
+
+  #[test]
+  fn reused_subtrees_are_only_converted_once() {
+    // as a tree this would hold 2^40 spheres, so every step has to share
+    let mut shape = builder::translate(builder::sphere(1.0), 1.0, 0.0, 0.0);
+    for _ in 0..40 {
+      shape = builder::min(shape.clone(), shape);
+    }
+    let value = shape.eval(glam::Vec3A::new(1.0, 0.0, 0.0)).unwrap();
+    assert!((value + 1.0).abs() < 1e-5);
+  }
 }
@@ -0,0 +1,142 @@
+//! Reflection for the [`SharedShape`] children of [`Shape`]s.
+//!
+//! A [`SharedShape`] is transparent to reflection: it reflects as the
+//! [`Shape`] it holds, so the whole tree can be browsed and edited in the
+//! inspector, and values built from a reflected `Shape` (like the dynamic
+//! values produced by `clone_value`) can be applied to or set on a child.
+//! Editing a child through reflection only changes that use of it, even if
+//! the subtree is shared with other shapes.
+
+use std::any::Any;
+
+use bevy_reflect::{
+  utility::NonGenericTypeInfoCell, FromReflect, GetTypeRegistration, Reflect,
+  ReflectMut, ReflectOwned, ReflectRef, TypeInfo, TypeRegistration, Typed,
+  ValueInfo,
+};
+
+use super::{Shape, SharedShape};
+
+impl Typed for SharedShape {
+  fn type_info() -> &'static TypeInfo {
+    // the child gets its own info so that registering it doesn't shadow
+    // `Shape`'s, but instances report `Shape`'s through
+    // `get_represented_type_info`
+    static CELL: NonGenericTypeInfoCell = NonGenericTypeInfoCell::new();
+    CELL.get_or_set(|| TypeInfo::Value(ValueInfo::new::<Self>()))
+  }
+}
+
+impl GetTypeRegistration for SharedShape {
+  fn get_type_registration() -> TypeRegistration {
+    TypeRegistration::of::<Self>()
+  }
+}
+
+impl Reflect for SharedShape {
+  fn get_represented_type_info(&self) -> Option<&'static TypeInfo> {
+    Some(<Shape as Typed>::type_info())
+  }
+
+  fn into_any(self: Box<Self>) -> Box<dyn Any> { self }
+
+  fn as_any(&self) -> &dyn Any { self }
+
+  fn as_any_mut(&mut self) -> &mut dyn Any { self }
+
+  fn into_reflect(self: Box<Self>) -> Box<dyn Reflect> { self }
+
+  fn as_reflect(&self) -> &dyn Reflect { self }
+
+  fn as_reflect_mut(&mut self) -> &mut dyn Reflect { self }
+
+  fn apply(&mut self, value: &dyn Reflect) { self.make_mut().apply(value) }
+
+  fn set(&mut self, value: Box<dyn Reflect>) -> Result<(), Box<dyn Reflect>> {
+    let value = match value.take::<Self>() {
+      Ok(shared) => shared,
+      Err(value) => SharedShape::new(value.take::<Shape>()?),
+    };
+    *self = value;
+    Ok(())
+  }
+
+  fn reflect_ref(&self) -> ReflectRef { (**self).reflect_ref() }
+
+  fn reflect_mut(&mut self) -> ReflectMut { self.make_mut().reflect_mut() }
+
+  fn reflect_owned(self: Box<Self>) -> ReflectOwned {
+    Reflect::reflect_owned(Box::new((*self).into_inner()))
+  }
+
+  fn clone_value(&self) -> Box<dyn Reflect> { (**self).clone_value() }
+
+  fn reflect_hash(&self) -> Option<u64> { (**self).reflect_hash() }
+
+  fn reflect_partial_eq(&self, value: &dyn Reflect) -> Option<bool> {
+    (**self).reflect_partial_eq(value)
+  }
+
+  fn debug(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    (**self).debug(f)
+  }
+}
+
+impl FromReflect for SharedShape {
+  fn from_reflect(reflect: &dyn Reflect) -> Option<Self> {
+    match reflect.downcast_ref::<Self>() {
+      Some(shared) => Some(shared.clone()),
+      None => Shape::from_reflect(reflect).map(SharedShape::new),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use bevy_reflect::{Enum, VariantType};
+
+  use super::*;
+  use crate::shape::builder;
+
+  #[test]
+  fn children_are_reflected() {
+    let mut shape = builder::translate(builder::sphere(1.0), 2.0, 0.0, 0.0);
+
+    // reach the sphere's radius through the remap's root
+    let ReflectMut::Enum(remap) = shape.reflect_mut() else {
+      panic!("shapes reflect as enums");
+    };
+    assert_eq!(remap.variant_name(), "Remap");
+    let root = remap.field_mut("root").unwrap();
+    let ReflectMut::Enum(extra) = root.reflect_mut() else {
+      panic!("children reflect as the shape they hold");
+    };
+    let ReflectMut::Enum(sphere) = extra.field_at_mut(0).unwrap().reflect_mut()
+    else {
+      panic!("compounds reflect as enums");
+    };
+    assert_eq!(sphere.variant_name(), "Sphere");
+    sphere
+      .field_mut("radius")
+      .unwrap()
+      .apply(&Shape::Constant(3.0));
+
+    let expected = builder::translate(builder::sphere(3.0), 2.0, 0.0, 0.0);
+    assert_eq!(format!("{shape:?}"), format!("{expected:?}"));
+  }
+
+  #[test]
+  fn whole_trees_round_trip() {
+    let shape = builder::min(
+      builder::sphere(1.0),
+      builder::cuboid(1.0, builder::var("h"), 1.0),
+    );
+    let dynamic = shape.clone_value();
+    assert!(matches!(
+      dynamic.reflect_ref(),
+      ReflectRef::Enum(e) if e.variant_type() == VariantType::Tuple
+    ));
+    let read = Shape::from_reflect(dynamic.as_ref()).unwrap();
+    assert_eq!(format!("{read:?}"), format!("{shape:?}"));
+  }
+}
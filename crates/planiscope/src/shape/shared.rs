@@ -0,0 +1,170 @@
+//! Reference-counted children of [`Shape`]s.
+//!
+//! Shapes are trees of [`SharedShape`]s, so cloning a shape to reuse it (like
+//! the same hole cut into every brick of a wall) only copies its top node, and
+//! every use points at the same subtree. Each subtree also caches its hash and
+//! its simplified form, so hashing or simplifying a shape that reuses a
+//! subtree many times only does that subtree's work once. Together with
+//! [`CachedIntoNode`](super::CachedIntoNode), which looks every subtree up by
+//! its hash, identical subtrees become a single fidget `Node`.
+
+use std::{
+  collections::hash_map::DefaultHasher,
+  fmt,
+  hash::{Hash, Hasher},
+  ops::Deref,
+  sync::{Arc, OnceLock},
+};
+
+use bevy_reflect::TypePath;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::Shape;
+
+/// A child of a [`Shape`], shared between every shape that it was cloned into.
+#[derive(Clone, TypePath)]
+pub struct SharedShape(Arc<Inner>);
+
+#[derive(Clone)]
+struct Inner {
+  shape:      Shape,
+  hash:       OnceLock<u64>,
+  simplified: OnceLock<SharedShape>,
+}
+
+impl SharedShape {
+  pub fn new(shape: Shape) -> Self {
+    Self(Arc::new(Inner {
+      shape,
+      hash: OnceLock::new(),
+      simplified: OnceLock::new(),
+    }))
+  }
+
+  /// Returns the shape, without cloning it if this is the only reference.
+  pub fn into_inner(self) -> Shape {
+    match Arc::try_unwrap(self.0) {
+      Ok(inner) => inner.shape,
+      Err(inner) => inner.shape.clone(),
+    }
+  }
+
+  /// Returns a mutable reference to the shape, first cloning it if it's
+  /// shared so that other shapes aren't changed.
+  pub fn make_mut(&mut self) -> &mut Shape {
+    let inner = Arc::make_mut(&mut self.0);
+    inner.hash = OnceLock::new();
+    inner.simplified = OnceLock::new();
+    &mut inner.shape
+  }
+
+  /// Returns whether both point at the same subtree.
+  pub fn ptr_eq(&self, other: &Self) -> bool { Arc::ptr_eq(&self.0, &other.0) }
+
+  /// Returns the hash of the shape, computing it on first use.
+  pub fn content_hash(&self) -> u64 {
+    *self.0.hash.get_or_init(|| {
+      let mut hasher = DefaultHasher::new();
+      self.0.shape.hash(&mut hasher);
+      hasher.finish()
+    })
+  }
+
+  /// Returns the simplified shape, simplifying it on first use. Every use of
+  /// a shared subtree gets the same simplified subtree back.
+  pub fn simplified(&self) -> SharedShape {
+    self
+      .0
+      .simplified
+      .get_or_init(|| SharedShape::new(self.0.shape.simplify()))
+      .clone()
+  }
+
+  /// Like [`Shape::simplify`], but only simplifies a shared subtree once.
+  /// This shadows `Shape::simplify` for children.
+  pub fn simplify(&self) -> Shape { self.simplified().as_ref().clone() }
+}
+
+impl Deref for SharedShape {
+  type Target = Shape;
+
+  fn deref(&self) -> &Shape { &self.0.shape }
+}
+
+impl AsRef<Shape> for SharedShape {
+  fn as_ref(&self) -> &Shape { &self.0.shape }
+}
+
+impl From<Shape> for SharedShape {
+  fn from(shape: Shape) -> Self { Self::new(shape) }
+}
+
+impl Hash for SharedShape {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    state.write_u64(self.content_hash())
+  }
+}
+
+impl fmt::Debug for SharedShape {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fmt::Debug::fmt(&self.0.shape, f)
+  }
+}
+
+impl fmt::Display for SharedShape {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fmt::Display::fmt(&self.0.shape, f)
+  }
+}
+
+impl Serialize for SharedShape {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    self.0.shape.serialize(serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for SharedShape {
+  fn deserialize<D: Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<Self, D::Error> {
+    Shape::deserialize(deserializer).map(Self::new)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::shape::builder;
+
+  #[test]
+  fn clones_share_subtrees() {
+    let hole = builder::translate(builder::cylinder(2.0, 0.5), 1.0, 0.0, 0.0);
+    let holes = builder::min(hole.clone(), hole);
+    let Shape::Min(a, b) = &holes else {
+      panic!("min builds a min");
+    };
+    // only the top node is copied; everything below it is the same subtree
+    assert!(!a.ptr_eq(b));
+    assert!(a
+      .children()
+      .into_iter()
+      .zip(b.children())
+      .all(|(a, b)| std::ptr::eq(a, b)));
+  }
+
+  #[test]
+  fn mutation_leaves_other_clones_alone() {
+    let shared = SharedShape::new(builder::sphere(1.0));
+    let mut edited = shared.clone();
+    let before = shared.content_hash();
+    *edited.make_mut() = builder::sphere(2.0);
+
+    assert!(!shared.ptr_eq(&edited));
+    assert_eq!(shared.content_hash(), before);
+    assert_ne!(edited.content_hash(), before);
+    assert_eq!(
+      format!("{:?}", edited.simplify()),
+      format!("{:?}", builder::sphere(2.0))
+    );
+  }
+}
@@ -0,0 +1,403 @@
+//! Algebraic simplification of [`Shape`] trees.
+
+use super::{Shape, SharedShape};
+
+/// An affine function of the coordinates, as the coefficients of X, Y and Z
+/// followed by a constant term.
+pub(super) type Affine = [f64; 4];
+
+const X_AFFINE: Affine = [1.0, 0.0, 0.0, 0.0];
+const Y_AFFINE: Affine = [0.0, 1.0, 0.0, 0.0];
+const Z_AFFINE: Affine = [0.0, 0.0, 1.0, 0.0];
+
+impl Shape {
+  /// Returns an equivalent shape with constants folded, identity operations
+  /// removed, and consecutive affine remaps (i.e. stacked `translate` and
+  /// `scale` calls) fused into one.
+  ///
+  /// Expressions are parsed into native trees where possible. Affine remaps
+  /// are also rewritten into a canonical form, so equivalent shapes built in
+  /// different ways simplify to the same tree. This is why shapes should be
+  /// simplified before they're hashed for a cache key.
+  pub fn simplify(&self) -> Shape {
+    match self {
+      // expressions that can't be parsed are left for rhai to report on
+      Shape::Expression { expr } => match Shape::parse(expr) {
+        Ok(shape) => shape.simplify(),
+        Err(_) => self.clone(),
+      },
+      Shape::XNode
+      | Shape::YNode
+      | Shape::ZNode
+      | Shape::Constant(_)
+      | Shape::Var(_) => self.clone(),
+      Shape::Add(lhs, rhs) => simplify_add(lhs.simplify(), rhs.simplify()),
+      Shape::Sub(lhs, rhs) => simplify_sub(lhs.simplify(), rhs.simplify()),
+      Shape::Mul(lhs, rhs) => simplify_mul(lhs.simplify(), rhs.simplify()),
+      Shape::Div(lhs, rhs) => simplify_div(lhs.simplify(), rhs.simplify()),
+      Shape::Min(lhs, rhs) => match (lhs.simplify(), rhs.simplify()) {
+        (Shape::Constant(a), Shape::Constant(b)) => Shape::Constant(a.min(b)),
+        (lhs, rhs) => Shape::Min(SharedShape::new(lhs), SharedShape::new(rhs)),
+      },
+      Shape::Max(lhs, rhs) => match (lhs.simplify(), rhs.simplify()) {
+        (Shape::Constant(a), Shape::Constant(b)) => Shape::Constant(a.max(b)),
+        (lhs, rhs) => Shape::Max(SharedShape::new(lhs), SharedShape::new(rhs)),
+      },
+      Shape::Neg(a) => simplify_neg(a.simplify()),
+      Shape::Exp(a) => match a.simplify() {
+        Shape::Constant(c) => Shape::Constant(c.exp()),
+        a => Shape::Exp(SharedShape::new(a)),
+      },
+      Shape::Sin(a) => match a.simplify() {
+        Shape::Constant(c) => Shape::Constant(c.sin()),
+        a => Shape::Sin(SharedShape::new(a)),
+      },
+      Shape::Cos(a) => match a.simplify() {
+        Shape::Constant(c) => Shape::Constant(c.cos()),
+        a => Shape::Cos(SharedShape::new(a)),
+      },
+      Shape::Recip(a) => match a.simplify() {
+        // leave a division by zero in place so that it can be reported
+        Shape::Constant(c) if c != 0.0 => Shape::Constant(c.recip()),
+        a => Shape::Recip(SharedShape::new(a)),
+      },
+      Shape::Abs(a) => match a.simplify() {
+        Shape::Constant(c) => Shape::Constant(c.abs()),
+        Shape::Neg(a) => Shape::Abs(a),
+        a @ (Shape::Abs(_) | Shape::Square(_) | Shape::Sqrt(_)) => a,
+        a => Shape::Abs(SharedShape::new(a)),
+      },
+      Shape::Sqrt(a) => match a.simplify() {
+        Shape::Constant(c) if c >= 0.0 => Shape::Constant(c.sqrt()),
+        a => Shape::Sqrt(SharedShape::new(a)),
+      },
+      Shape::Square(a) => match a.simplify() {
+        Shape::Constant(c) => Shape::Constant(c * c),
+        Shape::Neg(a) | Shape::Abs(a) => Shape::Square(a),
+        a => Shape::Square(SharedShape::new(a)),
+      },
+      Shape::Remap {
+        root,
+        new_x,
+        new_y,
+        new_z,
+      } => simplify_remap(
+        root.simplify(),
+        new_x.simplify(),
+        new_y.simplify(),
+        new_z.simplify(),
+      ),
+      Shape::Extra(extra) => {
+        Shape::Extra(extra.map_shared_children(SharedShape::simplified))
+      }
+    }
+  }
+}
+
+fn simplify_add(lhs: Shape, rhs: Shape) -> Shape {
+  match (lhs, rhs) {
+    (Shape::Constant(a), Shape::Constant(b)) => Shape::Constant(a + b),
+    (a, Shape::Constant(c)) | (Shape::Constant(c), a) if c == 0.0 => a,
+    (lhs, rhs) => Shape::Add(SharedShape::new(lhs), SharedShape::new(rhs)),
+  }
+}
+
+fn simplify_sub(lhs: Shape, rhs: Shape) -> Shape {
+  match (lhs, rhs) {
+    (Shape::Constant(a), Shape::Constant(b)) => Shape::Constant(a - b),
+    (a, Shape::Constant(c)) if c == 0.0 => a,
+    (Shape::Constant(c), a) if c == 0.0 => simplify_neg(a),
+    (lhs, rhs) => Shape::Sub(SharedShape::new(lhs), SharedShape::new(rhs)),
+  }
+}
+
+fn simplify_mul(lhs: Shape, rhs: Shape) -> Shape {
+  match (lhs, rhs) {
+    (Shape::Constant(a), Shape::Constant(b)) => Shape::Constant(a * b),
+    (a, Shape::Constant(c)) | (Shape::Constant(c), a) if c == 1.0 => a,
+    (a, Shape::Constant(c)) | (Shape::Constant(c), a) if c == -1.0 => {
+      simplify_neg(a)
+    }
+    (lhs, rhs) => Shape::Mul(SharedShape::new(lhs), SharedShape::new(rhs)),
+  }
+}
+
+fn simplify_div(lhs: Shape, rhs: Shape) -> Shape {
+  match (lhs, rhs) {
+    // leave a division by zero in place so that it can be reported
+    (Shape::Constant(a), Shape::Constant(b)) if b != 0.0 => {
+      Shape::Constant(a / b)
+    }
+    (a, Shape::Constant(c)) if c == 1.0 => a,
+    (lhs, rhs) => Shape::Div(SharedShape::new(lhs), SharedShape::new(rhs)),
+  }
+}
+
+fn simplify_neg(a: Shape) -> Shape {
+  match a {
+    Shape::Constant(c) => Shape::Constant(-c),
+    Shape::Neg(a) => a.into_inner(),
+    a => Shape::Neg(SharedShape::new(a)),
+  }
+}
+
+fn simplify_remap(root: Shape, x: Shape, y: Shape, z: Shape) -> Shape {
+  // remapping something that doesn't depend on the coordinates, or is a bare
+  // coordinate, doesn't need a remap.
+  match root {
+    Shape::Constant(_) | Shape::Var(_) => return root,
+    Shape::XNode => return x,
+    Shape::YNode => return y,
+    Shape::ZNode => return z,
+    _ => {}
+  }
+
+  let outer = [affine(&x), affine(&y), affine(&z)];
+  let [Some(outer_x), Some(outer_y), Some(outer_z)] = outer else {
+    return Shape::Remap {
+      root:  SharedShape::new(root),
+      new_x: SharedShape::new(x),
+      new_y: SharedShape::new(y),
+      new_z: SharedShape::new(z),
+    };
+  };
+  let outer = [outer_x, outer_y, outer_z];
+
+  // fuse with an inner affine remap
+  let (root, coords) = match root {
+    Shape::Remap {
+      root: inner_root,
+      new_x,
+      new_y,
+      new_z,
+    } => match [affine(&new_x), affine(&new_y), affine(&new_z)] {
+      [Some(a), Some(b), Some(c)] => (
+        inner_root.into_inner(),
+        [a, b, c].map(|inner| compose(inner, outer)),
+      ),
+      _ => (
+        Shape::Remap {
+          root: inner_root,
+          new_x,
+          new_y,
+          new_z,
+        },
+        outer,
+      ),
+    },
+    root => (root, outer),
+  };
+
+  if coords == [X_AFFINE, Y_AFFINE, Z_AFFINE] {
+    return root;
+  }
+
+  Shape::Remap {
+    root:  SharedShape::new(root),
+    new_x: SharedShape::new(affine_to_shape(coords[0])),
+    new_y: SharedShape::new(affine_to_shape(coords[1])),
+    new_z: SharedShape::new(affine_to_shape(coords[2])),
+  }
+}
+
+/// Returns the affine form of `shape`, if it's an affine function of the
+/// coordinates.
+pub(super) fn affine(shape: &Shape) -> Option<Affine> {
+  let constant = |a: &Affine| (a[0..3] == [0.0; 3]).then_some(a[3]);
+  match shape {
+    Shape::XNode => Some(X_AFFINE),
+    Shape::YNode => Some(Y_AFFINE),
+    Shape::ZNode => Some(Z_AFFINE),
+    Shape::Constant(c) => Some([0.0, 0.0, 0.0, *c]),
+    Shape::Add(lhs, rhs) => {
+      let (a, b) = (affine(lhs)?, affine(rhs)?);
+      Some([a[0] + b[0], a[1] + b[1], a[2] + b[2], a[3] + b[3]])
+    }
+    Shape::Sub(lhs, rhs) => {
+      let (a, b) = (affine(lhs)?, affine(rhs)?);
+      Some([a[0] - b[0], a[1] - b[1], a[2] - b[2], a[3] - b[3]])
+    }
+    Shape::Neg(a) => Some(affine(a)?.map(|v| -v)),
+    Shape::Mul(lhs, rhs) => {
+      let (a, b) = (affine(lhs)?, affine(rhs)?);
+      match (constant(&a), constant(&b)) {
+        (_, Some(k)) => Some(a.map(|v| v * k)),
+        (Some(k), _) => Some(b.map(|v| v * k)),
+        _ => None,
+      }
+    }
+    Shape::Div(lhs, rhs) => {
+      let (a, k) = (affine(lhs)?, constant(&affine(rhs)?)?);
+      (k != 0.0).then(|| a.map(|v| v / k))
+    }
+    _ => None,
+  }
+}
+
+/// Composes `inner` after `outer`, i.e. evaluates `inner` at the point given
+/// by `outer`.
+fn compose(inner: Affine, outer: [Affine; 3]) -> Affine {
+  let mut result = [0.0, 0.0, 0.0, inner[3]];
+  for (k, axis) in inner[0..3].iter().zip(outer) {
+    for i in 0..4 {
+      result[i] += k * axis[i];
+    }
+  }
+  result
+}
+
+/// Writes an affine function out as a shape, in a canonical form.
+pub(super) fn affine_to_shape(a: Affine) -> Shape {
+  let mut acc: Option<Shape> = None;
+  for (k, axis) in
+    a[0..3]
+      .iter()
+      .zip([Shape::XNode, Shape::YNode, Shape::ZNode])
+  {
+    if *k == 0.0 {
+      continue;
+    }
+    let term = if k.abs() == 1.0 {
+      axis
+    } else {
+      Shape::Mul(
+        SharedShape::new(axis),
+        SharedShape::new(Shape::Constant(k.abs())),
+      )
+    };
+    acc = Some(match (acc, *k < 0.0) {
+      (None, false) => term,
+      (None, true) => Shape::Neg(SharedShape::new(term)),
+      (Some(acc), false) => {
+        Shape::Add(SharedShape::new(acc), SharedShape::new(term))
+      }
+      (Some(acc), true) => {
+        Shape::Sub(SharedShape::new(acc), SharedShape::new(term))
+      }
+    });
+  }
+
+  let constant = a[3];
+  match acc {
+    None => Shape::Constant(constant),
+    Some(acc) if constant > 0.0 => Shape::Add(
+      SharedShape::new(acc),
+      SharedShape::new(Shape::Constant(constant)),
+    ),
+    Some(acc) if constant < 0.0 => Shape::Sub(
+      SharedShape::new(acc),
+      SharedShape::new(Shape::Constant(-constant)),
+    ),
+    Some(acc) => acc,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use fidget::{context::IntoNode, Context};
+
+  use super::*;
+  use crate::shape::builder;
+
+  fn eval(shape: &Shape, x: f64, y: f64, z: f64) -> f64 {
+    let mut ctx = Context::new();
+    let node = shape.into_node(&mut ctx).unwrap();
+    ctx.eval_xyz(node, x, y, z).unwrap()
+  }
+
+  fn assert_same_tree(a: &Shape, b: &Shape) {
+    assert_eq!(format!("{:?}", a), format!("{:?}", b));
+  }
+
+  #[test]
+  fn constants_fold() {
+    let shape = builder::add(
+      builder::mul(2.0, 3.0),
+      builder::sqrt(builder::square(builder::neg(4.0))),
+    );
+    assert_same_tree(&shape.simplify(), &Shape::Constant(10.0));
+  }
+
+  #[test]
+  fn identities_are_removed() {
+    let shape = builder::div(
+      builder::mul(builder::add(builder::x(), 0.0), 1.0),
+      builder::constant(1.0),
+    );
+    assert_same_tree(&shape.simplify(), &builder::x());
+
+    let shape = builder::neg(builder::neg(builder::sphere(1.0)));
+    assert_same_tree(&shape.simplify(), &builder::sphere(1.0));
+
+    let shape = builder::translate(builder::sphere(1.0), 0.0, 0.0, 0.0);
+    assert_same_tree(&shape.simplify(), &builder::sphere(1.0));
+  }
+
+  #[test]
+  fn division_by_zero_is_kept() {
+    let shape = builder::div(1.0, 0.0);
+    assert_same_tree(&shape.simplify(), &shape);
+  }
+
+  #[test]
+  fn stacked_translates_fuse() {
+    let stacked = builder::translate(
+      builder::translate(builder::sphere(1.0), 1.0, 0.0, -2.0),
+      1.0,
+      3.0,
+      0.0,
+    );
+    let single = builder::translate(builder::sphere(1.0), 2.0, 3.0, -2.0);
+    assert_same_tree(&stacked.simplify(), &single.simplify());
+
+    let undone = builder::translate(
+      builder::translate(builder::sphere(1.0), 1.0, 2.0, 3.0),
+      -1.0,
+      -2.0,
+      -3.0,
+    );
+    assert_same_tree(&undone.simplify(), &builder::sphere(1.0));
+  }
+
+  #[test]
+  fn fused_remaps_evaluate_identically() {
+    let shape = builder::scale(
+      builder::translate(
+        builder::scale(builder::cuboid(1.0, 2.0, 0.5), 2.0, 1.0, 0.5),
+        0.5,
+        -1.0,
+        2.0,
+      ),
+      1.5,
+      3.0,
+      0.25,
+    );
+    let simplified = shape.simplify();
+    assert!(matches!(
+      simplified,
+      Shape::Remap { ref root, .. } if !matches!(**root, Shape::Remap { .. })
+    ));
+
+    for point in [[0.0, 0.0, 0.0], [1.0, -2.0, 0.5], [-3.0, 0.25, 1.0]] {
+      let expected = eval(&shape, point[0], point[1], point[2]);
+      let actual = eval(&simplified, point[0], point[1], point[2]);
+      assert!((expected - actual).abs() < 1e-6);
+    }
+  }
+
+  #[test]
+  fn simplification_is_idempotent() {
+    let shape = builder::smooth_min_cubic(
+      builder::translate(
+        builder::scale(builder::sphere(1.0), 2.0, 2.0, 2.0),
+        1.0,
+        0.0,
+        0.0,
+      ),
+      builder::sub(builder::y(), builder::add(1.0, 1.0)),
+      0.5,
+    );
+    let once = shape.simplify();
+    assert_same_tree(&once, &once.simplify());
+  }
+}
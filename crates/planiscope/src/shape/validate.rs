@@ -0,0 +1,468 @@
+//! Up-front checks for [`Shape`]s that would fail or produce garbage when
+//! meshed.
+
+use std::{collections::BTreeSet, fmt};
+
+use super::{
+  compound::Compound, expr::is_var_name, simplify::affine, Shape,
+  MAX_GRID_SAMPLES,
+};
+use crate::nso::grid::sample_count;
+
+/// A kind of problem found by [`Shape::validate`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValidationErrorKind {
+  /// A constant is NaN.
+  NanConstant,
+  /// A division's right-hand side is a constant zero.
+  DivisionByZero,
+  /// A reciprocal is taken of a constant zero.
+  ReciprocalOfZero,
+  /// A remap sends every point to the same coordinate along an axis, e.g. a
+  /// scale by zero.
+  ZeroScale { axis: char },
+  /// A matrix transform can't be inverted.
+  SingularTransform,
+  /// A sampled grid has a different number of samples than its size needs.
+  WrongSampleCount { expected: usize, found: usize },
+  /// A sampled grid is larger than
+  /// [`MAX_GRID_SAMPLES`](super::MAX_GRID_SAMPLES) allows.
+  GridTooLarge { size: Vec<u32> },
+  /// An expression couldn't be parsed. Holds the parser's message.
+  InvalidExpression(String),
+  /// A [`Shape::Var`]'s name isn't an identifier, or is reserved (like `x`),
+  /// so it can't be told apart from other shapes when printed.
+  InvalidVarName(String),
+  /// A [`Shape::Var`] has no binding. Only reported by
+  /// [`MesherInputs::validate`](crate::mesher::MesherInputs::validate).
+  UnboundVariable(String),
+}
+
+impl fmt::Display for ValidationErrorKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::NanConstant => write!(f, "Constant is NaN"),
+      Self::DivisionByZero => write!(f, "Div rhs is Constant(0)"),
+      Self::ReciprocalOfZero => write!(f, "Recip of Constant(0)"),
+      Self::ZeroScale { axis } => write!(f, "Remap collapses the {axis} axis"),
+      Self::SingularTransform => write!(f, "MatTransform matrix is singular"),
+      Self::WrongSampleCount { expected, found } => {
+        write!(f, "Grid has {found} samples instead of {expected}")
+      }
+      Self::GridTooLarge { size } => {
+        write!(f, "Grid of {size:?} samples is too large")
+      }
+      Self::InvalidExpression(message) => {
+        write!(f, "Expression is invalid: {message}")
+      }
+      Self::InvalidVarName(name) => {
+        write!(f, "Var name `{name}` is reserved or not an identifier")
+      }
+      Self::UnboundVariable(name) => write!(f, "Var `{name}` is unbound"),
+    }
+  }
+}
+
+/// A problem found by [`Shape::validate`], along with where it is.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationError {
+  /// The fields followed from the root to reach the problem, e.g.
+  /// `["Remap.new_x", "Div.lhs"]`.
+  pub path: Vec<String>,
+  pub kind: ValidationErrorKind,
+}
+
+impl fmt::Display for ValidationError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for segment in &self.path {
+      write!(f, "{segment} -> ")?;
+    }
+    write!(f, "{}", self.kind)
+  }
+}
+
+/// Every problem found by [`Shape::validate`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationReport(pub Vec<ValidationError>);
+
+impl fmt::Display for ValidationReport {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "shape has {} problem(s)", self.0.len())?;
+    for error in &self.0 {
+      write!(f, "\n  {error}")?;
+    }
+    Ok(())
+  }
+}
+
+impl std::error::Error for ValidationReport {}
+
+impl Shape {
+  /// Checks the shape for problems that would make meshing fail or produce
+  /// garbage, such as NaN constants or divisions by a constant zero.
+  pub fn validate(&self) -> Result<(), ValidationReport> {
+    let mut errors = Vec::new();
+    validate(self, &mut Vec::new(), &mut errors);
+    if errors.is_empty() {
+      Ok(())
+    } else {
+      Err(ValidationReport(errors))
+    }
+  }
+
+  /// Returns the names of every [`Shape::Var`] that the simplified shape
+  /// depends on.
+  pub fn vars(&self) -> BTreeSet<String> {
+    let mut vars = BTreeSet::new();
+    collect_vars(&self.simplify(), &mut vars);
+    vars
+  }
+}
+
+fn collect_vars(shape: &Shape, vars: &mut BTreeSet<String>) {
+  match shape {
+    Shape::Var(name) => {
+      vars.insert(name.clone());
+    }
+    Shape::Expression { .. }
+    | Shape::XNode
+    | Shape::YNode
+    | Shape::ZNode
+    | Shape::Constant(_) => {}
+    Shape::Add(lhs, rhs)
+    | Shape::Sub(lhs, rhs)
+    | Shape::Mul(lhs, rhs)
+    | Shape::Div(lhs, rhs)
+    | Shape::Min(lhs, rhs)
+    | Shape::Max(lhs, rhs) => {
+      collect_vars(lhs, vars);
+      collect_vars(rhs, vars);
+    }
+    Shape::Neg(a)
+    | Shape::Exp(a)
+    | Shape::Sin(a)
+    | Shape::Cos(a)
+    | Shape::Recip(a)
+    | Shape::Abs(a)
+    | Shape::Sqrt(a)
+    | Shape::Square(a) => collect_vars(a, vars),
+    Shape::Remap {
+      root,
+      new_x,
+      new_y,
+      new_z,
+    } => {
+      for child in [root, new_x, new_y, new_z] {
+        collect_vars(child, vars);
+      }
+    }
+    Shape::Extra(compound) => {
+      for (_, child) in compound.children() {
+        collect_vars(child, vars);
+      }
+    }
+  }
+}
+
+fn is_zero(shape: &Shape) -> bool {
+  matches!(shape, Shape::Constant(c) if *c == 0.0)
+}
+
+fn validate(
+  shape: &Shape,
+  path: &mut Vec<String>,
+  errors: &mut Vec<ValidationError>,
+) {
+  let mut report = |path: &Vec<String>, kind| {
+    errors.push(ValidationError {
+      path: path.clone(),
+      kind,
+    })
+  };
+
+  let children: Vec<(String, &Shape)> = match shape {
+    Shape::Expression { expr } => match Shape::parse(expr) {
+      Ok(parsed) => {
+        path.push("Expression".to_string());
+        validate(&parsed, path, errors);
+        path.pop();
+        return;
+      }
+      Err(error) => {
+        // the parser only knows a subset of rhai, so defer to rhai itself
+        // before reporting
+        let mut engine = fidget::rhai::Engine::new(None);
+        if let Err(rhai_error) = engine.eval_no_clear(expr) {
+          report(
+            path,
+            ValidationErrorKind::InvalidExpression(format!(
+              "{error} ({rhai_error})"
+            )),
+          );
+        }
+        return;
+      }
+    },
+    Shape::XNode | Shape::YNode | Shape::ZNode => return,
+    Shape::Var(name) => {
+      if !is_var_name(name) {
+        report(path, ValidationErrorKind::InvalidVarName(name.clone()));
+      }
+      return;
+    }
+    Shape::Constant(c) => {
+      if c.is_nan() {
+        report(path, ValidationErrorKind::NanConstant);
+      }
+      return;
+    }
+    Shape::Add(lhs, rhs) => binary("Add", lhs, rhs),
+    Shape::Sub(lhs, rhs) => binary("Sub", lhs, rhs),
+    Shape::Mul(lhs, rhs) => binary("Mul", lhs, rhs),
+    Shape::Div(lhs, rhs) => {
+      if is_zero(rhs) {
+        report(path, ValidationErrorKind::DivisionByZero);
+      }
+      binary("Div", lhs, rhs)
+    }
+    Shape::Min(lhs, rhs) => binary("Min", lhs, rhs),
+    Shape::Max(lhs, rhs) => binary("Max", lhs, rhs),
+    Shape::Neg(a) => vec![("Neg".to_string(), a.as_ref())],
+    Shape::Exp(a) => vec![("Exp".to_string(), a.as_ref())],
+    Shape::Sin(a) => vec![("Sin".to_string(), a.as_ref())],
+    Shape::Cos(a) => vec![("Cos".to_string(), a.as_ref())],
+    Shape::Recip(a) => {
+      if is_zero(a) {
+        report(path, ValidationErrorKind::ReciprocalOfZero);
+      }
+      vec![("Recip".to_string(), a.as_ref())]
+    }
+    Shape::Abs(a) => vec![("Abs".to_string(), a.as_ref())],
+    Shape::Sqrt(a) => vec![("Sqrt".to_string(), a.as_ref())],
+    Shape::Square(a) => vec![("Square".to_string(), a.as_ref())],
+    Shape::Remap {
+      root,
+      new_x,
+      new_y,
+      new_z,
+    } => {
+      for (axis, coord) in [('x', new_x), ('y', new_y), ('z', new_z)] {
+        // a coordinate with no linear terms no longer depends on the sample
+        // point at all
+        if matches!(affine(coord), Some(a) if a[0..3] == [0.0; 3]) {
+          report(path, ValidationErrorKind::ZeroScale { axis });
+        }
+      }
+      vec![
+        ("Remap.root".to_string(), root.as_ref()),
+        ("Remap.new_x".to_string(), new_x.as_ref()),
+        ("Remap.new_y".to_string(), new_y.as_ref()),
+        ("Remap.new_z".to_string(), new_z.as_ref()),
+      ]
+    }
+    Shape::Extra(compound) => {
+      if let Compound::MatTransform { mat, .. } = compound {
+        let determinant = mat.determinant();
+        if determinant == 0.0 || !determinant.is_finite() {
+          report(path, ValidationErrorKind::SingularTransform);
+        }
+      }
+      let grid = match compound {
+        Compound::DistanceGrid { size, values, .. } => {
+          Some((&size[..], values))
+        }
+        Compound::Heightmap { size, heights, .. } => Some((&size[..], heights)),
+        _ => None,
+      };
+      if let Some((size, samples)) = grid {
+        match sample_count(size) {
+          Some(expected) if expected <= MAX_GRID_SAMPLES => {
+            if samples.len() != expected {
+              report(path, ValidationErrorKind::WrongSampleCount {
+                expected,
+                found: samples.len(),
+              });
+            }
+          }
+          _ => report(path, ValidationErrorKind::GridTooLarge {
+            size: size.to_vec(),
+          }),
+        }
+      }
+      compound
+        .children()
+        .into_iter()
+        .map(|(field, child)| (format!("{}.{field}", compound.name()), child))
+        .collect()
+    }
+  };
+
+  for (segment, child) in children {
+    path.push(segment);
+    validate(child, path, errors);
+    path.pop();
+  }
+}
+
+fn binary<'a>(
+  name: &str,
+  lhs: &'a Shape,
+  rhs: &'a Shape,
+) -> Vec<(String, &'a Shape)> {
+  vec![(format!("{name}.lhs"), lhs), (format!("{name}.rhs"), rhs)]
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::shape::builder;
+
+  fn problems(shape: &Shape) -> Vec<String> {
+    match shape.validate() {
+      Ok(()) => Vec::new(),
+      Err(report) => report.0.iter().map(ToString::to_string).collect(),
+    }
+  }
+
+  #[test]
+  fn valid_shapes_pass() {
+    let shape = builder::translate(
+      builder::smooth_min_cubic(
+        builder::sphere(1.0),
+        builder::cuboid(1.0, 2.0, 3.0),
+        0.5,
+      ),
+      1.0,
+      2.0,
+      3.0,
+    );
+    assert_eq!(shape.validate(), Ok(()));
+    assert_eq!(
+      builder::expr("sqrt(square(x) + square(y)) - 1").validate(),
+      Ok(())
+    );
+  }
+
+  #[test]
+  fn zero_scale_is_reported_with_a_path() {
+    let shape = builder::scale(builder::sphere(1.0), 0.0, 1.0, 1.0);
+    assert_eq!(problems(&shape), vec![
+      "Remap.new_x -> Div rhs is Constant(0)"
+    ]);
+  }
+
+  #[test]
+  fn nan_constants_and_reciprocals_are_reported() {
+    let shape = builder::min(builder::sphere(f64::NAN), builder::recip(0.0));
+    assert_eq!(problems(&shape), vec![
+      "Min.lhs -> Sphere.radius -> Constant is NaN",
+      "Min.rhs -> Recip of Constant(0)",
+    ]);
+  }
+
+  #[test]
+  fn collapsed_axes_and_singular_matrices_are_reported() {
+    let shape = builder::remap(
+      builder::sphere(1.0),
+      builder::x(),
+      2.0,
+      builder::mul(builder::z(), 0.0),
+    );
+    assert_eq!(problems(&shape), vec![
+      "Remap collapses the y axis",
+      "Remap collapses the z axis",
+    ]);
+
+    let shape = builder::transform(
+      builder::sphere(1.0),
+      glam::Mat4::from_scale(glam::Vec3::new(1.0, 0.0, 1.0)),
+    );
+    assert_eq!(problems(&shape), vec!["MatTransform matrix is singular"]);
+  }
+
+  #[test]
+  fn mismatched_grids_are_reported() {
+    let shape = Shape::Extra(Compound::Heightmap {
+      size:    [3, 2],
+      extent:  [1.0, 1.0],
+      heights: vec![0.0; 4],
+    });
+    assert_eq!(problems(&shape), vec!["Grid has 4 samples instead of 6"]);
+
+    let shape = Shape::Extra(Compound::Heightmap {
+      size:    [1024, 1024],
+      extent:  [1.0, 1.0],
+      heights: Vec::new(),
+    });
+    assert_eq!(problems(&shape), vec![
+      "Grid of [1024, 1024] samples is too large"
+    ]);
+  }
+
+  #[test]
+  fn bad_expressions_are_reported() {
+    let shape = builder::expr("x + ");
+    let problems = problems(&shape);
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].starts_with("Expression is invalid"));
+
+    let shape = builder::expr("x / 0");
+    assert_eq!(problems(&shape), vec![
+      "Expression -> Div rhs is Constant(0)"
+    ]);
+  }
+
+  #[test]
+  fn invalid_var_names_are_reported() {
+    let shape = builder::add(
+      Shape::Var("x".to_string()),
+      builder::sphere(Shape::Var("a b".to_string())),
+    );
+    assert_eq!(problems(&shape), vec![
+      "Add.lhs -> Var name `x` is reserved or not an identifier",
+      "Add.rhs -> Sphere.radius -> Var name `a b` is reserved or not an \
+       identifier",
+    ]);
+  }
+
+  #[test]
+  fn unbound_vars_are_reported_by_inputs() {
+    use crate::mesher::{MesherDetail, MesherInputs, MesherRegion};
+
+    let shape = builder::remap(
+      builder::sphere(builder::var("radius")),
+      builder::x(),
+      builder::sub(builder::y(), builder::var("height")),
+      builder::z(),
+    );
+    assert_eq!(shape.validate(), Ok(()));
+    assert_eq!(shape.vars().into_iter().collect::<Vec<_>>(), vec![
+      "height", "radius"
+    ]);
+
+    let mut inputs = MesherInputs {
+      shape,
+      region: MesherRegion {
+        position: glam::Vec3A::ZERO,
+        scale:    glam::Vec3A::ONE,
+        detail:   MesherDetail::Subdivs(4),
+        prune:    false,
+        simplify: false,
+      },
+      gen_collider: false,
+      bindings: Default::default(),
+    };
+    let problems = match inputs.validate() {
+      Ok(()) => Vec::new(),
+      Err(report) => report.0.iter().map(ToString::to_string).collect(),
+    };
+    assert_eq!(problems, vec![
+      "Var `height` is unbound",
+      "Var `radius` is unbound"
+    ]);
+
+    inputs.bindings.insert("height".to_string(), 2.0);
+    inputs.bindings.insert("radius".to_string(), 1.0);
+    assert_eq!(inputs.validate(), Ok(()));
+  }
+}